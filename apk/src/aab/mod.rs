@@ -0,0 +1,395 @@
+use crate::aar::Aar;
+use crate::compiler::{FileContents, Symbols, Table};
+use crate::icon::AdaptiveIcon;
+use crate::manifest::AndroidManifest;
+use crate::Target;
+use anyhow::Result;
+use prost::Message;
+use std::path::{Component, Path, PathBuf};
+use xcommon::{Certificate, Zip, ZipFileOptions};
+
+pub mod proto;
+
+const BUNDLETOOL_VERSION: &str = "1.8.0";
+
+/// Writes an android app bundle containing a single `base` module.
+///
+/// The layout mirrors an apk, but the manifest and resource table are
+/// stored as protobuf and every file is placed inside the module
+/// directory. Play generates and signs the apks from it.
+pub struct Aab {
+    manifest: AndroidManifest,
+    zip: Zip,
+    xml_resources: Vec<(String, String)>,
+    adaptive_icon: Option<AdaptiveIcon>,
+    libraries: Vec<Aar>,
+    symbols: Symbols,
+    proguard_rules: String,
+    stable_ids: Symbols,
+}
+
+impl Aab {
+    pub fn new(path: PathBuf, manifest: AndroidManifest, compress: bool) -> Result<Self> {
+        let zip = Zip::new(&path, compress)?;
+        Ok(Self {
+            manifest,
+            zip,
+            xml_resources: vec![],
+            adaptive_icon: None,
+            libraries: vec![],
+            symbols: Symbols::default(),
+            proguard_rules: String::new(),
+            stable_ids: Symbols::default(),
+        })
+    }
+
+    /// Uses an adaptive launcher icon on android 8 and later, the icon
+    /// passed to [`Aab::add_res`] is used on older versions.
+    pub fn set_adaptive_icon(&mut self, icon: AdaptiveIcon) {
+        self.adaptive_icon = Some(icon);
+    }
+
+    /// Keeps the resource ids of a previous build, see
+    /// [`Symbols::parse_stable_ids`]. Has to be called before [`Aab::add_res`].
+    pub fn set_stable_ids(&mut self, ids: Symbols) {
+        self.stable_ids = ids;
+    }
+
+    /// Adds an xml resource like `res/xml/file_paths.xml`. It is compiled
+    /// to protobuf xml by [`Aab::add_res`], which has to be called afterwards.
+    pub fn add_xml_resource(&mut self, path: &str, xml: &str) {
+        self.xml_resources.push((path.to_string(), xml.to_string()));
+    }
+
+    /// Merges the manifest and resources of an android library, see [`Aar`].
+    /// Has to be called before [`Aab::add_res`], resources and manifest
+    /// elements declared by the app take precedence.
+    pub fn add_library(&mut self, aar: Aar) {
+        self.libraries.push(aar);
+    }
+
+    pub fn add_res(
+        &mut self,
+        icon: Option<&Path>,
+        res: Option<&Path>,
+        android: &Path,
+    ) -> Result<()> {
+        let mut table = Table::default();
+        table.import_apk(android)?;
+        let resources = crate::collect_resources(
+            &mut self.manifest,
+            icon,
+            self.adaptive_icon.as_ref(),
+            res,
+            std::mem::take(&mut self.xml_resources),
+            &self.libraries,
+            &self.stable_ids,
+        )?;
+        for (path, png) in resources.pngs() {
+            self.zip.create_file(
+                &module_path(Path::new(path)),
+                ZipFileOptions::Aligned(4),
+                png,
+            )?;
+        }
+        let chunk = resources.compile(&mut table)?;
+        let resource_table = crate::compiler::compile_table(&chunk)?;
+        self.zip.create_file(
+            &module_path(Path::new("resources.pb")),
+            ZipFileOptions::Compressed,
+            &resource_table.encode_to_vec(),
+        )?;
+        for (path, contents) in resources.files() {
+            let dest = module_path(Path::new(path));
+            match contents {
+                FileContents::Xml(xml) => {
+                    let xml = crate::compiler::compile_xml_proto(xml, &table)
+                        .map_err(|err| anyhow::anyhow!("{}: {}", path, err))?;
+                    self.zip.create_file(
+                        &dest,
+                        ZipFileOptions::Compressed,
+                        &xml.encode_to_vec(),
+                    )?;
+                }
+                FileContents::Raw(source) => {
+                    self.zip
+                        .add_file(source, &dest, ZipFileOptions::Aligned(4))?;
+                }
+            }
+        }
+        self.symbols = resources.symbols(&table)?;
+        let manifest = crate::aar::merge_manifests(&self.manifest, &self.libraries)?;
+        let package = self.manifest.package.as_deref().unwrap_or_default();
+        self.proguard_rules = crate::proguard::keep_rules(package, &manifest, resources.files())?;
+        let manifest = crate::compiler::compile_xml_proto(&manifest, &table)?;
+        self.zip.create_file(
+            &module_path(&Path::new("manifest").join("AndroidManifest.xml")),
+            ZipFileOptions::Compressed,
+            &manifest.encode_to_vec(),
+        )?;
+        Ok(())
+    }
+
+    /// Returns the resource ids for generating `R` classes, which are known
+    /// once [`Aab::add_res`] was called.
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// Returns keep rules for the classes referenced by the manifest and the
+    /// xml resources, which are known once [`Aab::add_res`] was called.
+    pub fn proguard_rules(&self) -> &str {
+        &self.proguard_rules
+    }
+
+    /// Adds a dex file using its file name, multidex apps add `classes.dex`,
+    /// `classes2.dex` and so on.
+    pub fn add_dex(&mut self, dex: &Path) -> Result<()> {
+        let name = crate::dex_name(dex)?;
+        self.zip.add_file(
+            dex,
+            &module_path(&Path::new("dex").join(name)),
+            ZipFileOptions::Compressed,
+        )?;
+        Ok(())
+    }
+
+    pub fn add_lib(&mut self, target: Target, path: &Path) -> Result<()> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("invalid path"))?
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("invalid path"))?;
+        self.zip.add_file(
+            path,
+            &module_path(&Path::new("lib").join(target.android_abi()).join(name)),
+            ZipFileOptions::Compressed,
+        )
+    }
+
+    /// Adds a file using the path it would have inside an apk.
+    pub fn add_file(&mut self, source: &Path, dest: &Path, opts: ZipFileOptions) -> Result<()> {
+        self.zip.add_file(source, &module_path(dest), opts)
+    }
+
+    /// Adds a directory using the path it would have inside an apk.
+    pub fn add_directory(&mut self, source: &Path, dest: &Path) -> Result<()> {
+        self.zip.add_directory(source, &module_path(dest))
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        let config = proto::BundleConfig {
+            bundletool: Some(proto::Bundletool {
+                version: BUNDLETOOL_VERSION.into(),
+            }),
+            compression: None,
+        };
+        self.zip.create_file(
+            Path::new("BundleConfig.pb"),
+            ZipFileOptions::Compressed,
+            &config.encode_to_vec(),
+        )?;
+        self.zip.finish()
+    }
+
+    /// Returns the manifest of the `base` module as xml.
+    pub fn manifest(path: &Path) -> Result<String> {
+        let manifest = xcommon::extract_zip_file(path, "base/manifest/AndroidManifest.xml")?;
+        let node = proto::XmlNode::decode(&*manifest)?;
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        format_xml(&node, &[], 0, &mut xml);
+        Ok(xml)
+    }
+
+    /// Verifies the jar signature and returns the certificates of the signers.
+    pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
+        crate::jar::verify(path)
+    }
+}
+
+fn format_xml(
+    node: &proto::XmlNode,
+    namespaces: &[proto::XmlNamespace],
+    depth: usize,
+    xml: &mut String,
+) {
+    let element = match &node.node {
+        Some(proto::xml_node::Node::Element(element)) => element,
+        Some(proto::xml_node::Node::Text(text)) => {
+            if !text.trim().is_empty() {
+                xml.push_str(&"    ".repeat(depth));
+                xml.push_str(&crate::res::escape(text.trim()));
+                xml.push('\n');
+            }
+            return;
+        }
+        None => return,
+    };
+    let namespaces = namespaces
+        .iter()
+        .chain(&element.namespace_declaration)
+        .cloned()
+        .collect::<Vec<_>>();
+    let prefixed = |uri: &str, name: &str| match namespaces
+        .iter()
+        .rev()
+        .find(|ns| ns.uri == uri && !uri.is_empty())
+    {
+        Some(ns) if !ns.prefix.is_empty() => format!("{}:{}", ns.prefix, name),
+        _ => name.to_string(),
+    };
+    let name = prefixed(&element.namespace_uri, &element.name);
+    xml.push_str(&"    ".repeat(depth));
+    xml.push('<');
+    xml.push_str(&name);
+    for ns in &element.namespace_declaration {
+        if ns.prefix.is_empty() {
+            xml.push_str(&format!(" xmlns=\"{}\"", crate::res::escape(&ns.uri)));
+        } else {
+            xml.push_str(&format!(
+                " xmlns:{}=\"{}\"",
+                ns.prefix,
+                crate::res::escape(&ns.uri)
+            ));
+        }
+    }
+    for attr in &element.attribute {
+        let name = prefixed(&attr.namespace_uri, &attr.name);
+        xml.push_str(&format!(
+            " {}=\"{}\"",
+            name,
+            crate::res::escape(&attr.value)
+        ));
+    }
+    if element.child.is_empty() {
+        xml.push_str(" />\n");
+        return;
+    }
+    xml.push_str(">\n");
+    for child in &element.child {
+        format_xml(child, &namespaces, depth + 1, xml);
+    }
+    xml.push_str(&"    ".repeat(depth));
+    xml.push_str(&format!("</{}>\n", name));
+}
+
+/// Maps a path inside an apk to the path inside the `base` module.
+fn module_path(dest: &Path) -> PathBuf {
+    let base = Path::new("base");
+    let first = dest.components().next();
+    match first {
+        Some(Component::Normal(dir))
+            if dest.components().count() > 1
+                && matches!(
+                    dir.to_str(),
+                    Some("assets" | "dex" | "lib" | "manifest" | "res")
+                ) =>
+        {
+            base.join(dest)
+        }
+        _ if dest == Path::new("resources.pb") => base.join(dest),
+        _ => base.join("root").join(dest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_xml() {
+        let android = "http://schemas.android.com/apk/res/android";
+        let element = |name: &str, attribute, child| proto::XmlNode {
+            node: Some(proto::xml_node::Node::Element(proto::XmlElement {
+                name: name.into(),
+                attribute,
+                child,
+                ..Default::default()
+            })),
+        };
+        let mut manifest = element(
+            "manifest",
+            vec![proto::XmlAttribute {
+                name: "package".into(),
+                value: "com.example.helloworld".into(),
+                ..Default::default()
+            }],
+            vec![element(
+                "application",
+                vec![proto::XmlAttribute {
+                    namespace_uri: android.into(),
+                    name: "label".into(),
+                    value: "@string/app_name".into(),
+                    ..Default::default()
+                }],
+                vec![],
+            )],
+        );
+        if let Some(proto::xml_node::Node::Element(element)) = &mut manifest.node {
+            element.namespace_declaration.push(proto::XmlNamespace {
+                prefix: "android".into(),
+                uri: android.into(),
+            });
+        }
+        let mut xml = String::new();
+        format_xml(&manifest, &[], 0, &mut xml);
+        assert_eq!(
+            xml,
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.helloworld">
+    <application android:label="@string/app_name" />
+</manifest>
+"#
+        );
+    }
+
+    #[test]
+    fn test_module_path() {
+        let cases = [
+            (
+                "assets/flutter_assets/kernel_blob.bin",
+                "base/assets/flutter_assets/kernel_blob.bin",
+            ),
+            ("lib/arm64-v8a/libapp.so", "base/lib/arm64-v8a/libapp.so"),
+            (
+                "manifest/AndroidManifest.xml",
+                "base/manifest/AndroidManifest.xml",
+            ),
+            ("res/mipmap/icon48.png", "base/res/mipmap/icon48.png"),
+            ("resources.pb", "base/resources.pb"),
+            ("lib", "base/root/lib"),
+            ("META-INF/services/foo", "base/root/META-INF/services/foo"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(module_path(Path::new(path)), Path::new(expected));
+        }
+    }
+
+    #[test]
+    fn test_add_dex() -> Result<()> {
+        let dir = std::env::temp_dir().join("test_aab_add_dex");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("test.aab");
+        let mut aab = Aab::new(path.clone(), Default::default(), true)?;
+        for name in ["classes.dex", "classes2.dex"] {
+            let dex = dir.join(name);
+            std::fs::write(&dex, name)?;
+            aab.add_dex(&dex)?;
+        }
+        aab.finish()?;
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+        let mut names = zip.file_names().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "BundleConfig.pb",
+                "base/dex/classes.dex",
+                "base/dex/classes2.dex"
+            ]
+        );
+        let mut dex = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("base/dex/classes2.dex")?, &mut dex)?;
+        assert_eq!(dex, "classes2.dex");
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+//! Subset of the aapt2 `Resources.proto`/`Configuration.proto` and the
+//! bundletool `config.proto` messages needed to write app bundles.
+//!
+//! Field tags have to match the upstream definitions, message names only
+//! matter for readability.
+use prost::{Enumeration, Message, Oneof};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ResourceTable {
+    #[prost(message, repeated, tag = "2")]
+    pub package: Vec<Package>,
+    #[prost(message, repeated, tag = "4")]
+    pub tool_fingerprint: Vec<ToolFingerprint>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ToolFingerprint {
+    #[prost(string, tag = "1")]
+    pub tool: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PackageId {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Package {
+    #[prost(message, optional, tag = "1")]
+    pub package_id: Option<PackageId>,
+    #[prost(string, tag = "2")]
+    pub package_name: String,
+    #[prost(message, repeated, tag = "3")]
+    pub r#type: Vec<Type>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TypeId {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Type {
+    #[prost(message, optional, tag = "1")]
+    pub type_id: Option<TypeId>,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(message, repeated, tag = "3")]
+    pub entry: Vec<Entry>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct EntryId {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Visibility {
+    #[prost(enumeration = "visibility::Level", tag = "1")]
+    pub level: i32,
+}
+
+pub mod visibility {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Eq, Enumeration, Hash, Ord, PartialEq, PartialOrd)]
+    #[repr(i32)]
+    pub enum Level {
+        Unknown = 0,
+        Private = 1,
+        Public = 2,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Entry {
+    #[prost(message, optional, tag = "1")]
+    pub entry_id: Option<EntryId>,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(message, optional, tag = "3")]
+    pub visibility: Option<Visibility>,
+    #[prost(message, repeated, tag = "6")]
+    pub config_value: Vec<ConfigValue>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ConfigValue {
+    #[prost(message, optional, tag = "1")]
+    pub config: Option<Configuration>,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<Value>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Value {
+    #[prost(string, tag = "2")]
+    pub comment: String,
+    #[prost(bool, tag = "3")]
+    pub weak: bool,
+    #[prost(oneof = "value::Value", tags = "4, 5")]
+    pub value: Option<value::Value>,
+}
+
+pub mod value {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "4")]
+        Item(super::Item),
+        #[prost(message, tag = "5")]
+        CompoundValue(super::CompoundValue),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Item {
+    #[prost(oneof = "item::Value", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub value: Option<item::Value>,
+}
+
+pub mod item {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "1")]
+        Ref(super::Reference),
+        #[prost(message, tag = "2")]
+        Str(super::Str),
+        #[prost(message, tag = "3")]
+        RawStr(super::Str),
+        #[prost(message, tag = "4")]
+        StyledStr(super::Str),
+        #[prost(message, tag = "5")]
+        File(super::FileReference),
+        #[prost(message, tag = "6")]
+        Id(super::Id),
+        #[prost(message, tag = "7")]
+        Prim(super::Primitive),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CompoundValue {
+    #[prost(oneof = "compound_value::Value", tags = "2, 4")]
+    pub value: Option<compound_value::Value>,
+}
+
+pub mod compound_value {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "2")]
+        Style(super::Style),
+        #[prost(message, tag = "4")]
+        Array(super::Array),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Reference {
+    #[prost(enumeration = "reference::Type", tag = "1")]
+    pub r#type: i32,
+    #[prost(uint32, tag = "2")]
+    pub id: u32,
+    #[prost(string, tag = "3")]
+    pub name: String,
+}
+
+pub mod reference {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Eq, Enumeration, Hash, Ord, PartialEq, PartialOrd)]
+    #[repr(i32)]
+    pub enum Type {
+        Reference = 0,
+        Attribute = 1,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Id {}
+
+/// The `String`, `RawString` and `StyledString` messages share the layout
+/// of their first field.
+#[derive(Clone, PartialEq, Message)]
+pub struct Str {
+    #[prost(string, tag = "1")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FileReference {
+    #[prost(string, tag = "1")]
+    pub path: String,
+    #[prost(enumeration = "file_reference::Type", tag = "2")]
+    pub r#type: i32,
+}
+
+pub mod file_reference {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Eq, Enumeration, Hash, Ord, PartialEq, PartialOrd)]
+    #[repr(i32)]
+    pub enum Type {
+        Unknown = 0,
+        Png = 1,
+        BinaryXml = 2,
+        ProtoXml = 3,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Primitive {
+    #[prost(
+        oneof = "primitive::Value",
+        tags = "1, 2, 3, 13, 14, 6, 7, 8, 9, 10, 11, 12"
+    )]
+    pub value: Option<primitive::Value>,
+}
+
+pub mod primitive {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct NullType {}
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct EmptyType {}
+
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum Value {
+        #[prost(message, tag = "1")]
+        NullValue(NullType),
+        #[prost(message, tag = "2")]
+        EmptyValue(EmptyType),
+        #[prost(float, tag = "3")]
+        FloatValue(f32),
+        #[prost(uint32, tag = "13")]
+        DimensionValue(u32),
+        #[prost(uint32, tag = "14")]
+        FractionValue(u32),
+        #[prost(int32, tag = "6")]
+        IntDecimalValue(i32),
+        #[prost(uint32, tag = "7")]
+        IntHexadecimalValue(u32),
+        #[prost(bool, tag = "8")]
+        BooleanValue(bool),
+        #[prost(uint32, tag = "9")]
+        ColorArgb8Value(u32),
+        #[prost(uint32, tag = "10")]
+        ColorRgb8Value(u32),
+        #[prost(uint32, tag = "11")]
+        ColorArgb4Value(u32),
+        #[prost(uint32, tag = "12")]
+        ColorRgb4Value(u32),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Style {
+    #[prost(message, optional, tag = "1")]
+    pub parent: Option<Reference>,
+    #[prost(message, repeated, tag = "3")]
+    pub entry: Vec<style::Entry>,
+}
+
+pub mod style {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Entry {
+        #[prost(message, optional, tag = "3")]
+        pub key: Option<super::Reference>,
+        #[prost(message, optional, tag = "4")]
+        pub item: Option<super::Item>,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Array {
+    #[prost(message, repeated, tag = "1")]
+    pub element: Vec<array::Element>,
+}
+
+pub mod array {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Element {
+        #[prost(message, optional, tag = "3")]
+        pub item: Option<super::Item>,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Configuration {
+    #[prost(uint32, tag = "1")]
+    pub mcc: u32,
+    #[prost(uint32, tag = "2")]
+    pub mnc: u32,
+    #[prost(string, tag = "3")]
+    pub locale: String,
+    #[prost(int32, tag = "4")]
+    pub layout_direction: i32,
+    #[prost(uint32, tag = "5")]
+    pub screen_width: u32,
+    #[prost(uint32, tag = "6")]
+    pub screen_height: u32,
+    #[prost(uint32, tag = "7")]
+    pub screen_width_dp: u32,
+    #[prost(uint32, tag = "8")]
+    pub screen_height_dp: u32,
+    #[prost(uint32, tag = "9")]
+    pub smallest_screen_width_dp: u32,
+    #[prost(int32, tag = "10")]
+    pub screen_layout_size: i32,
+    #[prost(int32, tag = "11")]
+    pub screen_layout_long: i32,
+    #[prost(int32, tag = "12")]
+    pub screen_round: i32,
+    #[prost(int32, tag = "13")]
+    pub wide_color_gamut: i32,
+    #[prost(int32, tag = "14")]
+    pub hdr: i32,
+    #[prost(int32, tag = "15")]
+    pub orientation: i32,
+    #[prost(int32, tag = "16")]
+    pub ui_mode_type: i32,
+    #[prost(int32, tag = "17")]
+    pub ui_mode_night: i32,
+    #[prost(uint32, tag = "18")]
+    pub density: u32,
+    #[prost(int32, tag = "19")]
+    pub touchscreen: i32,
+    #[prost(int32, tag = "20")]
+    pub keys_hidden: i32,
+    #[prost(int32, tag = "21")]
+    pub keyboard: i32,
+    #[prost(int32, tag = "22")]
+    pub nav_hidden: i32,
+    #[prost(int32, tag = "23")]
+    pub navigation: i32,
+    #[prost(uint32, tag = "24")]
+    pub sdk_version: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct XmlNode {
+    #[prost(oneof = "xml_node::Node", tags = "1, 2")]
+    pub node: Option<xml_node::Node>,
+}
+
+pub mod xml_node {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Oneof)]
+    pub enum Node {
+        #[prost(message, tag = "1")]
+        Element(super::XmlElement),
+        #[prost(string, tag = "2")]
+        Text(String),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct XmlElement {
+    #[prost(message, repeated, tag = "1")]
+    pub namespace_declaration: Vec<XmlNamespace>,
+    #[prost(string, tag = "2")]
+    pub namespace_uri: String,
+    #[prost(string, tag = "3")]
+    pub name: String,
+    #[prost(message, repeated, tag = "4")]
+    pub attribute: Vec<XmlAttribute>,
+    #[prost(message, repeated, tag = "5")]
+    pub child: Vec<XmlNode>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct XmlNamespace {
+    #[prost(string, tag = "1")]
+    pub prefix: String,
+    #[prost(string, tag = "2")]
+    pub uri: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct XmlAttribute {
+    #[prost(string, tag = "1")]
+    pub namespace_uri: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(string, tag = "3")]
+    pub value: String,
+    #[prost(uint32, tag = "5")]
+    pub resource_id: u32,
+    #[prost(message, optional, tag = "6")]
+    pub compiled_item: Option<Item>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BundleConfig {
+    #[prost(message, optional, tag = "1")]
+    pub bundletool: Option<Bundletool>,
+    #[prost(message, optional, tag = "3")]
+    pub compression: Option<Compression>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Bundletool {
+    #[prost(string, tag = "2")]
+    pub version: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Compression {
+    #[prost(string, repeated, tag = "1")]
+    pub uncompressed_glob: Vec<String>,
+}
@@ -0,0 +1,356 @@
+//! Android libraries, which are distributed as `.aar` files containing
+//! compiled classes, resources, native libraries and a manifest that is
+//! merged into the manifest of the app.
+use crate::manifest::AndroidManifest;
+use crate::Target;
+use anyhow::Result;
+use roxmltree::{Document, Node};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+const NS_ANDROID: &str = "http://schemas.android.com/apk/res/android";
+
+/// Elements of the library `<manifest>` which are merged.
+const MANIFEST_ELEMENTS: [&str; 5] = [
+    "permission",
+    "queries",
+    "uses-feature",
+    "uses-permission",
+    "uses-permission-sdk-23",
+];
+
+/// Elements of the library `<application>` which are merged.
+const APPLICATION_ELEMENTS: [&str; 7] = [
+    "activity",
+    "activity-alias",
+    "meta-data",
+    "provider",
+    "receiver",
+    "service",
+    "uses-library",
+];
+
+/// Elements whose `android:name` is a class.
+const COMPONENTS: [&str; 5] = ["activity", "application", "provider", "receiver", "service"];
+
+/// An unpacked android library.
+#[derive(Clone, Debug)]
+pub struct Aar {
+    dir: PathBuf,
+    package: String,
+}
+
+impl Aar {
+    /// Extracts an aar into `dir`, unless it was extracted before.
+    pub fn extract(aar: &Path, dir: &Path) -> Result<Self> {
+        if !dir.exists() {
+            let mut zip = ZipArchive::new(BufReader::new(File::open(aar)?))?;
+            if let Err(err) = zip.extract(dir) {
+                std::fs::remove_dir_all(dir).ok();
+                anyhow::bail!("failed to extract {}: {}", aar.display(), err);
+            }
+        }
+        Self::open(dir)
+    }
+
+    /// Opens an unpacked aar.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let manifest = std::fs::read_to_string(dir.join("AndroidManifest.xml"))?;
+        let doc = Document::parse(&manifest)?;
+        let package = doc
+            .root_element()
+            .attribute("package")
+            .ok_or_else(|| anyhow::anyhow!("{} is missing a package", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            package: package.to_string(),
+        })
+    }
+
+    /// Java package of the library, which is also the package of its `R` class.
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    pub fn manifest(&self) -> PathBuf {
+        self.dir.join("AndroidManifest.xml")
+    }
+
+    /// Returns `classes.jar` and the jars in `libs`.
+    pub fn jars(&self) -> Result<Vec<PathBuf>> {
+        let mut jars = vec![];
+        let classes = self.dir.join("classes.jar");
+        if classes.exists() {
+            jars.push(classes);
+        }
+        jars.extend(list_files(&self.dir.join("libs"), "jar")?);
+        Ok(jars)
+    }
+
+    /// Returns the `res` directory.
+    pub fn res(&self) -> Option<PathBuf> {
+        let res = self.dir.join("res");
+        if res.exists() {
+            Some(res)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the native libraries of `target` in `jni/<abi>`.
+    pub fn libs(&self, target: Target) -> Result<Vec<PathBuf>> {
+        list_files(&self.dir.join("jni").join(target.android_abi()), "so")
+    }
+
+    /// Returns the consumer proguard rules of the library and rules keeping
+    /// the components declared by its manifest.
+    pub fn proguard_rules(&self) -> Result<String> {
+        let mut rules = String::new();
+        let manifest = std::fs::read_to_string(self.manifest())?;
+        let doc = Document::parse(&manifest)?;
+        for node in doc.descendants() {
+            if !COMPONENTS.contains(&node.tag_name().name()) {
+                continue;
+            }
+            if let Some(name) = node.attribute((NS_ANDROID, "name")) {
+                let name = class_name(&self.package, name);
+                rules.push_str(&format!("-keep class {} {{ <init>(); }}\n", name));
+            }
+        }
+        let proguard = self.dir.join("proguard.txt");
+        if proguard.exists() {
+            rules.push_str(&std::fs::read_to_string(proguard)?);
+            rules.push('\n');
+        }
+        Ok(rules)
+    }
+}
+
+fn list_files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    if dir.exists() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() == Some(extension.as_ref()) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Resolves class names like `.MainActivity` relative to `package`.
+pub(crate) fn class_name(package: &str, name: &str) -> String {
+    if name.starts_with('.') {
+        format!("{}{}", package, name)
+    } else if !name.contains('.') {
+        format!("{}.{}", package, name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns the manifest of the app with the permissions and components of
+/// the libraries merged into it. Elements declared by the app or a previous
+/// library take precedence.
+pub(crate) fn merge_manifests(manifest: &AndroidManifest, libraries: &[Aar]) -> Result<String> {
+    let app = quick_xml::se::to_string(manifest)?;
+    if libraries.is_empty() {
+        return Ok(app);
+    }
+    let mut sources = vec![];
+    for library in libraries {
+        sources.push((
+            library.package.as_str(),
+            std::fs::read_to_string(library.manifest())?,
+        ));
+    }
+    let docs = sources
+        .iter()
+        .map(|(package, xml)| Ok((*package, Document::parse(xml)?)))
+        .collect::<Result<Vec<_>>>()?;
+    merge(&app, &docs)
+}
+
+fn merge(app: &str, libraries: &[(&str, Document)]) -> Result<String> {
+    let doc = Document::parse(app)?;
+    let root = doc.root_element();
+    let app_package = root.attribute("package").unwrap_or_default();
+    let key = |package: &str, node: Node| {
+        let name = node.attribute((NS_ANDROID, "name")).map(|name| {
+            if COMPONENTS.contains(&node.tag_name().name()) {
+                class_name(package, name)
+            } else {
+                name.to_string()
+            }
+        });
+        (node.tag_name().name().to_string(), name)
+    };
+    let mut declared = BTreeSet::new();
+    for node in root.children().filter(|node| node.is_element()) {
+        declared.insert(key(app_package, node));
+        if node.has_tag_name("application") {
+            for node in node.children().filter(|node| node.is_element()) {
+                declared.insert(key(app_package, node));
+            }
+        }
+    }
+    let mut manifest_elements = vec![];
+    let mut application_elements = vec![];
+    for (package, doc) in libraries {
+        let mut merge = |node: Node<'_, '_>, depth: usize, elements: &mut Vec<_>| {
+            let key = key(package, node);
+            // elements without a name like `<queries>` are always merged
+            if key.1.is_none() || declared.insert(key) {
+                let mut xml = String::new();
+                write_element(node, package, app_package, depth, &mut xml);
+                elements.push(xml);
+            }
+        };
+        for node in doc.root_element().children() {
+            let tag = node.tag_name().name();
+            if MANIFEST_ELEMENTS.contains(&tag) {
+                merge(node, 1, &mut manifest_elements);
+            } else if tag == "application" {
+                for node in node.children() {
+                    if APPLICATION_ELEMENTS.contains(&node.tag_name().name()) {
+                        merge(node, 2, &mut application_elements);
+                    }
+                }
+            }
+        }
+    }
+    let mut xml = String::new();
+    xml.push_str("<manifest");
+    xml.push_str(&format!(" xmlns:android=\"{}\"", NS_ANDROID));
+    write_attributes(root, app_package, app_package, &mut xml);
+    xml.push_str(">\n");
+    for node in root.children().filter(|node| node.is_element()) {
+        if !node.has_tag_name("application") {
+            write_element(node, app_package, app_package, 1, &mut xml);
+            continue;
+        }
+        for element in manifest_elements.drain(..) {
+            xml.push_str(&element);
+        }
+        xml.push_str("  <application");
+        write_attributes(node, app_package, app_package, &mut xml);
+        xml.push_str(">\n");
+        for node in node.children().filter(|node| node.is_element()) {
+            write_element(node, app_package, app_package, 2, &mut xml);
+        }
+        for element in application_elements.drain(..) {
+            xml.push_str(&element);
+        }
+        xml.push_str("  </application>\n");
+    }
+    for element in manifest_elements {
+        xml.push_str(&element);
+    }
+    xml.push_str("</manifest>\n");
+    Ok(xml)
+}
+
+fn write_element(node: Node, package: &str, app_package: &str, depth: usize, xml: &mut String) {
+    if !node.is_element() {
+        return;
+    }
+    let indent = "  ".repeat(depth);
+    let tag = node.tag_name().name();
+    xml.push_str(&format!("{}<{}", indent, tag));
+    write_attributes(node, package, app_package, xml);
+    let children = node.children().filter(|node| node.is_element());
+    if children.clone().next().is_none() {
+        xml.push_str(" />\n");
+        return;
+    }
+    xml.push_str(">\n");
+    for child in children {
+        write_element(child, package, app_package, depth + 1, xml);
+    }
+    xml.push_str(&format!("{}</{}>\n", indent, tag));
+}
+
+/// Writes the attributes in the android namespace and attributes without a
+/// namespace, tool attributes like `tools:ignore` are dropped. Relative class
+/// names are resolved and the `${applicationId}` placeholder is replaced.
+fn write_attributes(node: Node, package: &str, app_package: &str, xml: &mut String) {
+    for attr in node.attributes() {
+        let name = match attr.namespace() {
+            Some(NS_ANDROID) => format!("android:{}", attr.name()),
+            Some(_) => continue,
+            None => attr.name().to_string(),
+        };
+        let is_class = match name.as_str() {
+            "android:name" => COMPONENTS.contains(&node.tag_name().name()),
+            "android:targetActivity" => true,
+            _ => false,
+        };
+        let value = if is_class {
+            class_name(package, attr.value())
+        } else {
+            attr.value().replace("${applicationId}", app_package)
+        };
+        xml.push_str(&format!(" {}=\"{}\"", name, crate::res::escape(&value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_manifests() -> Result<()> {
+        let app = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.helloworld">
+            <uses-permission android:name="android.permission.INTERNET" />
+            <application android:label="helloworld">
+                <activity android:name="android.app.NativeActivity" />
+            </application>
+        </manifest>"#;
+        let library = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" xmlns:tools="http://schemas.android.com/tools" package="androidx.startup">
+            <uses-sdk android:minSdkVersion="14" />
+            <uses-permission android:name="android.permission.INTERNET" />
+            <uses-permission android:name="android.permission.WAKE_LOCK" />
+            <application>
+                <provider
+                    android:name=".InitializationProvider"
+                    android:authorities="${applicationId}.androidx-startup"
+                    android:exported="false"
+                    tools:node="merge">
+                    <meta-data android:name="androidx.emoji2.text.EmojiCompatInitializer" android:value="androidx.startup" />
+                </provider>
+            </application>
+        </manifest>"#;
+        let library = Document::parse(library)?;
+        let xml = merge(app, &[("androidx.startup", library)])?;
+        assert_eq!(
+            xml,
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.helloworld">
+  <uses-permission android:name="android.permission.INTERNET" />
+  <uses-permission android:name="android.permission.WAKE_LOCK" />
+  <application android:label="helloworld">
+    <activity android:name="android.app.NativeActivity" />
+    <provider android:name="androidx.startup.InitializationProvider" android:authorities="com.example.helloworld.androidx-startup" android:exported="false">
+      <meta-data android:name="androidx.emoji2.text.EmojiCompatInitializer" android:value="androidx.startup" />
+    </provider>
+  </application>
+</manifest>
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_class_name() {
+        assert_eq!(class_name("androidx.core", ".Foo"), "androidx.core.Foo");
+        assert_eq!(class_name("androidx.core", "Foo"), "androidx.core.Foo");
+        assert_eq!(
+            class_name("androidx.core", "android.app.Foo"),
+            "android.app.Foo"
+        );
+    }
+}
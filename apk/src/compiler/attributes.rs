@@ -1,41 +1,92 @@
-use crate::compiler::table::{Ref, Table};
+use crate::compiler::table::{Entry, Ref, Table};
+use crate::compiler::values;
 use crate::res::{ResAttributeType, ResValue, ResValueType};
 use anyhow::Result;
 use roxmltree::Attribute;
 use std::collections::{BTreeMap, BTreeSet};
 
-pub fn compile_attr(table: &Table, name: &str, value: &str, strings: &Strings) -> Result<ResValue> {
-    let entry = table.entry_by_ref(Ref::attr(name))?;
-    let attr_type = entry.attribute_type().unwrap();
-    let (data, data_type) = match attr_type {
-        ResAttributeType::Reference => {
+const NS_ANDROID: &str = "http://schemas.android.com/apk/res/android";
+const NS_RES_AUTO: &str = "http://schemas.android.com/apk/res-auto";
+
+/// Returns the package declaring the attributes of an xml namespace,
+/// `Some(None)` for attributes declared by the app or its libraries.
+pub fn attr_package(namespace: Option<&str>) -> Option<Option<&'static str>> {
+    match namespace? {
+        NS_ANDROID => Some(Some("android")),
+        NS_RES_AUTO => Some(None),
+        _ => None,
+    }
+}
+
+pub fn compile_attr(
+    table: &Table,
+    package: Option<&str>,
+    name: &str,
+    value: &str,
+    strings: &Strings,
+) -> Result<ResValue> {
+    if value == "@null" || value == "@empty" {
+        return Ok(ResValue {
+            size: 8,
+            res0: 0,
+            data_type: ResValueType::Null as u8,
+            data: (value == "@empty") as u32,
+        });
+    }
+    if value.starts_with('?') {
+        let id = table.entry_by_ref(Ref::parse_attr(value)?)?.id();
+        return Ok(ResValue {
+            size: 8,
+            res0: 0,
+            data_type: ResValueType::Attribute as u8,
+            data: u32::from(id),
+        });
+    }
+    if value.starts_with('@') {
+        // string attributes like `android:label` accept references too
+        let id = table.entry_by_ref(Ref::parse(value)?)?.id();
+        return Ok(ResValue {
+            size: 8,
+            res0: 0,
+            data_type: ResValueType::Reference as u8,
+            data: u32::from(id),
+        });
+    }
+    let entry = table.entry_by_ref(Ref::new(package, "attr", name))?;
+    let (data, data_type) = match entry.attribute_type() {
+        Some(ResAttributeType::Reference) => {
             let id = table.entry_by_ref(Ref::parse(value)?)?.id();
             (u32::from(id), ResValueType::Reference)
         }
-        ResAttributeType::String => (strings.id(value) as u32, ResValueType::String),
-        ResAttributeType::Integer => (value.parse()?, ResValueType::IntDec),
-        ResAttributeType::Boolean => match value {
+        Some(ResAttributeType::String) => (strings.id(value) as u32, ResValueType::String),
+        Some(ResAttributeType::Integer) => (value.parse()?, ResValueType::IntDec),
+        Some(ResAttributeType::Boolean) => match value {
             "true" => (0xffff_ffff, ResValueType::IntBoolean),
             "false" => (0x0000_0000, ResValueType::IntBoolean),
             _ => anyhow::bail!("expected boolean"),
         },
-        ResAttributeType::Enum => {
-            let id = table.entry_by_ref(Ref::id(value))?.id();
-            let value = entry.lookup_value(id).unwrap();
+        Some(ResAttributeType::Enum) => {
+            let id = table.entry_by_ref(Ref::new(package, "id", value))?.id();
+            let value = entry
+                .lookup_value(id)
+                .ok_or_else(|| anyhow::anyhow!("invalid value {} for attribute {}", value, name))?;
             (value.data, ResValueType::from_u8(value.data_type).unwrap())
         }
-        ResAttributeType::Flags => {
+        Some(ResAttributeType::Flags) => {
             let mut data = 0;
             let mut data_type = ResValueType::Null;
             for flag in value.split('|') {
-                let id = table.entry_by_ref(Ref::id(flag))?.id();
-                let value = entry.lookup_value(id).unwrap();
+                let id = table.entry_by_ref(Ref::new(package, "id", flag))?.id();
+                let value = entry.lookup_value(id).ok_or_else(|| {
+                    anyhow::anyhow!("invalid value {} for attribute {}", value, name)
+                })?;
                 data |= value.data;
                 data_type = ResValueType::from_u8(value.data_type).unwrap();
             }
             (data, data_type)
         }
-        _ => anyhow::bail!("unsupported attribute type"),
+        _ => compile_any(table, package, entry, value, strings)
+            .ok_or_else(|| anyhow::anyhow!("invalid value {} for attribute {}", value, name))?,
     };
     Ok(ResValue {
         size: 8,
@@ -45,6 +96,66 @@ pub fn compile_attr(table: &Table, name: &str, value: &str, strings: &Strings) -
     })
 }
 
+/// Compiles the value of an attribute accepting multiple formats like
+/// `layout_width`, which is either a dimension or an enum.
+fn compile_any(
+    table: &Table,
+    package: Option<&str>,
+    entry: Entry,
+    value: &str,
+    strings: &Strings,
+) -> Option<(u32, ResValueType)> {
+    let format = entry.attribute_format()?;
+    let accepts = |ty: ResAttributeType| format & ty as u32 != 0;
+    if accepts(ResAttributeType::Enum) || accepts(ResAttributeType::Flags) {
+        let flags = value
+            .split('|')
+            .map(|flag| {
+                let id = table.entry_by_ref(Ref::new(package, "id", flag)).ok()?.id();
+                entry.lookup_value(id)
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(flags) = flags {
+            let data = flags.iter().fold(0, |data, flag| data | flag.data);
+            let data_type = ResValueType::from_u8(flags.last()?.data_type)?;
+            return Some((data, data_type));
+        }
+    }
+    if accepts(ResAttributeType::Color) {
+        if let Some(color) = values::parse_color(value) {
+            return Some(color);
+        }
+    }
+    if accepts(ResAttributeType::Boolean) {
+        if let Some(data) = values::parse_bool(value) {
+            return Some((data, ResValueType::IntBoolean));
+        }
+    }
+    if accepts(ResAttributeType::Integer) {
+        if let Some(integer) = values::parse_integer(value) {
+            return Some(integer);
+        }
+    }
+    if let Some((data, data_type)) = values::parse_dimension(value) {
+        let ty = match data_type {
+            ResValueType::Fraction => ResAttributeType::Fraction,
+            _ => ResAttributeType::Dimension,
+        };
+        if accepts(ty) {
+            return Some((data, data_type));
+        }
+    }
+    if accepts(ResAttributeType::Float) {
+        if let Ok(float) = value.parse::<f32>() {
+            return Some((float.to_bits(), ResValueType::Float));
+        }
+    }
+    if accepts(ResAttributeType::String) {
+        return Some((strings.id(value) as u32, ResValueType::String));
+    }
+    None
+}
+
 pub struct StringPoolBuilder<'a> {
     table: &'a Table,
     attributes: BTreeMap<u32, &'a str>,
@@ -61,15 +172,22 @@ impl<'a> StringPoolBuilder<'a> {
     }
 
     pub fn add_attribute(&mut self, attr: &'a Attribute<'a>) -> Result<()> {
-        if let Some(ns) = attr.namespace() {
-            if ns == "http://schemas.android.com/apk/res/android" {
-                let entry = self.table.entry_by_ref(Ref::attr(attr.name()))?;
-                self.attributes.insert(entry.id().into(), attr.name());
-                if entry.attribute_type() == Some(ResAttributeType::String) {
-                    self.strings.insert(attr.value());
+        if let Some(package) = attr_package(attr.namespace()) {
+            let entry = self
+                .table
+                .entry_by_ref(Ref::new(package, "attr", attr.name()))?;
+            self.attributes.insert(entry.id().into(), attr.name());
+            let accepts_string = match entry.attribute_type() {
+                Some(ResAttributeType::Any) | None => {
+                    let format = entry.attribute_format().unwrap_or_default();
+                    format & ResAttributeType::String as u32 != 0
                 }
-                return Ok(());
+                Some(ty) => ty == ResAttributeType::String,
+            };
+            if accepts_string && !attr.value().starts_with(['@', '?']) {
+                self.strings.insert(attr.value());
             }
+            return Ok(());
         }
         if attr.name() == "platformBuildVersionCode" || attr.name() == "platformBuildVersionName" {
             self.strings.insert(attr.name());
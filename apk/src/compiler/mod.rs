@@ -1,140 +1,368 @@
 use crate::manifest::AndroidManifest;
-use crate::res::{
-    Chunk, ResTableConfig, ResTableEntry, ResTableHeader, ResTablePackageHeader,
-    ResTableTypeHeader, ResTableTypeSpecHeader, ResTableValue, ResValue, ScreenType,
-};
+use crate::res::Chunk;
 use anyhow::Result;
 
 mod attributes;
+mod proto;
+mod qualifiers;
+mod resources;
+mod symbols;
 mod table;
+mod values;
 mod xml;
 
-pub use table::Table;
+pub use proto::{compile_table, compile_xml as compile_xml_proto};
+pub use qualifiers::density_bucket;
+pub use resources::{FileContents, Resources};
+pub use symbols::Symbols;
+pub use table::{Entry, Table};
+pub use xml::compile_xml;
 
 pub fn compile_manifest(manifest: &AndroidManifest, table: &Table) -> Result<Chunk> {
     let xml = quick_xml::se::to_string(manifest)?;
     xml::compile_xml(&xml, table)
 }
 
-const DPI_SIZE: [u32; 5] = [48, 72, 96, 144, 192];
+/// Densities a mipmap is generated for, 160 is the baseline density.
+const MIPMAP_DENSITIES: [u16; 5] = [160, 240, 320, 480, 640];
 
-fn variants(name: &str) -> impl Iterator<Item = (String, u32)> + '_ {
-    DPI_SIZE
-        .into_iter()
-        .map(move |size| (format!("res/{0}/{0}{1}.png", name, size), size))
-}
-
-pub fn compile_mipmap<'a>(package_name: &str, name: &'a str) -> Result<Mipmap<'a>> {
-    let chunk = Chunk::Table(
-        ResTableHeader { package_count: 1 },
-        vec![
-            Chunk::StringPool(variants(name).map(|(res, _)| res).collect(), vec![]),
-            Chunk::TablePackage(
-                ResTablePackageHeader {
-                    id: 127,
-                    name: package_name.to_string(),
-                    type_strings: 288,
-                    last_public_type: 1,
-                    key_strings: 332,
-                    last_public_key: 1,
-                    type_id_offset: 0,
-                },
-                vec![
-                    Chunk::StringPool(vec!["mipmap".to_string()], vec![]),
-                    Chunk::StringPool(vec!["icon".to_string()], vec![]),
-                    Chunk::TableTypeSpec(
-                        ResTableTypeSpecHeader {
-                            id: 1,
-                            res0: 0,
-                            res1: 0,
-                            entry_count: 1,
-                        },
-                        vec![256],
-                    ),
-                    mipmap_table_type(1, 160, 0),
-                    mipmap_table_type(1, 240, 1),
-                    mipmap_table_type(1, 320, 2),
-                    mipmap_table_type(1, 480, 3),
-                    mipmap_table_type(1, 640, 4),
-                ],
-            ),
-        ],
-    );
-    Ok(Mipmap { name, chunk })
-}
-
-fn mipmap_table_type(type_id: u8, density: u16, string_id: u32) -> Chunk {
-    Chunk::TableType(
-        ResTableTypeHeader {
-            id: type_id,
-            res0: 0,
-            res1: 0,
-            entry_count: 1,
-            entries_start: 88,
-            config: ResTableConfig {
-                size: 28 + 36,
-                imsi: 0,
-                locale: 0,
-                screen_type: ScreenType {
-                    orientation: 0,
-                    touchscreen: 0,
-                    density,
-                },
-                input: 0,
-                screen_size: 0,
-                version: 4,
-                unknown: vec![0; 36],
-            },
-        },
-        vec![0],
-        vec![Some(ResTableEntry {
-            size: 8,
-            flags: 0,
-            key: 0,
-            value: ResTableValue::Simple(ResValue {
-                size: 8,
-                res0: 0,
-                data_type: 3,
-                data: string_id,
-            }),
-        })],
-    )
-}
-
-pub struct Mipmap<'a> {
-    name: &'a str,
-    chunk: Chunk,
-}
-
-impl<'a> Mipmap<'a> {
-    pub fn chunk(&self) -> &Chunk {
-        &self.chunk
-    }
-
-    pub fn variants(&self) -> impl Iterator<Item = (String, u32)> + 'a {
-        variants(self.name)
-    }
+/// Returns the path, density and size in pixels of each variant of a
+/// mipmap which is `size_dp` wide.
+pub fn mipmap_variants(name: &str, size_dp: u32) -> impl Iterator<Item = (String, u16, u32)> + '_ {
+    MIPMAP_DENSITIES.into_iter().map(move |density| {
+        let size = size_dp * density as u32 / 160;
+        (format!("res/{0}/{0}{1}.png", name, size), density, size)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::compiler::table::Ref;
-    use crate::manifest::Activity;
+    use crate::manifest::{Activity, IntentFilter, Provider, Queries, Service, SupportsScreens};
     use std::io::Cursor;
 
     #[test]
     fn test_compile_mipmap() -> Result<()> {
         crate::tests::init_logger();
-        let mipmap = compile_mipmap("com.example.helloworld", "icon")?;
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_mipmap("icon", 48)?;
+        let chunk = resources.compile(&Table::default())?;
         let mut buf = vec![];
         let mut cursor = Cursor::new(&mut buf);
-        mipmap.chunk().write(&mut cursor)?;
+        chunk.write(&mut cursor)?;
         let mut cursor = Cursor::new(&buf);
-        let chunk = Chunk::parse(&mut cursor)?;
-        println!("{:#?}", mipmap.chunk());
+        let parsed = Chunk::parse(&mut cursor)?;
         println!("{:#?}", chunk);
-        assert_eq!(*mipmap.chunk(), chunk);
+        println!("{:#?}", parsed);
+        assert_eq!(chunk, parsed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_values() -> Result<()> {
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_mipmap("icon", 48)?;
+        resources.add_values(
+            Default::default(),
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <resources>
+                <string name="app_name">Hello \"world\"</string>
+                <string name="title">@string/app_name</string>
+                <color name="background">#ff000000</color>
+                <dimen name="margin">16dp</dimen>
+                <bool name="enabled">true</bool>
+                <integer name="count">42</integer>
+                <string-array name="planets">
+                    <item>Mercury</item>
+                    <item>Venus</item>
+                </string-array>
+                <style name="LaunchTheme" parent="" />
+                <style name="LaunchTheme.Dark" />
+            </resources>"#,
+        )?;
+        resources.add_values(
+            "de".parse()?,
+            r#"<resources><string name="app_name">Hallo Welt</string></resources>"#,
+        )?;
+        let chunk = resources.compile(&Table::default())?;
+        let mut buf = vec![];
+        chunk.write(&mut Cursor::new(&mut buf))?;
+        assert_eq!(chunk, Chunk::parse(&mut Cursor::new(&buf))?);
+
+        let mut table = Table::default();
+        table.import_chunk(&chunk);
+        let app_name = table.entry_by_ref(Ref::parse("@string/app_name")?)?;
+        assert_eq!(u32::from(app_name.id()), 0x7f07_0000);
+        let theme = table.entry_by_ref(Ref::parse("@style/LaunchTheme")?)?;
+        assert_eq!(u32::from(theme.id()), 0x7f08_0000);
+        assert!(table.entry_by_ref(Ref::parse("@mipmap/icon")?).is_ok());
+
+        let mut locales = vec![];
+        if let Chunk::Table(_, chunks) = &chunk {
+            for chunk in chunks {
+                if let Chunk::TablePackage(_, chunks) = chunk {
+                    for chunk in chunks {
+                        if let Chunk::TableType(header, _, _) = chunk {
+                            locales.extend(header.config.locale());
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(locales, ["de"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_file_resources() -> Result<()> {
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_xml(
+            "res/xml/file_paths.xml",
+            r#"<paths><files-path name="files" path="." /></paths>"#.into(),
+        )?;
+        resources.add_xml(
+            "res/layout-land/main.xml",
+            r#"<LinearLayout><TextView id="@+id/title" /></LinearLayout>"#.into(),
+        )?;
+        resources.add_file("res/drawable-hdpi/logo.9.png", "logo.9.png".into())?;
+        let paths = resources
+            .files()
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "res/xml/file_paths.xml",
+                "res/layout-land/main.xml",
+                "res/drawable-hdpi-v4/logo.9.png"
+            ]
+        );
+
+        let chunk = resources.compile(&Table::default())?;
+        let mut table = Table::default();
+        table.import_chunk(&chunk);
+        for r in [
+            "@xml/file_paths",
+            "@layout/main",
+            "@drawable/logo",
+            "@id/title",
+        ] {
+            assert!(table.entry_by_ref(Ref::parse(r)?).is_ok(), "{}", r);
+        }
+        for (_, contents) in resources.files() {
+            if let FileContents::Xml(xml) = contents {
+                let chunk = compile_xml(xml, &table)?;
+                let mut buf = vec![];
+                chunk.write(&mut Cursor::new(&mut buf))?;
+                assert_eq!(chunk, Chunk::parse(&mut Cursor::new(&buf))?);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_library() -> Result<()> {
+        let dir = std::env::temp_dir().join("apk_compile_library");
+        let values = dir.join("values");
+        std::fs::create_dir_all(&values)?;
+        std::fs::write(
+            values.join("values.xml"),
+            r#"<resources>
+                <string name="app_name">library</string>
+                <string name="library_name">library</string>
+                <declare-styleable name="Layout">
+                    <attr name="orientation">
+                        <enum name="horizontal" value="0" />
+                        <enum name="vertical" value="1" />
+                    </attr>
+                    <attr name="android:text" />
+                </declare-styleable>
+                <style name="Vertical">
+                    <item name="orientation">vertical</item>
+                </style>
+            </resources>"#,
+        )?;
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_values(
+            Default::default(),
+            r#"<resources><string name="app_name">helloworld</string></resources>"#,
+        )?;
+        resources.add_library_res_dir(&dir)?;
+        let chunk = resources.compile(&Table::default())?;
+        let mut table = Table::default();
+        table.import_chunk(&chunk);
+        for r in ["@string/library_name", "@id/vertical", "@style/Vertical"] {
+            assert!(table.entry_by_ref(Ref::parse(r)?).is_ok(), "{}", r);
+        }
+        let orientation = table.entry_by_ref(Ref::new(None, "attr", "orientation"))?;
+        assert_eq!(orientation.attribute_format(), Some(1 << 16));
+        let vertical = table.entry_by_ref(Ref::parse("@id/vertical")?)?.id();
+        assert_eq!(orientation.lookup_value(vertical).map(|v| v.data), Some(1));
+        assert!(resources
+            .add_values(
+                Default::default(),
+                r#"<resources><string name="app_name">duplicate</string></resources>"#,
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stable_ids() -> Result<()> {
+        let values = |resources: &mut Resources, xml: &str| {
+            resources.add_values(
+                Default::default(),
+                &format!("<resources>{}</resources>", xml),
+            )
+        };
+        let mut resources = Resources::new("com.example.helloworld");
+        values(
+            &mut resources,
+            r#"<string name="app_name">helloworld</string>
+            <string name="title">title</string>
+            <style name="LaunchTheme" parent="" />"#,
+        )?;
+        let ids = resources.symbols(&Table::default())?;
+
+        // adding a resource which sorts first and a type doesn't change ids
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.set_stable_ids(ids.clone());
+        values(
+            &mut resources,
+            r#"<string name="about">about</string>
+            <string name="app_name">helloworld</string>
+            <string name="title">@string/about</string>
+            <color name="background">#ff000000</color>
+            <style name="LaunchTheme" parent="" />"#,
+        )?;
+        let symbols = resources.symbols(&Table::default())?;
+        for (ty, name) in [
+            ("string", "app_name"),
+            ("string", "title"),
+            ("style", "LaunchTheme"),
+        ] {
+            assert_eq!(symbols.id(ty, name), ids.id(ty, name), "{}/{}", ty, name);
+        }
+        assert_eq!(symbols.id("string", "about"), Some(0x7f01_0002));
+        assert_eq!(symbols.id("color", "background"), Some(0x7f03_0000));
+        let mut table = Table::default();
+        table.import_chunk(&resources.compile(&Table::default())?);
+        for (ty, name) in [
+            ("string", "about"),
+            ("string", "app_name"),
+            ("color", "background"),
+        ] {
+            let id = table.entry_by_ref(Ref::new(None, ty, name))?.id();
+            assert_eq!(symbols.id(ty, name), Some(id.into()), "{}/{}", ty, name);
+        }
+
+        // removing a resource leaves its id unused
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.set_stable_ids(symbols);
+        values(&mut resources, r#"<string name="title">title</string>"#)?;
+        let mut table = Table::default();
+        table.import_chunk(&resources.compile(&Table::default())?);
+        let title = table.entry_by_ref(Ref::new(None, "string", "title"))?.id();
+        assert_eq!(ids.id("string", "title"), Some(title.into()));
+        assert!(table
+            .entry_by_ref(Ref::new(None, "string", "app_name"))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompile_xml() -> Result<()> {
+        use crate::res::*;
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_values(
+            Default::default(),
+            r#"<resources><string name="app_name">helloworld</string></resources>"#,
+        )?;
+        let mut table = Table::default();
+        table.import_chunk(&resources.compile(&Table::default())?);
+        let app_name = table.entry_by_ref(Ref::parse("@string/app_name")?)?.id();
+
+        let attr =
+            |name: i32, raw_value: i32, data_type: ResValueType, data: u32| ResXmlAttribute {
+                namespace: 1,
+                name,
+                raw_value,
+                typed_value: ResValue {
+                    size: 8,
+                    res0: 0,
+                    data_type: data_type as u8,
+                    data,
+                },
+            };
+        let element = |name: i32, attribute_count: u16| ResXmlStartElement {
+            name,
+            attribute_count,
+            ..Default::default()
+        };
+        let ns = ResXmlNamespace { prefix: 0, uri: 1 };
+        let chunk = Chunk::Xml(vec![
+            Chunk::StringPool(
+                ResStringPoolHeader::UTF8_FLAG,
+                [
+                    "android",
+                    "http://schemas.android.com/apk/res/android",
+                    "LinearLayout",
+                    "label",
+                    "layout_width",
+                    "enabled",
+                    "TextView",
+                    "text",
+                    "hello & goodbye",
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+                vec![],
+                vec![],
+            ),
+            Chunk::XmlResourceMap(vec![]),
+            Chunk::XmlStartNamespace(Default::default(), ns),
+            Chunk::XmlStartElement(
+                Default::default(),
+                element(2, 3),
+                vec![
+                    attr(3, -1, ResValueType::Reference, u32::from(app_name)),
+                    attr(4, -1, ResValueType::Dimension, 0x1001),
+                    attr(5, -1, ResValueType::IntBoolean, 0xffff_ffff),
+                ],
+            ),
+            Chunk::XmlStartElement(
+                Default::default(),
+                element(6, 1),
+                vec![attr(7, 8, ResValueType::String, 8)],
+            ),
+            Chunk::XmlEndElement(
+                Default::default(),
+                ResXmlEndElement {
+                    namespace: -1,
+                    name: 6,
+                },
+            ),
+            Chunk::XmlEndElement(
+                Default::default(),
+                ResXmlEndElement {
+                    namespace: -1,
+                    name: 2,
+                },
+            ),
+            Chunk::XmlEndNamespace(Default::default(), ns),
+        ]);
+        let xml = decompile_xml(&chunk, &table)?;
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android" android:label="@string/app_name" android:layout_width="16dp" android:enabled="true">
+    <TextView android:text="hello &amp; goodbye" />
+</LinearLayout>
+"#
+        );
         Ok(())
     }
 
@@ -166,4 +394,96 @@ mod tests {
         let _chunk = compile_manifest(&manifest, &table)?;
         Ok(())
     }
+
+    #[test]
+    fn test_compile_manifest_components() -> Result<()> {
+        // the attributes of the framework used by the manifest
+        let mut android = Resources::new("android");
+        android.add_values(
+            Default::default(),
+            r#"<resources>
+                <attr name="anyDensity" format="boolean" />
+                <attr name="authorities" format="string" />
+                <attr name="colorMode">
+                    <enum name="default" value="0" />
+                    <enum name="wideColorGamut" value="1" />
+                </attr>
+                <attr name="exported" format="boolean" />
+                <attr name="foregroundServiceType">
+                    <flag name="mediaPlayback" value="2" />
+                    <flag name="location" value="8" />
+                </attr>
+                <attr name="grantUriPermissions" format="boolean" />
+                <attr name="maxAspectRatio" format="float" />
+                <attr name="minSdkVersion" format="integer" />
+                <attr name="name" format="string" />
+                <attr name="requiresSmallestWidthDp" format="integer" />
+                <attr name="resizeableActivity" format="boolean" />
+                <attr name="targetSdkVersion" format="integer" />
+                <attr name="taskAffinity" format="string" />
+                <attr name="theme" format="reference" />
+                <style name="Theme.Material" parent="" />
+            </resources>"#,
+        )?;
+        let mut table = Table::default();
+        table.import_chunk(&android.compile(&Table::default())?);
+        let mut manifest = AndroidManifest::default();
+        manifest.supports_screens = Some(SupportsScreens {
+            any_density: Some(true),
+            requires_smallest_width_dp: Some(600),
+            ..Default::default()
+        });
+        manifest.queries = Some(Queries {
+            packages: vec!["com.example.other".into()],
+            intents: vec![IntentFilter {
+                actions: vec!["android.intent.action.SEND".into()],
+                ..Default::default()
+            }],
+            providers: vec![],
+        });
+        manifest.application.activities.push(Activity {
+            theme: Some("@android:style/Theme.Material".into()),
+            task_affinity: Some("com.example.tasks".into()),
+            resizeable_activity: Some(false),
+            color_mode: Some("wideColorGamut".into()),
+            max_aspect_ratio: Some(2.4),
+            ..Default::default()
+        });
+        manifest.application.services.push(Service {
+            name: ".PlaybackService".into(),
+            foreground_service_type: Some("location|mediaPlayback".into()),
+            exported: Some(false),
+            ..Default::default()
+        });
+        manifest.application.providers.push(Provider {
+            name: "androidx.core.content.FileProvider".into(),
+            authorities: "com.example.helloworld.fileprovider".into(),
+            grant_uri_permissions: Some(true),
+            ..Default::default()
+        });
+        let chunk = compile_manifest(&manifest, &table)?;
+        // the framework resources have the package id of the app, so the
+        // theme decompiles without the `android` package
+        assert_eq!(
+            crate::res::decompile_xml(&chunk, &table)?,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android">
+    <uses-sdk android:minSdkVersion="23" />
+    <supports-screens android:anyDensity="true" android:requiresSmallestWidthDp="600" />
+    <queries>
+        <package android:name="com.example.other" />
+        <intent>
+            <action android:name="android.intent.action.SEND" />
+        </intent>
+    </queries>
+    <application>
+        <activity android:colorMode="wideColorGamut" android:maxAspectRatio="2.4" android:resizeableActivity="false" android:taskAffinity="com.example.tasks" android:theme="@style/Theme.Material" />
+        <service android:exported="false" android:foregroundServiceType="location|mediaPlayback" android:name=".PlaybackService" />
+        <provider android:authorities="com.example.helloworld.fileprovider" android:grantUriPermissions="true" android:name="androidx.core.content.FileProvider" />
+    </application>
+</manifest>
+"#
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,325 @@
+use crate::aab::proto as pb;
+use crate::compiler::attributes::{attr_package, compile_attr, StringPoolBuilder, Strings};
+use crate::compiler::table::{Ref, Table};
+use crate::compiler::xml::build_string_pool;
+use crate::res::{Chunk, ResTableConfig, ResTableValue, ResValue, ResValueType};
+use anyhow::Result;
+use roxmltree::{Document, Node};
+use std::collections::BTreeMap;
+
+pub fn compile_xml(xml: &str, table: &Table) -> Result<pb::XmlNode> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    let mut builder = StringPoolBuilder::new(table);
+    build_string_pool(root, &mut builder)?;
+    let strings = builder.build();
+    let mut element = compile_element(root, &strings, table)?;
+    element.namespace_declaration = root
+        .namespaces()
+        .iter()
+        .map(|ns| pb::XmlNamespace {
+            prefix: ns.name().unwrap_or_default().to_string(),
+            uri: ns.uri().to_string(),
+        })
+        .collect();
+    Ok(pb::XmlNode {
+        node: Some(pb::xml_node::Node::Element(element)),
+    })
+}
+
+fn compile_element(node: Node, strings: &Strings, table: &Table) -> Result<pb::XmlElement> {
+    let mut attribute = Vec::with_capacity(node.attributes().len());
+    for attr in node.attributes() {
+        let (resource_id, value) = if let Some(package) = attr_package(attr.namespace()) {
+            let id = table
+                .entry_by_ref(Ref::new(package, "attr", attr.name()))?
+                .id();
+            let value = compile_attr(table, package, attr.name(), attr.value(), strings)?;
+            (u32::from(id), Some(value))
+        } else if attr.name() == "platformBuildVersionCode"
+            || attr.name() == "platformBuildVersionName"
+        {
+            let value = ResValue {
+                size: 8,
+                res0: 0,
+                data_type: ResValueType::IntDec as u8,
+                data: attr.value().parse()?,
+            };
+            (0, Some(value))
+        } else {
+            (0, None)
+        };
+        let compiled_item = if let Some(value) = value {
+            Some(compile_item(&value, &strings.strings)?)
+        } else {
+            None
+        };
+        attribute.push(pb::XmlAttribute {
+            namespace_uri: attr.namespace().unwrap_or_default().to_string(),
+            name: attr.name().to_string(),
+            value: attr.value().to_string(),
+            resource_id,
+            compiled_item,
+        });
+    }
+    let mut child = vec![];
+    for node in node.children().filter(|node| node.is_element()) {
+        let element = compile_element(node, strings, table)?;
+        child.push(pb::XmlNode {
+            node: Some(pb::xml_node::Node::Element(element)),
+        });
+    }
+    Ok(pb::XmlElement {
+        namespace_declaration: vec![],
+        namespace_uri: node.tag_name().namespace().unwrap_or_default().to_string(),
+        name: node.tag_name().name().to_string(),
+        attribute,
+        child,
+    })
+}
+
+fn compile_item(value: &ResValue, strings: &[String]) -> Result<pb::Item> {
+    use pb::primitive::Value as Prim;
+    let ty = ResValueType::from_u8(value.data_type)
+        .ok_or_else(|| anyhow::anyhow!("unknown value type 0x{:x}", value.data_type))?;
+    let prim = match ty {
+        ResValueType::Reference | ResValueType::Attribute => {
+            let ty = if ty == ResValueType::Reference {
+                pb::reference::Type::Reference
+            } else {
+                pb::reference::Type::Attribute
+            };
+            return Ok(pb::Item {
+                value: Some(pb::item::Value::Ref(pb::Reference {
+                    r#type: ty as i32,
+                    id: value.data,
+                    name: String::new(),
+                })),
+            });
+        }
+        ResValueType::String => {
+            let string = strings
+                .get(value.data as usize)
+                .ok_or_else(|| anyhow::anyhow!("invalid string id {}", value.data))?;
+            let item = if string.starts_with("res/") {
+                let ty = if string.ends_with(".png") {
+                    pb::file_reference::Type::Png
+                } else if string.ends_with(".xml") {
+                    pb::file_reference::Type::ProtoXml
+                } else {
+                    pb::file_reference::Type::Unknown
+                };
+                pb::item::Value::File(pb::FileReference {
+                    path: string.clone(),
+                    r#type: ty as i32,
+                })
+            } else {
+                pb::item::Value::Str(pb::Str {
+                    value: string.clone(),
+                })
+            };
+            return Ok(pb::Item { value: Some(item) });
+        }
+        ResValueType::Null if value.data == 1 => Prim::EmptyValue(Default::default()),
+        ResValueType::Null => Prim::NullValue(Default::default()),
+        ResValueType::Float => Prim::FloatValue(f32::from_bits(value.data)),
+        ResValueType::Dimension => Prim::DimensionValue(value.data),
+        ResValueType::Fraction => Prim::FractionValue(value.data),
+        ResValueType::IntDec => Prim::IntDecimalValue(value.data as i32),
+        ResValueType::IntHex => Prim::IntHexadecimalValue(value.data),
+        ResValueType::IntBoolean => Prim::BooleanValue(value.data != 0),
+        ResValueType::IntColorArgb8 => Prim::ColorArgb8Value(value.data),
+        ResValueType::IntColorRgb8 => Prim::ColorRgb8Value(value.data),
+        ResValueType::IntColorArgb4 => Prim::ColorArgb4Value(value.data),
+        ResValueType::IntColorRgb4 => Prim::ColorRgb4Value(value.data),
+    };
+    Ok(pb::Item {
+        value: Some(pb::item::Value::Prim(pb::Primitive { value: Some(prim) })),
+    })
+}
+
+fn compile_config(config: &ResTableConfig) -> pb::Configuration {
+    // the binary format uses 1 for the negative variant of boolean
+    // qualifiers like `notnight`, protobuf uses 1 for the positive one.
+    let invert = |value: u8| match value {
+        1 => 2,
+        2 => 1,
+        _ => 0,
+    };
+    let layout = config.screen_layout;
+    pb::Configuration {
+        mcc: config.mcc as u32,
+        mnc: config.mnc as u32,
+        locale: config.locale().unwrap_or_default(),
+        layout_direction: ((layout & ResTableConfig::MASK_LAYOUTDIR) >> 6) as i32,
+        screen_width: config.screen_width as u32,
+        screen_height: config.screen_height as u32,
+        screen_width_dp: config.screen_width_dp as u32,
+        screen_height_dp: config.screen_height_dp as u32,
+        smallest_screen_width_dp: config.smallest_screen_width_dp as u32,
+        screen_layout_size: (layout & ResTableConfig::MASK_SCREENSIZE) as i32,
+        screen_layout_long: invert((layout & ResTableConfig::MASK_SCREENLONG) >> 4),
+        screen_round: invert(config.screen_layout2 & ResTableConfig::MASK_SCREENROUND),
+        wide_color_gamut: invert(config.color_mode & ResTableConfig::MASK_WIDE_COLOR_GAMUT),
+        hdr: invert((config.color_mode & ResTableConfig::MASK_HDR) >> 2),
+        orientation: config.orientation as i32,
+        ui_mode_type: (config.ui_mode & ResTableConfig::MASK_UI_MODE_TYPE) as i32,
+        ui_mode_night: invert((config.ui_mode & ResTableConfig::MASK_UI_MODE_NIGHT) >> 4),
+        density: config.density as u32,
+        touchscreen: config.touchscreen as i32,
+        keys_hidden: (config.input_flags & ResTableConfig::MASK_KEYSHIDDEN) as i32,
+        keyboard: config.keyboard as i32,
+        nav_hidden: ((config.input_flags & ResTableConfig::MASK_NAVHIDDEN) >> 2) as i32,
+        navigation: config.navigation as i32,
+        sdk_version: config.sdk_version as u32,
+    }
+}
+
+pub fn compile_table(chunk: &Chunk) -> Result<pb::ResourceTable> {
+    let chunks = if let Chunk::Table(_, chunks) = chunk {
+        chunks
+    } else {
+        anyhow::bail!("expected table chunk");
+    };
+    let strings = if let Some(Chunk::StringPool(_, strings, _, _)) = chunks.first() {
+        strings
+    } else {
+        anyhow::bail!("invalid table");
+    };
+    let mut package = vec![];
+    for chunk in &chunks[1..] {
+        if let Chunk::TablePackage(header, chunks) = chunk {
+            package.push(compile_package(header.id, &header.name, chunks, strings)?);
+        }
+    }
+    Ok(pb::ResourceTable {
+        package,
+        tool_fingerprint: vec![],
+    })
+}
+
+fn compile_package(
+    id: u32,
+    name: &str,
+    chunks: &[Chunk],
+    strings: &[String],
+) -> Result<pb::Package> {
+    let (types, keys) = match (chunks.first(), chunks.get(1)) {
+        (Some(Chunk::StringPool(_, types, _, _)), Some(Chunk::StringPool(_, keys, _, _))) => {
+            (types, keys)
+        }
+        _ => anyhow::bail!("invalid package"),
+    };
+    let mut entries: BTreeMap<u8, BTreeMap<u16, pb::Entry>> = BTreeMap::new();
+    for chunk in &chunks[2..] {
+        let (header, type_entries) = if let Chunk::TableType(header, _, entries) = chunk {
+            (header, entries)
+        } else {
+            continue;
+        };
+        let config = compile_config(&header.config);
+        let type_name = types
+            .get(header.id as usize - 1)
+            .ok_or_else(|| anyhow::anyhow!("invalid type id {}", header.id))?;
+        for (entry_id, entry) in type_entries.iter().enumerate() {
+            let entry = if let Some(entry) = entry {
+                entry
+            } else {
+                continue;
+            };
+            let value = match &entry.value {
+                ResTableValue::Simple(_) if type_name == "id" => pb::value::Value::Item(pb::Item {
+                    value: Some(pb::item::Value::Id(pb::Id {})),
+                }),
+                ResTableValue::Simple(value) => {
+                    pb::value::Value::Item(compile_item(value, strings)?)
+                }
+                ResTableValue::Complex(_, map) if type_name == "array" => {
+                    let mut array = pb::Array {
+                        element: Vec::with_capacity(map.len()),
+                    };
+                    for map in map {
+                        array.element.push(pb::array::Element {
+                            item: Some(compile_item(&map.value, strings)?),
+                        });
+                    }
+                    pb::value::Value::CompoundValue(pb::CompoundValue {
+                        value: Some(pb::compound_value::Value::Array(array)),
+                    })
+                }
+                ResTableValue::Complex(map_entry, map) => {
+                    let parent = if map_entry.parent != 0 {
+                        Some(pb::Reference {
+                            id: map_entry.parent,
+                            ..Default::default()
+                        })
+                    } else {
+                        None
+                    };
+                    let mut style = pb::Style {
+                        parent,
+                        entry: Vec::with_capacity(map.len()),
+                    };
+                    for map in map {
+                        style.entry.push(pb::style::Entry {
+                            key: Some(pb::Reference {
+                                id: map.name,
+                                ..Default::default()
+                            }),
+                            item: Some(compile_item(&map.value, strings)?),
+                        });
+                    }
+                    pb::value::Value::CompoundValue(pb::CompoundValue {
+                        value: Some(pb::compound_value::Value::Style(style)),
+                    })
+                }
+            };
+            let key = keys
+                .get(entry.key as usize)
+                .ok_or_else(|| anyhow::anyhow!("invalid key id {}", entry.key))?;
+            let visibility = if entry.is_public() {
+                pb::visibility::Level::Public
+            } else {
+                pb::visibility::Level::Unknown
+            };
+            entries
+                .entry(header.id)
+                .or_default()
+                .entry(entry_id as u16)
+                .or_insert_with(|| pb::Entry {
+                    entry_id: Some(pb::EntryId {
+                        id: entry_id as u32,
+                    }),
+                    name: key.clone(),
+                    visibility: Some(pb::Visibility {
+                        level: visibility as i32,
+                    }),
+                    config_value: vec![],
+                })
+                .config_value
+                .push(pb::ConfigValue {
+                    config: Some(config.clone()),
+                    value: Some(pb::Value {
+                        value: Some(value),
+                        ..Default::default()
+                    }),
+                });
+        }
+    }
+    let mut ty = Vec::with_capacity(entries.len());
+    for (type_id, entries) in entries {
+        let name = types
+            .get(type_id as usize - 1)
+            .ok_or_else(|| anyhow::anyhow!("invalid type id {}", type_id))?;
+        ty.push(pb::Type {
+            type_id: Some(pb::TypeId { id: type_id as u32 }),
+            name: name.clone(),
+            entry: entries.into_values().collect(),
+        });
+    }
+    Ok(pb::Package {
+        package_id: Some(pb::PackageId { id }),
+        package_name: name.to_string(),
+        r#type: ty,
+    })
+}
@@ -0,0 +1,478 @@
+//! Parsing of resource directory qualifiers like `values-de-rDE-night-v29`.
+use crate::res::{pack_language_or_region, ResTableConfig};
+use anyhow::Result;
+use std::fmt;
+use std::str::FromStr;
+
+const ORIENTATIONS: [(&str, u8); 3] = [("port", 1), ("land", 2), ("square", 3)];
+const UI_MODE_TYPES: [(&str, u8); 6] = [
+    ("desk", 2),
+    ("car", 3),
+    ("television", 4),
+    ("appliance", 5),
+    ("watch", 6),
+    ("vrheadset", 7),
+];
+const UI_MODE_NIGHT: [(&str, u8); 2] = [("notnight", 0x10), ("night", 0x20)];
+const LAYOUT_DIRS: [(&str, u8); 2] = [("ldltr", 0x40), ("ldrtl", 0x80)];
+const SCREEN_SIZES: [(&str, u8); 4] = [("small", 1), ("normal", 2), ("large", 3), ("xlarge", 4)];
+const SCREEN_LONG: [(&str, u8); 2] = [("notlong", 0x10), ("long", 0x20)];
+const SCREEN_ROUND: [(&str, u8); 2] = [("notround", 0x01), ("round", 0x02)];
+const WIDE_COLOR_GAMUT: [(&str, u8); 2] = [("nowidecg", 0x01), ("widecg", 0x02)];
+const HDR: [(&str, u8); 2] = [("lowdr", 0x04), ("highdr", 0x08)];
+const DENSITIES: [(&str, u16); 9] = [
+    ("ldpi", 120),
+    ("mdpi", 160),
+    ("tvdpi", 213),
+    ("hdpi", 240),
+    ("xhdpi", 320),
+    ("xxhdpi", 480),
+    ("xxxhdpi", 640),
+    ("anydpi", ResTableConfig::DENSITY_ANY),
+    ("nodpi", ResTableConfig::DENSITY_NONE),
+];
+const TOUCHSCREENS: [(&str, u8); 3] = [("notouch", 1), ("stylus", 2), ("finger", 3)];
+const KEYS_HIDDEN: [(&str, u8); 3] = [("keysexposed", 1), ("keyshidden", 2), ("keyssoft", 3)];
+const KEYBOARDS: [(&str, u8); 3] = [("nokeys", 1), ("qwerty", 2), ("12key", 3)];
+const NAV_HIDDEN: [(&str, u8); 2] = [("navexposed", 0x04), ("navhidden", 0x08)];
+const NAVIGATIONS: [(&str, u8); 4] = [("nonav", 1), ("dpad", 2), ("trackball", 3), ("wheel", 4)];
+
+/// Qualifiers in the order they have to appear in a directory name.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Qualifier {
+    Mcc,
+    Mnc,
+    Language,
+    Region,
+    LayoutDir,
+    SmallestWidth,
+    Width,
+    Height,
+    ScreenSize,
+    ScreenLong,
+    ScreenRound,
+    WideColorGamut,
+    Hdr,
+    Orientation,
+    UiModeType,
+    UiModeNight,
+    Density,
+    Touchscreen,
+    KeysHidden,
+    Keyboard,
+    NavHidden,
+    Navigation,
+    ScreenDimensions,
+    Version,
+}
+
+/// Returns the name of a density bucket like `xxhdpi`. `anydpi` and `nodpi`
+/// are not buckets.
+pub fn density_bucket(density: u16) -> Option<&'static str> {
+    if density == ResTableConfig::DENSITY_ANY || density == ResTableConfig::DENSITY_NONE {
+        return None;
+    }
+    DENSITIES
+        .iter()
+        .find(|(_, value)| *value == density)
+        .map(|(name, _)| *name)
+}
+
+/// Splits a resource directory name like `values-de` into the resource
+/// type and its config.
+pub fn parse_dir_name(name: &str) -> Result<(&str, ResTableConfig)> {
+    let (ty, qualifiers) = name.split_once('-').unwrap_or((name, ""));
+    let config = qualifiers
+        .parse()
+        .map_err(|err| anyhow::anyhow!("invalid resource directory {}: {}", name, err))?;
+    Ok((ty, config))
+}
+
+impl FromStr for ResTableConfig {
+    type Err = anyhow::Error;
+
+    /// Parses dash separated qualifiers, an empty string is the default config.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut config = ResTableConfig::default();
+        let mut last = None;
+        for part in s.split('-').filter(|part| !part.is_empty()) {
+            let qualifier = parse_qualifier(&mut config, part, last)?
+                .ok_or_else(|| anyhow::anyhow!("unknown qualifier {}", part))?;
+            if let Some(last) = last {
+                anyhow::ensure!(qualifier > last, "qualifier {} is out of order", part);
+            }
+            last = Some(qualifier);
+        }
+        apply_version_for_compatibility(&mut config);
+        Ok(config)
+    }
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], part: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(name, _)| *name == part)
+        .map(|(_, value)| *value)
+}
+
+fn parse_number(s: Option<&str>) -> Option<u16> {
+    let s = s?;
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn parse_qualifier(
+    config: &mut ResTableConfig,
+    part: &str,
+    last: Option<Qualifier>,
+) -> Result<Option<Qualifier>> {
+    let lower = part.to_ascii_lowercase();
+    let part = lower.as_str();
+    if let Some(mcc) = parse_number(part.strip_prefix("mcc")) {
+        config.mcc = mcc;
+        return Ok(Some(Qualifier::Mcc));
+    }
+    if let Some(mnc) = parse_number(part.strip_prefix("mnc")) {
+        config.mnc = if mnc == 0 { 0xffff } else { mnc };
+        return Ok(Some(Qualifier::Mnc));
+    }
+    if let Some(tag) = part.strip_prefix("b+") {
+        parse_bcp47(config, tag)?;
+        return Ok(Some(Qualifier::Language));
+    }
+    if let Some(region) = part.strip_prefix('r') {
+        if last == Some(Qualifier::Language)
+            && region.len() == 2
+            && region.bytes().all(|b| b.is_ascii_alphabetic())
+        {
+            config.region = pack_language_or_region(&region.to_ascii_uppercase(), b'0')?;
+            return Ok(Some(Qualifier::Region));
+        }
+    }
+    // languages are only matched in their position and like aapt2 `car` is
+    // always the ui mode, even though it looks like a three letter language
+    if last < Some(Qualifier::Language)
+        && (part.len() == 2 || part.len() == 3)
+        && part.bytes().all(|b| b.is_ascii_lowercase())
+        && part != "car"
+    {
+        config.language = pack_language_or_region(part, b'a')?;
+        return Ok(Some(Qualifier::Language));
+    }
+    if let Some(value) = lookup(&LAYOUT_DIRS, part) {
+        config.screen_layout |= value;
+        return Ok(Some(Qualifier::LayoutDir));
+    }
+    if let Some(dp) = parse_number(part.strip_prefix("sw").and_then(|s| s.strip_suffix("dp"))) {
+        config.smallest_screen_width_dp = dp;
+        return Ok(Some(Qualifier::SmallestWidth));
+    }
+    if let Some(dp) = parse_number(part.strip_prefix('w').and_then(|s| s.strip_suffix("dp"))) {
+        config.screen_width_dp = dp;
+        return Ok(Some(Qualifier::Width));
+    }
+    if let Some(dp) = parse_number(part.strip_prefix('h').and_then(|s| s.strip_suffix("dp"))) {
+        config.screen_height_dp = dp;
+        return Ok(Some(Qualifier::Height));
+    }
+    if let Some(value) = lookup(&SCREEN_SIZES, part) {
+        config.screen_layout |= value;
+        return Ok(Some(Qualifier::ScreenSize));
+    }
+    if let Some(value) = lookup(&SCREEN_LONG, part) {
+        config.screen_layout |= value;
+        return Ok(Some(Qualifier::ScreenLong));
+    }
+    if let Some(value) = lookup(&SCREEN_ROUND, part) {
+        config.screen_layout2 |= value;
+        return Ok(Some(Qualifier::ScreenRound));
+    }
+    if let Some(value) = lookup(&WIDE_COLOR_GAMUT, part) {
+        config.color_mode |= value;
+        return Ok(Some(Qualifier::WideColorGamut));
+    }
+    if let Some(value) = lookup(&HDR, part) {
+        config.color_mode |= value;
+        return Ok(Some(Qualifier::Hdr));
+    }
+    if let Some(value) = lookup(&ORIENTATIONS, part) {
+        config.orientation = value;
+        return Ok(Some(Qualifier::Orientation));
+    }
+    if let Some(value) = lookup(&UI_MODE_TYPES, part) {
+        config.ui_mode |= value;
+        return Ok(Some(Qualifier::UiModeType));
+    }
+    if let Some(value) = lookup(&UI_MODE_NIGHT, part) {
+        config.ui_mode |= value;
+        return Ok(Some(Qualifier::UiModeNight));
+    }
+    if let Some(density) =
+        lookup(&DENSITIES, part).or_else(|| parse_number(part.strip_suffix("dpi")))
+    {
+        config.density = density;
+        return Ok(Some(Qualifier::Density));
+    }
+    if let Some(value) = lookup(&TOUCHSCREENS, part) {
+        config.touchscreen = value;
+        return Ok(Some(Qualifier::Touchscreen));
+    }
+    if let Some(value) = lookup(&KEYS_HIDDEN, part) {
+        config.input_flags |= value;
+        return Ok(Some(Qualifier::KeysHidden));
+    }
+    if let Some(value) = lookup(&KEYBOARDS, part) {
+        config.keyboard = value;
+        return Ok(Some(Qualifier::Keyboard));
+    }
+    if let Some(value) = lookup(&NAV_HIDDEN, part) {
+        config.input_flags |= value;
+        return Ok(Some(Qualifier::NavHidden));
+    }
+    if let Some(value) = lookup(&NAVIGATIONS, part) {
+        config.navigation = value;
+        return Ok(Some(Qualifier::Navigation));
+    }
+    if let Some((width, height)) = part.split_once('x') {
+        if let (Some(width), Some(height)) = (parse_number(Some(width)), parse_number(Some(height)))
+        {
+            anyhow::ensure!(width >= height, "screen width must be larger than height");
+            config.screen_width = width;
+            config.screen_height = height;
+            return Ok(Some(Qualifier::ScreenDimensions));
+        }
+    }
+    if let Some(version) = parse_number(part.strip_prefix('v')) {
+        config.sdk_version = version;
+        return Ok(Some(Qualifier::Version));
+    }
+    Ok(None)
+}
+
+/// Parses a BCP 47 tag with subtags separated by `+` like `sr+Latn+RS`.
+fn parse_bcp47(config: &mut ResTableConfig, tag: &str) -> Result<()> {
+    let mut subtags = tag.split('+');
+    let language = subtags.next().unwrap_or_default();
+    anyhow::ensure!(
+        (2..=3).contains(&language.len()),
+        "invalid language {}",
+        language
+    );
+    config.language = pack_language_or_region(language, b'a')?;
+    for subtag in subtags {
+        let all_alpha = subtag.bytes().all(|b| b.is_ascii_alphabetic());
+        let all_digit = subtag.bytes().all(|b| b.is_ascii_digit());
+        match subtag.len() {
+            2 if all_alpha => {
+                config.region = pack_language_or_region(&subtag.to_ascii_uppercase(), b'0')?
+            }
+            3 if all_digit => config.region = pack_language_or_region(subtag, b'0')?,
+            4 if all_alpha => {
+                let mut script = [0; 4];
+                script.copy_from_slice(subtag.as_bytes());
+                script[0] = script[0].to_ascii_uppercase();
+                config.locale_script = script;
+            }
+            5..=8 => {
+                let mut variant = [0; 8];
+                variant[..subtag.len()].copy_from_slice(subtag.as_bytes());
+                config.locale_variant = variant;
+            }
+            _ => anyhow::bail!("invalid locale subtag {}", subtag),
+        }
+    }
+    Ok(())
+}
+
+/// Newer qualifiers are only understood by newer platforms, so like aapt2
+/// the sdk version is raised to the version that introduced them.
+fn apply_version_for_compatibility(config: &mut ResTableConfig) {
+    let min_sdk =
+        if config.ui_mode & ResTableConfig::MASK_UI_MODE_TYPE == 7 || config.color_mode != 0 {
+            26
+        } else if config.screen_layout2 & ResTableConfig::MASK_SCREENROUND != 0 {
+            23
+        } else if config.density == ResTableConfig::DENSITY_ANY {
+            21
+        } else if config.screen_layout & ResTableConfig::MASK_LAYOUTDIR != 0 {
+            17
+        } else if config.smallest_screen_width_dp != 0
+            || config.screen_width_dp != 0
+            || config.screen_height_dp != 0
+        {
+            13
+        } else if config.ui_mode != 0 {
+            8
+        } else if config.screen_layout
+            & (ResTableConfig::MASK_SCREENSIZE | ResTableConfig::MASK_SCREENLONG)
+            != 0
+            || config.density != 0
+        {
+            4
+        } else {
+            0
+        };
+    config.sdk_version = config.sdk_version.max(min_sdk);
+}
+
+fn push_name<T: Copy + PartialEq>(parts: &mut Vec<String>, table: &[(&str, T)], value: T) {
+    if let Some((name, _)) = table.iter().find(|(_, v)| *v == value) {
+        parts.push(name.to_string());
+    }
+}
+
+impl fmt::Display for ResTableConfig {
+    /// Formats the config as dash separated qualifiers, the default config
+    /// is an empty string.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if self.mcc != 0 {
+            parts.push(format!("mcc{}", self.mcc));
+        }
+        if self.mnc != 0 {
+            parts.push(format!(
+                "mnc{}",
+                if self.mnc == 0xffff { 0 } else { self.mnc }
+            ));
+        }
+        if let Some(locale) = self.locale() {
+            let subtags = locale.split('-').collect::<Vec<_>>();
+            match subtags.as_slice() {
+                [language] => parts.push(language.to_string()),
+                [language, region] if region.len() == 2 => {
+                    parts.push(language.to_string());
+                    parts.push(format!("r{}", region));
+                }
+                _ => parts.push(format!("b+{}", subtags.join("+"))),
+            }
+        }
+        let layout = self.screen_layout;
+        push_name(&mut parts, &LAYOUT_DIRS, layout & Self::MASK_LAYOUTDIR);
+        if self.smallest_screen_width_dp != 0 {
+            parts.push(format!("sw{}dp", self.smallest_screen_width_dp));
+        }
+        if self.screen_width_dp != 0 {
+            parts.push(format!("w{}dp", self.screen_width_dp));
+        }
+        if self.screen_height_dp != 0 {
+            parts.push(format!("h{}dp", self.screen_height_dp));
+        }
+        push_name(&mut parts, &SCREEN_SIZES, layout & Self::MASK_SCREENSIZE);
+        push_name(&mut parts, &SCREEN_LONG, layout & Self::MASK_SCREENLONG);
+        let round = self.screen_layout2 & Self::MASK_SCREENROUND;
+        push_name(&mut parts, &SCREEN_ROUND, round);
+        let color_mode = self.color_mode;
+        push_name(
+            &mut parts,
+            &WIDE_COLOR_GAMUT,
+            color_mode & Self::MASK_WIDE_COLOR_GAMUT,
+        );
+        push_name(&mut parts, &HDR, color_mode & Self::MASK_HDR);
+        push_name(&mut parts, &ORIENTATIONS, self.orientation);
+        let ui_mode = self.ui_mode;
+        push_name(
+            &mut parts,
+            &UI_MODE_TYPES,
+            ui_mode & Self::MASK_UI_MODE_TYPE,
+        );
+        push_name(
+            &mut parts,
+            &UI_MODE_NIGHT,
+            ui_mode & Self::MASK_UI_MODE_NIGHT,
+        );
+        if self.density != 0 {
+            if DENSITIES
+                .iter()
+                .any(|(_, density)| *density == self.density)
+            {
+                push_name(&mut parts, &DENSITIES, self.density);
+            } else {
+                parts.push(format!("{}dpi", self.density));
+            }
+        }
+        push_name(&mut parts, &TOUCHSCREENS, self.touchscreen);
+        let input = self.input_flags;
+        push_name(&mut parts, &KEYS_HIDDEN, input & Self::MASK_KEYSHIDDEN);
+        push_name(&mut parts, &KEYBOARDS, self.keyboard);
+        push_name(&mut parts, &NAV_HIDDEN, input & Self::MASK_NAVHIDDEN);
+        push_name(&mut parts, &NAVIGATIONS, self.navigation);
+        if self.screen_width != 0 || self.screen_height != 0 {
+            parts.push(format!("{}x{}", self.screen_width, self.screen_height));
+        }
+        if self.sdk_version != 0 {
+            parts.push(format!("v{}", self.sdk_version));
+        }
+        write!(f, "{}", parts.join("-"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_qualifiers() -> Result<()> {
+        let cases = [
+            ("", ""),
+            ("de", "de"),
+            ("de-rDE", "de-rDE"),
+            ("night", "night-v8"),
+            ("night-v29", "night-v29"),
+            ("hdpi", "hdpi-v4"),
+            ("anydpi-v26", "anydpi-v26"),
+            ("mcc310-mnc004-en-rUS", "mcc310-mnc4-en-rUS"),
+            ("b+sr+Latn+RS", "b+sr+Latn+RS"),
+            ("b+es+419", "b+es+419"),
+            ("fil", "fil"),
+            ("ldrtl-sw600dp-w720dp-land", "ldrtl-sw600dp-w720dp-land-v17"),
+            ("large-long-round-port-car", "large-long-round-port-car-v23"),
+            ("car", "car-v8"),
+            ("de-car", "de-car-v8"),
+            (
+                "widecg-highdr-finger-keyshidden-qwerty-navexposed-dpad",
+                "widecg-highdr-finger-keyshidden-qwerty-navexposed-dpad-v26",
+            ),
+            ("xxhdpi-320x240", "xxhdpi-320x240-v4"),
+        ];
+        for (qualifiers, expected) in cases {
+            let config: ResTableConfig = qualifiers.parse()?;
+            assert_eq!(config.to_string(), expected);
+            assert_eq!(expected.parse::<ResTableConfig>()?, config);
+
+            let mut buf = vec![];
+            config.write(&mut buf)?;
+            assert_eq!(buf.len(), ResTableConfig::SIZE as usize);
+            assert_eq!(ResTableConfig::read(&mut Cursor::new(&buf))?, config);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_qualifiers() {
+        for qualifiers in ["v21-night", "de-fr", "en-foo", "hdpi-de"] {
+            assert!(
+                qualifiers.parse::<ResTableConfig>().is_err(),
+                "{}",
+                qualifiers
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_dir_name() -> Result<()> {
+        let (ty, config) = parse_dir_name("values-de")?;
+        assert_eq!(ty, "values");
+        assert_eq!(config.locale().as_deref(), Some("de"));
+        let (ty, config) = parse_dir_name("values-car")?;
+        assert_eq!(ty, "values");
+        assert_eq!(config.locale(), None);
+        assert_eq!(config.ui_mode & ResTableConfig::MASK_UI_MODE_TYPE, 3);
+        let (ty, config) = parse_dir_name("drawable")?;
+        assert_eq!(ty, "drawable");
+        assert_eq!(config, ResTableConfig::default());
+        Ok(())
+    }
+}
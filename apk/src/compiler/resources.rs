@@ -0,0 +1,784 @@
+use crate::compiler::qualifiers;
+use crate::compiler::symbols::Symbols;
+use crate::compiler::table::{Entry, Ref, Table};
+use crate::compiler::values::{self, Format, Value};
+use crate::res::{
+    Chunk, ResAttributeType, ResStringPoolHeader, ResTableConfig, ResTableEntry, ResTableHeader,
+    ResTableMap, ResTableMapEntry, ResTablePackageHeader, ResTableRef, ResTableTypeHeader,
+    ResTableTypeSpecHeader, ResTableValue, ResValue, ResValueType, NO_ENTRY,
+};
+use anyhow::Result;
+use roxmltree::Document;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const PACKAGE_ID: u8 = 0x7f;
+/// Size of the package header, the type strings follow it.
+const PACKAGE_HEADER_SIZE: u32 = 288;
+/// Array items are keyed by their index.
+const ARRAY_KEY: u32 = 0x0200_0000;
+/// Key of the format of an attribute.
+const ATTR_TYPE: u32 = 0x0100_0000;
+
+/// Resource types stored as files in the `res` directory.
+const FILE_TYPES: [&str; 13] = [
+    "anim",
+    "animator",
+    "color",
+    "drawable",
+    "font",
+    "interpolator",
+    "layout",
+    "menu",
+    "mipmap",
+    "navigation",
+    "raw",
+    "transition",
+    "xml",
+];
+
+/// Values of an entry keyed by the index of their config.
+type Values = Vec<(usize, Value)>;
+
+/// Contents of a file resource.
+pub enum FileContents {
+    /// Xml which needs to be compiled.
+    Xml(String),
+    /// File which is stored as is.
+    Raw(PathBuf),
+}
+
+/// Collects the resources of an app and compiles them into a resource
+/// table containing a single package with id 0x7f.
+///
+/// Type and entry ids are assigned in alphabetical order, unless they are
+/// pinned using [`Resources::set_stable_ids`].
+pub struct Resources {
+    package: String,
+    configs: Vec<ResTableConfig>,
+    types: BTreeMap<&'static str, BTreeMap<String, Values>>,
+    files: Vec<(String, FileContents)>,
+    styleables: BTreeMap<String, Vec<String>>,
+    library: bool,
+    stable_ids: Symbols,
+}
+
+impl Resources {
+    pub fn new(package: &str) -> Self {
+        Self {
+            package: package.to_string(),
+            configs: vec![ResTableConfig::default()],
+            types: Default::default(),
+            files: vec![],
+            styleables: Default::default(),
+            library: false,
+            stable_ids: Symbols::default(),
+        }
+    }
+
+    /// Keeps the ids of resources from a previous build like
+    /// `aapt2 link --stable-ids`, so adding a resource doesn't change the
+    /// ids of the existing ones. New types take the first free type id and
+    /// new entries are appended to their type, ids of removed resources are
+    /// left unused.
+    pub fn set_stable_ids(&mut self, ids: Symbols) {
+        self.stable_ids = ids;
+    }
+
+    /// Returns if a resource like `string/app_name` is declared.
+    pub fn contains(&self, ty: &str, name: &str) -> bool {
+        self.types
+            .get(ty)
+            .and_then(|entries| entries.get(name))
+            .is_some()
+    }
+
+    /// Adds a string for the default config, the value is stored as is.
+    pub fn add_string(&mut self, name: &str, value: &str) -> Result<()> {
+        let value = format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+        self.add(0, "string", name, Value::Item(Format::String, value))?;
+        Ok(())
+    }
+
+    /// Adds a mipmap referencing a png for each density.
+    pub fn add_mipmap(&mut self, name: &str, size_dp: u32) -> Result<()> {
+        for (path, density, _) in super::mipmap_variants(name, size_dp) {
+            let config = self.config_id(ResTableConfig {
+                density,
+                sdk_version: 4,
+                ..Default::default()
+            });
+            self.add(config, "mipmap", name, Value::Item(Format::File, path))?;
+        }
+        Ok(())
+    }
+
+    /// Adds the resources of an android `res` directory. Files in `values`
+    /// directories are parsed, xml files in the other directories are
+    /// compiled once the table is known and the rest is stored as is.
+    pub fn add_res_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut dirs = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+        dirs.sort();
+        for dir in dirs {
+            let dir_name = dir.file_name().unwrap().to_str().unwrap_or_default();
+            let (ty, config) = qualifiers::parse_dir_name(dir_name)?;
+            if ty != "values" && !FILE_TYPES.contains(&ty) {
+                tracing::warn!("skipping unsupported resource directory {}", dir.display());
+                continue;
+            }
+            let mut files = vec![];
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+            files.sort();
+            for file in files {
+                let is_xml = file.extension() == Some("xml".as_ref());
+                let result = if ty == "values" {
+                    if !is_xml {
+                        continue;
+                    }
+                    let xml = std::fs::read_to_string(&file)?;
+                    self.add_values(config.clone(), &xml)
+                } else {
+                    let file_name = file.file_name().unwrap().to_str().unwrap_or_default();
+                    let path = format!("res/{}/{}", dir_name, file_name);
+                    if is_xml && ty != "raw" {
+                        let xml = std::fs::read_to_string(&file)?;
+                        self.add_xml(&path, xml)
+                    } else {
+                        self.add_file(&path, file.clone())
+                    }
+                };
+                result.map_err(|err| anyhow::anyhow!("{}: {}", file.display(), err))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the resources of a library like an unpacked aar. Resources
+    /// declared by the app or a previously added library take precedence.
+    pub fn add_library_res_dir(&mut self, dir: &Path) -> Result<()> {
+        self.library = true;
+        let result = self.add_res_dir(dir);
+        self.library = false;
+        result
+    }
+
+    /// Adds the contents of a `res/values` file for the given config.
+    pub fn add_values(&mut self, config: ResTableConfig, xml: &str) -> Result<()> {
+        let config = self.config_id(config);
+        for entry in values::parse_values(xml)? {
+            match &entry.value {
+                Value::Styleable(attrs) => {
+                    let styleable = self.styleables.entry(entry.name).or_default();
+                    for attr in attrs {
+                        if !styleable.contains(attr) {
+                            styleable.push(attr.clone());
+                        }
+                    }
+                    continue;
+                }
+                Value::Attr { symbols, .. } => {
+                    for (symbol, _) in symbols {
+                        self.add(0, "id", symbol, Value::Item(Format::Bool, "false".into()))?;
+                    }
+                }
+                _ => {}
+            }
+            self.add(config, entry.ty, &entry.name, entry.value)?;
+        }
+        Ok(())
+    }
+
+    /// Adds an xml file like `res/layout/main.xml`, which is compiled by
+    /// the caller once the table is known. Ids declared with `@+id/` are
+    /// added to the table.
+    pub fn add_xml(&mut self, path: &str, xml: String) -> Result<()> {
+        let path = if let Some(path) = self.add_file_entry(path)? {
+            path
+        } else {
+            return Ok(());
+        };
+        let doc = Document::parse(&xml)?;
+        for node in doc.descendants() {
+            for attr in node.attributes() {
+                if let Some(id) = attr.value().strip_prefix("@+id/") {
+                    // like aapt ids are compiled to `false`
+                    self.add(0, "id", id, Value::Item(Format::Bool, "false".into()))?;
+                }
+            }
+        }
+        self.files.push((path, FileContents::Xml(xml)));
+        Ok(())
+    }
+
+    /// Adds a file like `res/drawable/logo.png` which is stored as is.
+    pub fn add_file(&mut self, path: &str, source: PathBuf) -> Result<()> {
+        if let Some(path) = self.add_file_entry(path)? {
+            self.files.push((path, FileContents::Raw(source)));
+        }
+        Ok(())
+    }
+
+    /// Returns the file resources keyed by their path in the apk.
+    pub fn files(&self) -> &[(String, FileContents)] {
+        &self.files
+    }
+
+    /// Adds the entry of a file resource and returns its normalized path,
+    /// or `None` when a library file is shadowed.
+    fn add_file_entry(&mut self, path: &str) -> Result<Option<String>> {
+        let (dir, file_name) = path
+            .strip_prefix("res/")
+            .and_then(|path| path.split_once('/'))
+            .filter(|(_, file_name)| !file_name.contains('/'))
+            .ok_or_else(|| anyhow::anyhow!("invalid resource path {}", path))?;
+        let (ty, config) = qualifiers::parse_dir_name(dir)?;
+        let ty = FILE_TYPES
+            .iter()
+            .find(|t| **t == ty)
+            .ok_or_else(|| anyhow::anyhow!("unsupported resource type {}", ty))?;
+        // `drawable-hdpi` is stored as `drawable-hdpi-v4` like aapt does
+        let path = match config.to_string().as_str() {
+            "" => format!("res/{}/{}", ty, file_name),
+            qualifiers => format!("res/{}-{}/{}", ty, qualifiers, file_name),
+        };
+        let name = file_name.split('.').next().unwrap_or_default();
+        let config = self.config_id(config);
+        if self.add(config, ty, name, Value::Item(Format::File, path.clone()))? {
+            Ok(Some(path))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn config_id(&mut self, config: ResTableConfig) -> usize {
+        if let Some(id) = self.configs.iter().position(|c| *c == config) {
+            id
+        } else {
+            self.configs.push(config);
+            self.configs.len() - 1
+        }
+    }
+
+    /// Adds a value, returns `false` when it is already declared by the app
+    /// or a previously added library.
+    fn add(&mut self, config: usize, ty: &'static str, name: &str, value: Value) -> Result<bool> {
+        let library = self.library;
+        let values = self
+            .types
+            .entry(ty)
+            .or_default()
+            .entry(name.to_string())
+            .or_default();
+        if let Some((_, existing)) = values.iter().find(|(c, _)| *c == config) {
+            // ids and attributes may be declared by multiple files
+            let redeclared = *existing == value && (ty == "id" || ty == "attr");
+            anyhow::ensure!(library || redeclared, "duplicate resource {}/{}", ty, name);
+            return Ok(false);
+        }
+        values.push((config, value));
+        Ok(true)
+    }
+
+    /// Assigns the type and entry ids, keeping the stable ids.
+    fn ids(&self) -> Result<Ids<'_>> {
+        let mut type_ids = BTreeMap::new();
+        for ty in self.types.keys() {
+            for (name, id) in self.stable_ids.ids(ty) {
+                let id = ResTableRef::from(id);
+                anyhow::ensure!(
+                    id.package() == PACKAGE_ID && id.ty() != 0,
+                    "invalid stable id {}/{} = {:#010x}",
+                    ty,
+                    name,
+                    u32::from(id)
+                );
+                let type_id = *type_ids.entry(*ty).or_insert_with(|| id.ty());
+                anyhow::ensure!(
+                    type_id == id.ty(),
+                    "stable ids assign multiple type ids to {}",
+                    ty
+                );
+            }
+        }
+        let mut used_types = type_ids.values().copied().collect::<BTreeSet<_>>();
+        anyhow::ensure!(
+            used_types.len() == type_ids.len(),
+            "stable ids assign a type id to multiple types"
+        );
+        let mut next_type = 1;
+        let mut types = BTreeMap::new();
+        for (ty, entries) in &self.types {
+            let type_id = if let Some(type_id) = type_ids.get(ty) {
+                *type_id
+            } else {
+                while used_types.contains(&next_type) {
+                    next_type += 1;
+                }
+                used_types.insert(next_type);
+                next_type
+            };
+            let mut entry_ids = BTreeMap::new();
+            let mut used_entries = BTreeSet::new();
+            for (name, id) in self.stable_ids.ids(ty) {
+                let entry_id = ResTableRef::from(id).entry();
+                anyhow::ensure!(
+                    used_entries.insert(entry_id),
+                    "stable ids assign {:#010x} to multiple resources",
+                    id
+                );
+                if let Some((name, _)) = entries.get_key_value(name) {
+                    entry_ids.insert(name.as_str(), entry_id);
+                }
+            }
+            let mut next_entry = used_entries.iter().next_back().map_or(0, |id| id + 1);
+            for name in entries.keys() {
+                entry_ids.entry(name.as_str()).or_insert_with(|| {
+                    next_entry += 1;
+                    next_entry - 1
+                });
+            }
+            types.insert(*ty, (type_id, entry_ids));
+        }
+        Ok(Ids { types })
+    }
+
+    /// Returns the ids of the resources and the attributes of the styleables
+    /// for generating `R` classes. Framework attributes are resolved using
+    /// `table`.
+    pub fn symbols(&self, table: &Table) -> Result<Symbols> {
+        let layout = self.ids()?;
+        let mut symbols = Symbols::default();
+        for (ty, (type_id, entries)) in &layout.types {
+            for (name, entry_id) in entries {
+                let id = ResTableRef::new(PACKAGE_ID, *type_id, *entry_id);
+                symbols.add_resource(ty, name, id.into());
+            }
+        }
+        for (name, attrs) in &self.styleables {
+            let mut ids = Vec::with_capacity(attrs.len());
+            for attr in attrs {
+                let id = if let Some(attr) = attr.strip_prefix("android:") {
+                    table.entry_by_ref(Ref::attr(attr))?.id()
+                } else {
+                    layout
+                        .get("attr", attr)
+                        .ok_or_else(|| anyhow::anyhow!("unknown attribute {}", attr))?
+                };
+                ids.push((attr.clone(), id.into()));
+            }
+            symbols.add_styleable(name, ids);
+        }
+        Ok(symbols)
+    }
+
+    /// Compiles the resources. References to other packages are resolved
+    /// using `table`.
+    pub fn compile(&self, table: &Table) -> Result<Chunk> {
+        let keys = self
+            .types
+            .values()
+            .flat_map(|entries| entries.keys())
+            .map(|key| key.as_str())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut compiler = Compiler {
+            resources: self,
+            table,
+            ids: self.ids()?,
+            strings: vec![],
+        };
+        let mut types = self
+            .types
+            .iter()
+            .map(|(ty, entries)| {
+                let (type_id, entry_ids) = &compiler.ids.types[ty];
+                (*type_id, *ty, entries, entry_ids.clone())
+            })
+            .collect::<Vec<_>>();
+        types.sort_by_key(|(type_id, _, _, _)| *type_id);
+        // type ids left unused by stable ids get an empty name
+        let type_count = types.last().map_or(0, |(type_id, _, _, _)| *type_id);
+        let mut type_strings = vec![String::new(); type_count as usize];
+        for (type_id, ty, _, _) in &types {
+            type_strings[*type_id as usize - 1] = ty.to_string();
+        }
+        let mut chunks = vec![
+            Chunk::StringPool(ResStringPoolHeader::UTF8_FLAG, type_strings, vec![], vec![]),
+            Chunk::StringPool(
+                ResStringPoolHeader::UTF8_FLAG,
+                keys.iter().map(|key| key.to_string()).collect(),
+                vec![],
+                vec![],
+            ),
+        ];
+        for (type_id, _, entries, entry_ids) in types {
+            // ids of removed resources are missing entries
+            let entry_count = entry_ids.values().max().map_or(0, |id| *id as usize + 1);
+            let mut spec = vec![0; entry_count];
+            for (name, values) in entries {
+                let first = &self.configs[values[0].0];
+                spec[entry_ids[name.as_str()] as usize] =
+                    values.iter().fold(0, |mask, (config, _)| {
+                        mask | first.diff(&self.configs[*config])
+                    });
+            }
+            chunks.push(Chunk::TableTypeSpec(
+                ResTableTypeSpecHeader {
+                    id: type_id,
+                    res0: 0,
+                    res1: 0,
+                    entry_count: entry_count as u32,
+                },
+                spec,
+            ));
+            for (config_id, config) in self.configs.iter().enumerate() {
+                let mut type_entries = vec![None; entry_count];
+                for (name, values) in entries {
+                    if let Some((_, value)) = values.iter().find(|(c, _)| *c == config_id) {
+                        let key = keys.binary_search(&name.as_str()).unwrap() as u32;
+                        type_entries[entry_ids[name.as_str()] as usize] =
+                            Some(compiler.compile_entry(key, name, value)?);
+                    }
+                }
+                if type_entries.iter().any(Option::is_some) {
+                    chunks.push(table_type(type_id, config.clone(), type_entries));
+                }
+            }
+        }
+        let type_strings_len = chunk_len(&chunks[0])?;
+        let package = Chunk::TablePackage(
+            ResTablePackageHeader {
+                id: PACKAGE_ID as u32,
+                name: self.package.clone(),
+                type_strings: PACKAGE_HEADER_SIZE,
+                last_public_type: type_count as u32,
+                key_strings: PACKAGE_HEADER_SIZE + type_strings_len,
+                last_public_key: keys.len() as u32,
+                type_id_offset: 0,
+            },
+            chunks,
+        );
+        Ok(Chunk::Table(
+            ResTableHeader { package_count: 1 },
+            vec![
+                Chunk::StringPool(
+                    ResStringPoolHeader::UTF8_FLAG,
+                    compiler.strings,
+                    vec![],
+                    vec![],
+                ),
+                package,
+            ],
+        ))
+    }
+}
+
+/// Type and entry ids of the resources keyed by their type and name.
+struct Ids<'a> {
+    types: BTreeMap<&'a str, (u8, BTreeMap<&'a str, u16>)>,
+}
+
+impl Ids<'_> {
+    fn get(&self, ty: &str, name: &str) -> Option<ResTableRef> {
+        let (type_id, entries) = self.types.get(ty)?;
+        Some(ResTableRef::new(PACKAGE_ID, *type_id, *entries.get(name)?))
+    }
+}
+
+struct Compiler<'a> {
+    resources: &'a Resources,
+    table: &'a Table,
+    ids: Ids<'a>,
+    strings: Vec<String>,
+}
+
+impl<'a> Compiler<'a> {
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(id) = self.strings.iter().position(|s2| s == s2) {
+            id as u32
+        } else {
+            self.strings.push(s.to_string());
+            self.strings.len() as u32 - 1
+        }
+    }
+
+    fn lookup(&self, r: Ref) -> Result<ResTableRef> {
+        match r.package() {
+            None => {}
+            Some(package) if package == self.resources.package => {}
+            Some(_) => return Ok(self.table.entry_by_ref(r)?.id()),
+        }
+        self.ids
+            .get(r.ty(), r.name())
+            .ok_or_else(|| anyhow::anyhow!("unknown resource @{}/{}", r.ty(), r.name()))
+    }
+
+    fn lookup_attr(&self, name: &str) -> Result<(ResTableRef, Option<Entry<'a>>)> {
+        if let Some(name) = name.strip_prefix("android:") {
+            let entry = self.table.entry_by_ref(Ref::attr(name))?;
+            return Ok((entry.id(), Some(entry)));
+        }
+        let id = self
+            .ids
+            .get("attr", name)
+            .ok_or_else(|| anyhow::anyhow!("unknown attribute {}", name))?;
+        Ok((id, None))
+    }
+
+    fn compile_entry(&mut self, key: u32, name: &str, value: &Value) -> Result<ResTableEntry> {
+        let (parent, mut map) = match value {
+            Value::Item(format, value) => {
+                return Ok(ResTableEntry {
+                    size: 8,
+                    flags: 0,
+                    key,
+                    value: ResTableValue::Simple(self.compile_item(*format, value)?),
+                });
+            }
+            Value::Style { parent, items } => {
+                let parent = self.style_parent(name, parent.as_deref())?;
+                let mut map = Vec::with_capacity(items.len());
+                for (name, value) in items {
+                    let (attr, entry) = self.lookup_attr(name)?;
+                    let value = if let Some(value) = entry.and_then(|e| self.compile_enum(e, value))
+                    {
+                        value
+                    } else if let Some(value) = self.compile_local_enum(name, value) {
+                        value
+                    } else {
+                        self.compile_item(Format::Any, value)?
+                    };
+                    map.push(ResTableMap {
+                        name: attr.into(),
+                        value,
+                    });
+                }
+                (parent, map)
+            }
+            Value::Array(format, items) => {
+                let mut map = Vec::with_capacity(items.len());
+                for (i, item) in items.iter().enumerate() {
+                    map.push(ResTableMap {
+                        name: ARRAY_KEY + i as u32,
+                        value: self.compile_item(*format, item)?,
+                    });
+                }
+                (0, map)
+            }
+            Value::Attr { format, symbols } => {
+                let data_type = symbol_type(*format);
+                let mut map = Vec::with_capacity(symbols.len() + 1);
+                map.push(ResTableMap {
+                    name: ATTR_TYPE,
+                    value: ResValue {
+                        size: 8,
+                        res0: 0,
+                        data_type: ResValueType::IntDec as u8,
+                        data: *format,
+                    },
+                });
+                for (symbol, data) in symbols {
+                    map.push(ResTableMap {
+                        name: self.lookup(Ref::new(None, "id", symbol))?.into(),
+                        value: ResValue {
+                            size: 8,
+                            res0: 0,
+                            data_type: data_type as u8,
+                            data: *data,
+                        },
+                    });
+                }
+                (0, map)
+            }
+            Value::Styleable(_) => anyhow::bail!("styleable {} is not a resource", name),
+        };
+        map.sort_by_key(|map| map.name);
+        Ok(ResTableEntry {
+            size: 16,
+            flags: 1,
+            key,
+            value: ResTableValue::Complex(
+                ResTableMapEntry {
+                    parent,
+                    count: map.len() as u32,
+                },
+                map,
+            ),
+        })
+    }
+
+    /// Styles without a parent attribute inherit from the style their
+    /// dotted name starts with.
+    fn style_parent(&self, name: &str, parent: Option<&str>) -> Result<u32> {
+        let parent = match parent {
+            Some("") => return Ok(0),
+            Some(parent) if parent.starts_with('@') => parent.to_string(),
+            Some(parent) => match parent.split_once(':') {
+                Some((package, parent)) => format!("@{}:style/{}", package, parent),
+                None => format!("@style/{}", parent),
+            },
+            None => match name.rsplit_once('.') {
+                Some((parent, _)) if self.ids.get("style", parent).is_some() => {
+                    format!("@style/{}", parent)
+                }
+                _ => return Ok(0),
+            },
+        };
+        Ok(self.lookup(Ref::parse(&parent)?)?.into())
+    }
+
+    fn compile_enum(&self, attr: Entry, value: &str) -> Option<ResValue> {
+        let value = value.trim();
+        if value.is_empty()
+            || !value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '|')
+        {
+            return None;
+        }
+        let mut data = 0;
+        let mut data_type = ResValueType::IntDec as u8;
+        for flag in value.split('|') {
+            let id = self.table.entry_by_ref(Ref::id(flag)).ok()?.id();
+            let value = attr.lookup_value(id)?;
+            data |= value.data;
+            data_type = value.data_type;
+        }
+        Some(ResValue {
+            size: 8,
+            res0: 0,
+            data_type,
+            data,
+        })
+    }
+
+    /// Compiles the symbols of an enum or flags attribute declared by the app.
+    fn compile_local_enum(&self, attr: &str, value: &str) -> Option<ResValue> {
+        let (format, symbols) = match self.resources.types.get("attr")?.get(attr)?.first()? {
+            (_, Value::Attr { format, symbols }) => (*format, symbols),
+            _ => return None,
+        };
+        let mut data = 0;
+        for flag in value.trim().split('|') {
+            let (_, value) = symbols.iter().find(|(name, _)| name == flag.trim())?;
+            data |= value;
+        }
+        Some(ResValue {
+            size: 8,
+            res0: 0,
+            data_type: symbol_type(format) as u8,
+            data,
+        })
+    }
+
+    fn compile_item(&mut self, format: Format, raw: &str) -> Result<ResValue> {
+        let value = raw.trim();
+        let (data, data_type) = match value {
+            "@null" => (0, ResValueType::Null),
+            "@empty" => (1, ResValueType::Null),
+            _ if value.starts_with('@') => (
+                self.lookup(Ref::parse(value)?)?.into(),
+                ResValueType::Reference,
+            ),
+            _ if value.starts_with('?') => {
+                let id = self.lookup(Ref::parse_attr(value)?)?;
+                (id.into(), ResValueType::Attribute)
+            }
+            _ => match format {
+                Format::String => (self.string(&values::unescape(raw)?), ResValueType::String),
+                Format::File => (self.string(value), ResValueType::String),
+                Format::Color => values::parse_color(value)
+                    .ok_or_else(|| anyhow::anyhow!("invalid color {}", value))?,
+                Format::Dimension => values::parse_dimension(value)
+                    .ok_or_else(|| anyhow::anyhow!("invalid dimension {}", value))?,
+                Format::Bool => (
+                    values::parse_bool(value)
+                        .ok_or_else(|| anyhow::anyhow!("invalid bool {}", value))?,
+                    ResValueType::IntBoolean,
+                ),
+                Format::Integer => values::parse_integer(value)
+                    .ok_or_else(|| anyhow::anyhow!("invalid integer {}", value))?,
+                Format::Any => {
+                    if let Some(data) = values::parse_bool(value) {
+                        (data, ResValueType::IntBoolean)
+                    } else if let Some(value) = values::parse_color(value)
+                        .or_else(|| values::parse_integer(value))
+                        .or_else(|| values::parse_dimension(value))
+                    {
+                        value
+                    } else if let Some(float) = value.parse::<f32>().ok().filter(|_| {
+                        value.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+                    }) {
+                        (float.to_bits(), ResValueType::Float)
+                    } else {
+                        (self.string(&values::unescape(raw)?), ResValueType::String)
+                    }
+                }
+            },
+        };
+        Ok(ResValue {
+            size: 8,
+            res0: 0,
+            data_type: data_type as u8,
+            data,
+        })
+    }
+}
+
+/// Flags are compiled to hex integers and enums to decimal integers.
+fn symbol_type(format: u32) -> ResValueType {
+    if format & ResAttributeType::Flags as u32 != 0 {
+        ResValueType::IntHex
+    } else {
+        ResValueType::IntDec
+    }
+}
+
+fn table_type(id: u8, config: ResTableConfig, entries: Vec<Option<ResTableEntry>>) -> Chunk {
+    let mut index = Vec::with_capacity(entries.len());
+    let mut offset = 0;
+    for entry in &entries {
+        if let Some(entry) = entry {
+            index.push(offset);
+            offset += match &entry.value {
+                ResTableValue::Simple(_) => 16,
+                ResTableValue::Complex(_, map) => 16 + 12 * map.len() as u32,
+            };
+        } else {
+            index.push(NO_ENTRY);
+        }
+    }
+    // chunk header, type header, config and index
+    let entries_start = 8 + 12 + config.size + 4 * entries.len() as u32;
+    Chunk::TableType(
+        ResTableTypeHeader {
+            id,
+            flags: 0,
+            res1: 0,
+            entry_count: entries.len() as u32,
+            entries_start,
+            config,
+        },
+        index,
+        entries,
+    )
+}
+
+fn chunk_len(chunk: &Chunk) -> Result<u32> {
+    let mut cursor = Cursor::new(vec![]);
+    chunk.write(&mut cursor)?;
+    Ok(cursor.into_inner().len() as u32)
+}
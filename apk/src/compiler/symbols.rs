@@ -0,0 +1,278 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Resource ids of an app and the attributes of its styleables, which are
+/// used to generate the `R` classes of the app and its libraries.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Symbols {
+    resources: BTreeMap<String, BTreeMap<String, u32>>,
+    styleables: BTreeMap<String, Vec<(String, u32)>>,
+}
+
+impl Symbols {
+    pub(crate) fn add_resource(&mut self, ty: &str, name: &str, id: u32) {
+        self.resources
+            .entry(ty.to_string())
+            .or_default()
+            .insert(name.to_string(), id);
+    }
+
+    /// Styleable attributes are sorted by id like aapt does, the index of an
+    /// attribute in the `R.styleable` array is its position.
+    pub(crate) fn add_styleable(&mut self, name: &str, mut attrs: Vec<(String, u32)>) {
+        attrs.sort_by_key(|(_, id)| *id);
+        self.styleables.insert(name.to_string(), attrs);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty() && self.styleables.is_empty()
+    }
+
+    /// Returns the id of a resource like `string`, `app_name`.
+    pub fn id(&self, ty: &str, name: &str) -> Option<u32> {
+        self.resources.get(ty)?.get(name).copied()
+    }
+
+    /// Returns the ids of the resources of a type keyed by their name.
+    pub(crate) fn ids(&self, ty: &str) -> impl Iterator<Item = (&str, u32)> {
+        self.resources
+            .get(ty)
+            .into_iter()
+            .flatten()
+            .map(|(name, id)| (name.as_str(), *id))
+    }
+
+    /// Returns the resource ids in the format of `aapt2 link --emit-ids`,
+    /// one `package:type/name = 0x7f010000` line per resource.
+    pub fn to_stable_ids(&self, package: &str) -> String {
+        let mut ids = String::new();
+        for (ty, entries) in &self.resources {
+            for (name, id) in entries {
+                writeln!(ids, "{}:{}/{} = {:#010x}", package, ty, name, id).unwrap();
+            }
+        }
+        ids
+    }
+
+    /// Parses resource ids written by [`Symbols::to_stable_ids`] or
+    /// `aapt2 link --emit-ids`, which can be passed to
+    /// [`Apk::set_stable_ids`](crate::Apk::set_stable_ids).
+    pub fn parse_stable_ids(ids: &str) -> Result<Self> {
+        let mut symbols = Self::default();
+        for line in ids.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let parsed = line.split_once('=').and_then(|(name, id)| {
+                let name = name.trim();
+                let name = name.split_once(':').map(|(_, name)| name).unwrap_or(name);
+                let (ty, name) = name.split_once('/')?;
+                let id = u32::from_str_radix(id.trim().strip_prefix("0x")?, 16).ok()?;
+                Some((ty, name, id))
+            });
+            let (ty, name, id) =
+                parsed.ok_or_else(|| anyhow::anyhow!("invalid stable id `{}`", line))?;
+            symbols.add_resource(ty, name, id);
+        }
+        Ok(symbols)
+    }
+
+    /// Returns the source of the `R` class of a java package.
+    pub fn to_java(&self, package: &str) -> String {
+        let mut java = String::new();
+        writeln!(java, "package {};\n", package).unwrap();
+        writeln!(java, "public final class R {{").unwrap();
+        for (ty, entries) in &self.resources {
+            writeln!(java, "    public static final class {} {{", ty).unwrap();
+            for (name, id) in entries {
+                writeln!(
+                    java,
+                    "        public static final int {} = {:#010x};",
+                    identifier(name),
+                    id
+                )
+                .unwrap();
+            }
+            writeln!(java, "    }}").unwrap();
+        }
+        if !self.styleables.is_empty() {
+            writeln!(java, "    public static final class styleable {{").unwrap();
+            for (name, attrs) in &self.styleables {
+                let name = identifier(name);
+                let ids = attrs
+                    .iter()
+                    .map(|(_, id)| format!("{:#010x}", id))
+                    .collect::<Vec<_>>();
+                writeln!(
+                    java,
+                    "        public static final int[] {} = {{ {} }};",
+                    name,
+                    ids.join(", ")
+                )
+                .unwrap();
+                for (i, (attr, _)) in attrs.iter().enumerate() {
+                    writeln!(
+                        java,
+                        "        public static final int {}_{} = {};",
+                        name,
+                        identifier(attr),
+                        i
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(java, "    }}").unwrap();
+        }
+        writeln!(java, "}}").unwrap();
+        java
+    }
+
+    /// Returns rust constants with the same layout as the `R` class, like
+    /// `string::app_name`, for native code calling into java.
+    pub fn to_rust(&self) -> String {
+        let mut rust = String::new();
+        writeln!(rust, "// Resource ids of the app, generated by xbuild.").unwrap();
+        for (ty, entries) in &self.resources {
+            writeln!(rust, "#[allow(dead_code, non_upper_case_globals)]").unwrap();
+            writeln!(rust, "pub mod {} {{", rust_identifier(ty)).unwrap();
+            for (name, id) in entries {
+                writeln!(
+                    rust,
+                    "    pub const {}: u32 = {:#010x};",
+                    rust_identifier(name),
+                    id
+                )
+                .unwrap();
+            }
+            writeln!(rust, "}}").unwrap();
+        }
+        if !self.styleables.is_empty() {
+            writeln!(rust, "#[allow(dead_code, non_upper_case_globals)]").unwrap();
+            writeln!(rust, "pub mod styleable {{").unwrap();
+            for (name, attrs) in &self.styleables {
+                let ids = attrs
+                    .iter()
+                    .map(|(_, id)| format!("{:#010x}", id))
+                    .collect::<Vec<_>>();
+                writeln!(
+                    rust,
+                    "    pub const {}: [u32; {}] = [{}];",
+                    rust_identifier(name),
+                    ids.len(),
+                    ids.join(", ")
+                )
+                .unwrap();
+                for (i, (attr, _)) in attrs.iter().enumerate() {
+                    writeln!(
+                        rust,
+                        "    pub const {}_{}: usize = {};",
+                        identifier(name),
+                        identifier(attr),
+                        i
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(rust, "}}").unwrap();
+        }
+        rust
+    }
+}
+
+/// Maps a resource name like `Theme.AppCompat` or `android:text` to a java
+/// identifier.
+fn identifier(name: &str) -> String {
+    name.replace(['.', ':'], "_")
+}
+
+/// Maps a resource name to a rust identifier, keywords are escaped.
+fn rust_identifier(name: &str) -> String {
+    let name = identifier(name);
+    match name.as_str() {
+        "crate" | "self" | "super" | "Self" => format!("{}_", name),
+        "as" | "async" | "await" | "box" | "break" | "const" | "continue" | "dyn" | "else"
+        | "enum" | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop"
+        | "match" | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct"
+        | "trait" | "true" | "try" | "type" | "unsafe" | "use" | "where" | "while" | "yield" => {
+            format!("r#{}", name)
+        }
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_java() {
+        let mut symbols = Symbols::default();
+        symbols.add_resource("attr", "orientation", 0x7f01_0000);
+        symbols.add_resource("style", "Theme.Dark", 0x7f02_0000);
+        symbols.add_styleable(
+            "Layout",
+            vec![
+                ("orientation".into(), 0x7f01_0000),
+                ("android:text".into(), 0x0101_014f),
+            ],
+        );
+        assert_eq!(
+            symbols.to_java("com.example"),
+            r#"package com.example;
+
+public final class R {
+    public static final class attr {
+        public static final int orientation = 0x7f010000;
+    }
+    public static final class style {
+        public static final int Theme_Dark = 0x7f020000;
+    }
+    public static final class styleable {
+        public static final int[] Layout = { 0x0101014f, 0x7f010000 };
+        public static final int Layout_android_text = 0;
+        public static final int Layout_orientation = 1;
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_stable_ids() -> Result<()> {
+        let mut symbols = Symbols::default();
+        symbols.add_resource("string", "app_name", 0x7f01_0000);
+        symbols.add_resource("style", "Theme.Dark", 0x7f02_0001);
+        let ids = symbols.to_stable_ids("com.example");
+        assert_eq!(
+            ids,
+            "com.example:string/app_name = 0x7f010000\ncom.example:style/Theme.Dark = 0x7f020001\n"
+        );
+        assert_eq!(Symbols::parse_stable_ids(&ids)?, symbols);
+        assert!(Symbols::parse_stable_ids("string/app_name = 7f010000").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_rust() {
+        let mut symbols = Symbols::default();
+        symbols.add_resource("string", "app_name", 0x7f01_0000);
+        symbols.add_resource("id", "type", 0x7f02_0000);
+        symbols.add_styleable("Layout", vec![("android:text".into(), 0x0101_014f)]);
+        assert_eq!(
+            symbols.to_rust(),
+            r#"// Resource ids of the app, generated by xbuild.
+#[allow(dead_code, non_upper_case_globals)]
+pub mod id {
+    pub const r#type: u32 = 0x7f020000;
+}
+#[allow(dead_code, non_upper_case_globals)]
+pub mod string {
+    pub const app_name: u32 = 0x7f010000;
+}
+#[allow(dead_code, non_upper_case_globals)]
+pub mod styleable {
+    pub const Layout: [u32; 1] = [0x0101014f];
+    pub const Layout_android_text: usize = 0;
+}
+"#
+        );
+    }
+}
@@ -10,6 +10,11 @@ pub struct Ref<'a> {
 }
 
 impl<'a> Ref<'a> {
+    /// Reference to a resource of `package`, `None` references the app.
+    pub fn new(package: Option<&'a str>, ty: &'a str, name: &'a str) -> Self {
+        Self { package, ty, name }
+    }
+
     pub fn attr(name: &'a str) -> Self {
         Self {
             package: Some("android"),
@@ -30,6 +35,8 @@ impl<'a> Ref<'a> {
         let s = s
             .strip_prefix('@')
             .ok_or_else(|| anyhow::anyhow!("invalid reference {}: expected `@`", s))?;
+        // `@+id/name` declares the id it references
+        let s = s.strip_prefix('+').unwrap_or(s);
         let (descr, name) = s
             .split_once('/')
             .ok_or_else(|| anyhow::anyhow!("invalid reference {}: expected `/`", s))?;
@@ -40,6 +47,33 @@ impl<'a> Ref<'a> {
         };
         Ok(Self { package, ty, name })
     }
+
+    /// Parses a reference to a theme attribute like `?android:attr/textColor`
+    /// or `?colorPrimary`.
+    pub fn parse_attr(s: &'a str) -> Result<Self> {
+        let s = s
+            .strip_prefix('?')
+            .ok_or_else(|| anyhow::anyhow!("invalid attribute reference {}: expected `?`", s))?;
+        let (package, name) = if let Some((package, name)) = s.split_once(':') {
+            (Some(package), name)
+        } else {
+            (None, s)
+        };
+        let (ty, name) = name.split_once('/').unwrap_or(("attr", name));
+        Ok(Self { package, ty, name })
+    }
+
+    pub fn package(&self) -> Option<&'a str> {
+        self.package
+    }
+
+    pub fn ty(&self) -> &'a str {
+        self.ty
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
 }
 
 struct Package<'a> {
@@ -51,12 +85,12 @@ struct Package<'a> {
 
 impl<'a> Package<'a> {
     fn new(id: u8, chunks: &'a [Chunk]) -> Result<Self> {
-        let types = if let Chunk::StringPool(strings, _) = &chunks[0] {
+        let types = if let Chunk::StringPool(_, strings, _, _) = &chunks[0] {
             strings
         } else {
             anyhow::bail!("invalid package");
         };
-        let keys = if let Chunk::StringPool(strings, _) = &chunks[1] {
+        let keys = if let Chunk::StringPool(_, strings, _, _) = &chunks[1] {
             strings
         } else {
             anyhow::bail!("invalid package");
@@ -88,6 +122,24 @@ impl<'a> Package<'a> {
         Ok(id as u32)
     }
 
+    fn lookup_key(&self, ty: u8, entry: u16) -> Result<&'a str> {
+        for chunk in self.chunks {
+            if let Chunk::TableType(header, _offsets, entries) = chunk {
+                if header.id != ty {
+                    continue;
+                }
+                if let Some(Some(entry)) = entries.get(entry as usize) {
+                    return self
+                        .keys
+                        .get(entry.key as usize)
+                        .map(|key| key.as_str())
+                        .ok_or_else(|| anyhow::anyhow!("invalid key id {}", entry.key));
+                }
+            }
+        }
+        anyhow::bail!("failed to locate entry {} of type {}", entry, ty);
+    }
+
     fn lookup_type(&self, id: u8) -> Result<Type<'a>> {
         for chunk in self.chunks {
             if let Chunk::TableType(header, _offsets, entries) = chunk {
@@ -162,16 +214,36 @@ impl<'a> Entry<'a> {
             if data == 0b111110 {
                 return Some(ResAttributeType::String);
             }
-            if let Some(value) = ResAttributeType::from_u32(entries[0].value.data) {
-                Some(value)
-            } else {
-                panic!("attribute_type: 0x{:x}", data);
-            }
+            // attributes accepting multiple formats are handled using
+            // `attribute_format`
+            ResAttributeType::from_u32(data)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the bitmask of `ResAttributeType`s accepted by an attribute.
+    pub fn attribute_format(self) -> Option<u32> {
+        if let ResTableValue::Complex(_, entries) = &self.entry.value {
+            Some(entries[0].value.data)
         } else {
             None
         }
     }
 
+    /// Returns the symbols of an enum or flags attribute and their values.
+    pub fn attribute_symbols(self) -> impl Iterator<Item = (ResTableRef, ResValue)> + 'a {
+        let entries: &'a [_] = match &self.entry.value {
+            ResTableValue::Complex(_, entries) => entries,
+            ResTableValue::Simple(_) => &[],
+        };
+        entries
+            .iter()
+            .map(|entry| (ResTableRef::from(entry.name), entry.value))
+            // `ATTR_MIN`, `ATTR_MAX` and friends are not ids
+            .filter(|(id, _)| id.ty() != 0)
+    }
+
     pub fn lookup_value(&self, id: ResTableRef) -> Option<ResValue> {
         if let ResTableValue::Complex(_, entries) = &self.entry.value {
             for entry in &entries[1..] {
@@ -239,9 +311,29 @@ impl Table {
         ty.lookup_entry(id)
     }
 
-    /*pub fn entry(&self, r: ResTableRef) -> Result<Entry> {
+    pub fn entry(&self, r: ResTableRef) -> Result<Entry<'_>> {
         let package = self.lookup_package(r.package())?;
         let ty = package.lookup_type(r.ty())?;
         ty.lookup_entry(r.entry())
-    }*/
+    }
+
+    /// Returns the package, type and name of a resource id.
+    pub fn entry_name(&self, r: ResTableRef) -> Result<(&str, &str, &str)> {
+        for package in &self.packages {
+            if let Chunk::TablePackage(header, chunks) = package {
+                if header.id != r.package() as u32 {
+                    continue;
+                }
+                let package = Package::new(r.package(), chunks)?;
+                let ty = r
+                    .ty()
+                    .checked_sub(1)
+                    .and_then(|ty| package.types.get(ty as usize))
+                    .ok_or_else(|| anyhow::anyhow!("failed to locate type {}", r.ty()))?;
+                let key = package.lookup_key(r.ty(), r.entry())?;
+                return Ok((&header.name, ty, key));
+            }
+        }
+        anyhow::bail!("failed to locate package {}", r.package());
+    }
 }
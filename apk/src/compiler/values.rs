@@ -0,0 +1,357 @@
+use crate::res::{ResAttributeType, ResValueType};
+use anyhow::Result;
+use roxmltree::{Document, Node, NodeType};
+
+/// Expected format of a value, references are allowed for every format.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Any,
+    String,
+    Color,
+    Dimension,
+    Bool,
+    Integer,
+    File,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    Item(Format, String),
+    Style {
+        parent: Option<String>,
+        items: Vec<(String, String)>,
+    },
+    Array(Format, Vec<String>),
+    /// Attribute accepting a bitmask of `ResAttributeType`s. Enum and flag
+    /// symbols are mapped to their value.
+    Attr {
+        format: u32,
+        symbols: Vec<(String, u32)>,
+    },
+    /// Attributes of a `<declare-styleable>`, which is only used to generate
+    /// the `R` class.
+    Styleable(Vec<String>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValueEntry {
+    pub ty: &'static str,
+    pub name: String,
+    pub value: Value,
+}
+
+/// Parses a file in `res/values`.
+pub fn parse_values(xml: &str) -> Result<Vec<ValueEntry>> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    anyhow::ensure!(
+        root.tag_name().name() == "resources",
+        "expected <resources> got <{}>",
+        root.tag_name().name()
+    );
+    let mut entries = vec![];
+    for node in root.children().filter(|node| node.is_element()) {
+        let tag = node.tag_name().name();
+        let (ty, value) = match tag {
+            "string" => ("string", Value::Item(Format::String, text(node))),
+            "color" => ("color", Value::Item(Format::Color, text(node))),
+            "dimen" => ("dimen", Value::Item(Format::Dimension, text(node))),
+            "bool" => ("bool", Value::Item(Format::Bool, text(node))),
+            "integer" => ("integer", Value::Item(Format::Integer, text(node))),
+            "string-array" => ("array", Value::Array(Format::String, items(node))),
+            "integer-array" => ("array", Value::Array(Format::Integer, items(node))),
+            "array" => ("array", Value::Array(Format::Any, items(node))),
+            "style" => {
+                let parent = node.attribute("parent").map(|parent| parent.to_string());
+                let mut items = vec![];
+                for item in node.children().filter(|node| node.is_element()) {
+                    let name = item
+                        .attribute("name")
+                        .ok_or_else(|| anyhow::anyhow!("style item without a name"))?;
+                    items.push((name.to_string(), text(item)));
+                }
+                ("style", Value::Style { parent, items })
+            }
+            "attr" => (
+                "attr",
+                attr(node)?.unwrap_or(Value::Attr {
+                    format: ResAttributeType::Any as u32,
+                    symbols: vec![],
+                }),
+            ),
+            "declare-styleable" => {
+                let mut attrs = vec![];
+                for child in node.children().filter(|node| node.has_tag_name("attr")) {
+                    let name = child
+                        .attribute("name")
+                        .ok_or_else(|| anyhow::anyhow!("<attr> without a name"))?;
+                    // attributes without a format are declared elsewhere
+                    if !name.starts_with("android:") {
+                        if let Some(value) = attr(child)? {
+                            entries.push(ValueEntry {
+                                ty: "attr",
+                                name: name.to_string(),
+                                value,
+                            });
+                        }
+                    }
+                    attrs.push(name.to_string());
+                }
+                ("styleable", Value::Styleable(attrs))
+            }
+            "item" => {
+                let float = node.attribute("format") == Some("float");
+                let (ty, format) = match node.attribute("type").unwrap_or_default() {
+                    "dimen" if float => ("dimen", Format::Any),
+                    "dimen" => ("dimen", Format::Dimension),
+                    "fraction" => ("fraction", Format::Dimension),
+                    "string" => ("string", Format::String),
+                    "color" => ("color", Format::Color),
+                    "drawable" => ("drawable", Format::Color),
+                    "bool" => ("bool", Format::Bool),
+                    "integer" => ("integer", Format::Integer),
+                    // like aapt ids are compiled to `false`
+                    "id" => ("id", Format::Bool),
+                    ty => {
+                        tracing::warn!("skipping unsupported resource <item type=\"{}\">", ty);
+                        continue;
+                    }
+                };
+                let value = if ty == "id" {
+                    "false".to_string()
+                } else {
+                    text(node)
+                };
+                (ty, Value::Item(format, value))
+            }
+            "drawable" => ("drawable", Value::Item(Format::Color, text(node))),
+            "fraction" => ("fraction", Value::Item(Format::Dimension, text(node))),
+            _ => {
+                tracing::warn!("skipping unsupported resource <{}>", tag);
+                continue;
+            }
+        };
+        let name = node
+            .attribute("name")
+            .ok_or_else(|| anyhow::anyhow!("<{}> without a name", tag))?;
+        entries.push(ValueEntry {
+            ty,
+            name: name.to_string(),
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+/// Parses an `<attr>`, returns `None` when it has neither a format nor
+/// symbols and only references an attribute declared elsewhere.
+fn attr(node: Node) -> Result<Option<Value>> {
+    let mut format = 0;
+    if let Some(formats) = node.attribute("format") {
+        for name in formats.split('|') {
+            format |= match name.trim() {
+                "reference" => ResAttributeType::Reference,
+                "string" => ResAttributeType::String,
+                "integer" => ResAttributeType::Integer,
+                "boolean" => ResAttributeType::Boolean,
+                "color" => ResAttributeType::Color,
+                "float" => ResAttributeType::Float,
+                "dimension" => ResAttributeType::Dimension,
+                "fraction" => ResAttributeType::Fraction,
+                "enum" => ResAttributeType::Enum,
+                "flags" => ResAttributeType::Flags,
+                _ => anyhow::bail!("unknown attribute format {}", name),
+            } as u32;
+        }
+    }
+    let mut symbols = vec![];
+    for child in node.children().filter(|node| node.is_element()) {
+        format |= match child.tag_name().name() {
+            "enum" => ResAttributeType::Enum,
+            "flag" => ResAttributeType::Flags,
+            tag => anyhow::bail!("unexpected <{}> in <attr>", tag),
+        } as u32;
+        let name = child
+            .attribute("name")
+            .ok_or_else(|| anyhow::anyhow!("<{}> without a name", child.tag_name().name()))?;
+        let value = child
+            .attribute("value")
+            .and_then(parse_integer)
+            .ok_or_else(|| anyhow::anyhow!("invalid value for {}", name))?;
+        symbols.push((name.to_string(), value.0));
+    }
+    if format == 0 {
+        return Ok(None);
+    }
+    Ok(Some(Value::Attr { format, symbols }))
+}
+
+fn items(node: Node) -> Vec<String> {
+    node.children()
+        .filter(|node| node.has_tag_name("item"))
+        .map(text)
+        .collect()
+}
+
+/// Returns the text content of a node, styling tags like `<b>` are dropped.
+fn text(node: Node) -> String {
+    node.descendants()
+        .filter(|node| node.node_type() == NodeType::Text)
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+/// Processes a string like aapt does. Whitespace is collapsed unless it
+/// is quoted and escape sequences are replaced.
+pub fn unescape(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut quoted = false;
+    let mut space = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() && !quoted {
+            space = true;
+            continue;
+        }
+        if space && !out.is_empty() {
+            out.push(' ');
+        }
+        space = false;
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| anyhow::anyhow!("invalid unicode escape \\u{}", hex))?;
+                    out.push(c);
+                }
+                Some(c) => out.push(c),
+                None => anyhow::bail!("trailing `\\` in {}", s),
+            },
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+pub fn parse_bool(s: &str) -> Option<u32> {
+    match s {
+        "true" => Some(0xffff_ffff),
+        "false" => Some(0),
+        _ => None,
+    }
+}
+
+pub fn parse_integer(s: &str) -> Option<(u32, ResValueType)> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        Some((value, ResValueType::IntHex))
+    } else {
+        let value: i32 = s.parse().ok()?;
+        Some((value as u32, ResValueType::IntDec))
+    }
+}
+
+pub fn parse_color(s: &str) -> Option<(u32, ResValueType)> {
+    let hex = s.strip_prefix('#')?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let expand = |value: u32| {
+        (0..4).fold(0, |color, i| {
+            let nibble = (value >> (i * 4)) & 0xf;
+            color | (nibble * 0x11) << (i * 8)
+        })
+    };
+    Some(match hex.len() {
+        3 => (0xff00_0000 | expand(value), ResValueType::IntColorRgb4),
+        4 => (expand(value), ResValueType::IntColorArgb4),
+        6 => (0xff00_0000 | value, ResValueType::IntColorRgb8),
+        8 => (value, ResValueType::IntColorArgb8),
+        _ => return None,
+    })
+}
+
+/// Parses dimensions like `16dp` and fractions like `50%p`.
+pub fn parse_dimension(s: &str) -> Option<(u32, ResValueType)> {
+    let split = s.find(|c: char| c.is_ascii_alphabetic() || c == '%')?;
+    let (number, unit) = s.split_at(split);
+    let value: f32 = number.parse().ok()?;
+    let (value, unit, ty) = match unit {
+        "px" => (value, 0, ResValueType::Dimension),
+        "dp" | "dip" => (value, 1, ResValueType::Dimension),
+        "sp" => (value, 2, ResValueType::Dimension),
+        "pt" => (value, 3, ResValueType::Dimension),
+        "in" => (value, 4, ResValueType::Dimension),
+        "mm" => (value, 5, ResValueType::Dimension),
+        "%" => (value / 100.0, 0, ResValueType::Fraction),
+        "%p" => (value / 100.0, 1, ResValueType::Fraction),
+        _ => return None,
+    };
+    Some((float_to_complex(value) | unit, ty))
+}
+
+/// Encodes a float as a 24 bit mantissa and a radix.
+fn float_to_complex(value: f32) -> u32 {
+    let neg = value < 0.0;
+    let bits = (value.abs() * (1 << 23) as f32 + 0.5) as u64;
+    let (radix, shift) = if bits & 0x7f_ffff == 0 {
+        (0, 23)
+    } else if bits & 0xffff_ffff_ff80_0000 == 0 {
+        (3, 0)
+    } else if bits & 0xffff_ffff_8000_0000 == 0 {
+        (2, 8)
+    } else if bits & 0xffff_ff80_0000_0000 == 0 {
+        (1, 16)
+    } else {
+        (0, 23)
+    };
+    let mut mantissa = ((bits >> shift) & 0xff_ffff) as u32;
+    if neg {
+        mantissa = mantissa.wrapping_neg() & 0xff_ffff;
+    }
+    mantissa << 8 | radix << 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() -> Result<()> {
+        assert_eq!(unescape("  hello \n  world ")?, "hello world");
+        assert_eq!(unescape(r#"don\'t \"quote\"\nme"#)?, "don't \"quote\"\nme");
+        assert_eq!(unescape(r#""  spaced  ""#)?, "  spaced  ");
+        assert_eq!(unescape(r"été")?, "été");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            parse_color("#f00"),
+            Some((0xffff_0000, ResValueType::IntColorRgb4))
+        );
+        assert_eq!(
+            parse_color("#80ff0000"),
+            Some((0x80ff_0000, ResValueType::IntColorArgb8))
+        );
+        assert_eq!(
+            parse_dimension("16dp"),
+            Some((0x1001, ResValueType::Dimension))
+        );
+        assert_eq!(
+            parse_dimension("1.5sp"),
+            Some((0x00c0_0022, ResValueType::Dimension))
+        );
+        assert_eq!(
+            parse_dimension("50%"),
+            Some((0x4000_0030, ResValueType::Fraction))
+        );
+        assert_eq!(parse_integer("-1"), Some((u32::MAX, ResValueType::IntDec)));
+        assert_eq!(parse_integer("0x10"), Some((16, ResValueType::IntHex)));
+    }
+}
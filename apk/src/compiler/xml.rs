@@ -1,8 +1,8 @@
-use crate::compiler::attributes::{StringPoolBuilder, Strings};
+use crate::compiler::attributes::{attr_package, compile_attr, StringPoolBuilder, Strings};
 use crate::compiler::table::Table;
 use crate::res::{
-    Chunk, ResValue, ResValueType, ResXmlAttribute, ResXmlEndElement, ResXmlNamespace,
-    ResXmlNodeHeader, ResXmlStartElement,
+    Chunk, ResStringPoolHeader, ResValue, ResValueType, ResXmlAttribute, ResXmlEndElement,
+    ResXmlNamespace, ResXmlNodeHeader, ResXmlStartElement,
 };
 use anyhow::Result;
 use roxmltree::{Document, Node, NodeType};
@@ -36,12 +36,20 @@ pub fn compile_xml(xml: &str, table: &Table) -> Result<Chunk> {
         ));
     }
 
-    chunks[0] = Chunk::StringPool(strings.strings, vec![]);
+    chunks[0] = Chunk::StringPool(
+        ResStringPoolHeader::UTF8_FLAG,
+        strings.strings,
+        vec![],
+        vec![],
+    );
     chunks[1] = Chunk::XmlResourceMap(strings.map);
     Ok(Chunk::Xml(chunks))
 }
 
-fn build_string_pool<'a>(node: Node<'a, 'a>, builder: &mut StringPoolBuilder<'a>) -> Result<()> {
+pub fn build_string_pool<'a>(
+    node: Node<'a, 'a>,
+    builder: &mut StringPoolBuilder<'a>,
+) -> Result<()> {
     if node.node_type() != NodeType::Element {
         for node in node.children() {
             build_string_pool(node, builder)?;
@@ -91,8 +99,8 @@ fn compile_node(
             "style" => style_index = i as u16 + 1,
             _ => {}
         }
-        let value = if let Some("http://schemas.android.com/apk/res/android") = attr.namespace() {
-            super::attributes::compile_attr(table, attr.name(), attr.value(), strings)?
+        let value = if let Some(package) = attr_package(attr.namespace()) {
+            compile_attr(table, package, attr.name(), attr.value(), strings)?
         } else if attr.name() == "platformBuildVersionCode"
             || attr.name() == "platformBuildVersionName"
         {
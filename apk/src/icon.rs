@@ -0,0 +1,208 @@
+use crate::compiler::{mipmap_variants, Resources};
+use anyhow::Result;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use xcommon::{Scaler, ScalerOpts};
+
+/// Size of a legacy launcher icon in dp.
+const ICON_DP: u32 = 48;
+/// Size of an adaptive icon layer in dp.
+const LAYER_DP: u32 = 108;
+
+/// Background layer of an [`AdaptiveIcon`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IconBackground {
+    /// Color like `#ffffff`.
+    Color(String),
+    Image(PathBuf),
+}
+
+/// Launcher icon used by android 8 and later, which is masked to the shape
+/// used by the launcher. Layers are 108x108dp of which the center 72x72dp
+/// are always visible.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdaptiveIcon {
+    pub foreground: PathBuf,
+    pub background: IconBackground,
+    /// Used by launchers showing themed icons on android 13 and later.
+    pub monochrome: Option<PathBuf>,
+}
+
+/// Adds the `icon` mipmap and returns the pngs that need to be written.
+/// When an adaptive icon is used the legacy icon defaults to its foreground.
+pub(crate) fn add_icon(
+    resources: &mut Resources,
+    icon: Option<&Path>,
+    adaptive: Option<&AdaptiveIcon>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut files = vec![];
+    let legacy = icon.or_else(|| adaptive.map(|icon| icon.foreground.as_path()));
+    if let Some(path) = legacy {
+        add_mipmap(resources, &mut files, "icon", path, ICON_DP)?;
+    }
+    if let Some(adaptive) = adaptive {
+        let foreground = &adaptive.foreground;
+        add_mipmap(
+            resources,
+            &mut files,
+            "icon_foreground",
+            foreground,
+            LAYER_DP,
+        )?;
+        let background = match &adaptive.background {
+            IconBackground::Color(color) => {
+                let xml = format!(
+                    r#"<resources><color name="icon_background">{}</color></resources>"#,
+                    color
+                );
+                resources.add_values(Default::default(), &xml)?;
+                "@color/icon_background"
+            }
+            IconBackground::Image(path) => {
+                add_mipmap(resources, &mut files, "icon_background", path, LAYER_DP)?;
+                "@mipmap/icon_background"
+            }
+        };
+        let mut xml = format!(
+            r#"<adaptive-icon xmlns:android="http://schemas.android.com/apk/res/android"><background android:drawable="{}" /><foreground android:drawable="@mipmap/icon_foreground" />"#,
+            background
+        );
+        if let Some(path) = adaptive.monochrome.as_deref() {
+            add_mipmap(resources, &mut files, "icon_monochrome", path, LAYER_DP)?;
+            xml.push_str(r#"<monochrome android:drawable="@mipmap/icon_monochrome" />"#);
+        }
+        xml.push_str("</adaptive-icon>");
+        resources.add_xml("res/mipmap-anydpi-v26/icon.xml", xml)?;
+    }
+    Ok(files)
+}
+
+fn add_mipmap(
+    resources: &mut Resources,
+    files: &mut Vec<(String, Vec<u8>)>,
+    name: &str,
+    path: &Path,
+    size_dp: u32,
+) -> Result<()> {
+    let mut scaler = Scaler::open(path)?;
+    scaler.optimize();
+    resources.add_mipmap(name, size_dp)?;
+    for (path, _, size) in mipmap_variants(name, size_dp) {
+        let mut buf = vec![];
+        scaler.write(&mut Cursor::new(&mut buf), ScalerOpts::new(size))?;
+        files.push((path, buf));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::FileContents;
+
+    fn create_png(name: &str) -> Result<PathBuf> {
+        let path = std::env::temp_dir().join(name);
+        image::RgbaImage::new(512, 512).save(&path)?;
+        Ok(path)
+    }
+
+    fn icon_xml(resources: &Resources) -> Option<&str> {
+        resources
+            .files()
+            .iter()
+            .find_map(|(path, contents)| match contents {
+                FileContents::Xml(xml) if path == "res/mipmap-anydpi-v26/icon.xml" => {
+                    Some(xml.as_str())
+                }
+                _ => None,
+            })
+    }
+
+    fn png_size(png: &[u8]) -> Result<u32> {
+        let img = image::load_from_memory(png)?;
+        Ok(img.width())
+    }
+
+    #[test]
+    fn test_add_adaptive_icon() -> Result<()> {
+        let foreground = create_png("test_add_adaptive_icon_foreground.png")?;
+        let monochrome = create_png("test_add_adaptive_icon_monochrome.png")?;
+        let mut resources = Resources::new("com.example.icon");
+        let icon = AdaptiveIcon {
+            foreground,
+            background: IconBackground::Color("#ff0000".into()),
+            monochrome: Some(monochrome),
+        };
+        let files = add_icon(&mut resources, None, Some(&icon))?;
+        let paths = files
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>();
+        for (name, size_dp) in [
+            ("icon", ICON_DP),
+            ("icon_foreground", LAYER_DP),
+            ("icon_monochrome", LAYER_DP),
+        ] {
+            for (path, _, size) in mipmap_variants(name, size_dp) {
+                assert!(paths.contains(&path.as_str()), "{}", path);
+                let (_, png) = files.iter().find(|(p, _)| *p == path).unwrap();
+                assert_eq!(png_size(png)?, size);
+            }
+        }
+        assert!(!paths.iter().any(|path| path.contains("icon_background")));
+        let xml = icon_xml(&resources).unwrap();
+        let doc = roxmltree::Document::parse(xml)?;
+        let layers = doc
+            .root_element()
+            .children()
+            .map(|node| {
+                let drawable = node
+                    .attribute(("http://schemas.android.com/apk/res/android", "drawable"))
+                    .unwrap();
+                (node.tag_name().name(), drawable)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(doc.root_element().tag_name().name(), "adaptive-icon");
+        assert_eq!(
+            layers,
+            [
+                ("background", "@color/icon_background"),
+                ("foreground", "@mipmap/icon_foreground"),
+                ("monochrome", "@mipmap/icon_monochrome"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_adaptive_icon_image_background() -> Result<()> {
+        let legacy = create_png("test_add_adaptive_icon_image_background_legacy.png")?;
+        let foreground = create_png("test_add_adaptive_icon_image_background_foreground.png")?;
+        let background = create_png("test_add_adaptive_icon_image_background.png")?;
+        let mut resources = Resources::new("com.example.icon");
+        let icon = AdaptiveIcon {
+            foreground,
+            background: IconBackground::Image(background),
+            monochrome: None,
+        };
+        let files = add_icon(&mut resources, Some(&legacy), Some(&icon))?;
+        for (path, _, size) in mipmap_variants("icon_background", LAYER_DP) {
+            let (_, png) = files.iter().find(|(p, _)| *p == path).unwrap();
+            assert_eq!(png_size(png)?, size);
+        }
+        let xml = icon_xml(&resources).unwrap();
+        assert!(xml.contains(r#"<background android:drawable="@mipmap/icon_background" />"#));
+        assert!(!xml.contains("monochrome"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_legacy_icon() -> Result<()> {
+        let legacy = create_png("test_add_legacy_icon.png")?;
+        let mut resources = Resources::new("com.example.icon");
+        let files = add_icon(&mut resources, Some(&legacy), None)?;
+        assert_eq!(files.len(), mipmap_variants("icon", ICON_DP).count());
+        assert!(icon_xml(&resources).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! APK signature scheme v4.
+//!
+//! The signature is stored in a `.idsig` file next to the apk. It contains
+//! the fs-verity merkle tree of the apk, which allows `adb install --incremental`
+//! to stream the apk to the device while it is being installed.
+use anyhow::Result;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rasn_pkix::Certificate;
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use xcommon::Signer;
+
+const VERSION: u32 = 2;
+const HASH_ALGORITHM_SHA256: u32 = 1;
+const LOG2_BLOCK_SIZE: u8 = 12;
+const BLOCK_SIZE: usize = 1 << LOG2_BLOCK_SIZE;
+
+/// Returns the path of the v4 signature of an apk.
+pub fn idsig_path(apk: &Path) -> PathBuf {
+    let mut path = OsString::from(apk);
+    path.push(".idsig");
+    path.into()
+}
+
+/// Writes the v4 signature of an apk signed with the v2 or v3 scheme.
+pub fn sign(path: &Path, signer: &Signer) -> Result<()> {
+    let apk = std::fs::read(path)?;
+    let (tree, root_hash) = merkle_tree(&apk);
+    let hashing_info = HashingInfo {
+        salt: vec![],
+        root_hash: root_hash.to_vec(),
+    };
+    let algorithm = crate::sign::algorithm_id(signer)?;
+    let mut signing_info = SigningInfo {
+        apk_digest: crate::sign::apk_digest(path, algorithm)?,
+        certificate: rasn::der::encode(signer.cert()).map_err(|err| anyhow::anyhow!("{}", err))?,
+        additional_data: vec![],
+        public_key: signer.pubkey().to_vec(),
+        signature_algorithm: algorithm,
+        signature: vec![],
+    };
+    let signed_data = signed_data(apk.len() as u64, &hashing_info, &signing_info)?;
+    signing_info.signature = crate::sign::sign_data(signer, &signed_data)?;
+
+    let f = File::create(idsig_path(path))?;
+    let mut w = BufWriter::new(f);
+    w.write_u32::<LittleEndian>(VERSION)?;
+    let mut buf = vec![];
+    hashing_info.write(&mut buf)?;
+    write_bytes(&mut w, &buf)?;
+    buf.clear();
+    signing_info.write(&mut buf)?;
+    write_bytes(&mut w, &buf)?;
+    write_bytes(&mut w, &tree)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Verifies the v4 signature of an apk and returns the certificate of the signer.
+pub fn verify(path: &Path) -> Result<Certificate> {
+    let f = File::open(idsig_path(path))?;
+    let mut r = BufReader::new(f);
+    let version = r.read_u32::<LittleEndian>()?;
+    anyhow::ensure!(version == VERSION, "unsupported v4 version {}", version);
+    let hashing_info = HashingInfo::read(&mut &read_bytes(&mut r)?[..])?;
+    let signing_info = SigningInfo::read(&mut &read_bytes(&mut r)?[..])?;
+    let tree = read_bytes(&mut r)?;
+
+    let apk = std::fs::read(path)?;
+    let (expected_tree, root_hash) = merkle_tree(&apk);
+    anyhow::ensure!(
+        hashing_info.salt.is_empty() && hashing_info.root_hash == root_hash,
+        "v4 root hash doesn't match"
+    );
+    anyhow::ensure!(tree == expected_tree, "v4 merkle tree doesn't match");
+    anyhow::ensure!(
+        signing_info.apk_digest == crate::sign::apk_digest(path, signing_info.signature_algorithm)?,
+        "v4 apk digest doesn't match the v2/v3 digest"
+    );
+    let signed_data = signed_data(apk.len() as u64, &hashing_info, &signing_info)?;
+    crate::sign::verify_signature(
+        signing_info.signature_algorithm,
+        &signing_info.public_key,
+        &signed_data,
+        &signing_info.signature,
+    )?;
+    let cert = rasn::der::decode::<Certificate>(&signing_info.certificate)
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+    let public_key = rasn::der::encode(&cert.tbs_certificate.subject_public_key_info)
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+    anyhow::ensure!(
+        public_key == signing_info.public_key,
+        "v4 public key doesn't match the certificate"
+    );
+    Ok(cert)
+}
+
+/// Computes the fs-verity merkle tree using 4k blocks and no salt. Levels are
+/// ordered from the root to the leaves and each level is padded to a block.
+fn merkle_tree(data: &[u8]) -> (Vec<u8>, [u8; 32]) {
+    let mut levels = vec![];
+    let mut level = hash_blocks(data);
+    loop {
+        let done = level.len() <= BLOCK_SIZE;
+        let padded = level.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        level.resize(padded, 0);
+        levels.push(level);
+        if done {
+            break;
+        }
+        level = hash_blocks(levels.last().unwrap());
+    }
+    let root_hash = Sha256::digest(levels.last().unwrap()).into();
+    let tree = levels.into_iter().rev().flatten().collect();
+    (tree, root_hash)
+}
+
+fn hash_blocks(data: &[u8]) -> Vec<u8> {
+    let mut hashes = Vec::with_capacity((data.len() / BLOCK_SIZE + 1) * 32);
+    for block in data.chunks(BLOCK_SIZE) {
+        let mut hasher = Sha256::new();
+        hasher.update(block);
+        hasher.update(&[0; BLOCK_SIZE][block.len()..]);
+        hashes.extend_from_slice(&hasher.finalize());
+    }
+    hashes
+}
+
+fn signed_data(
+    file_size: u64,
+    hashing_info: &HashingInfo,
+    signing_info: &SigningInfo,
+) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    buf.write_u64::<LittleEndian>(file_size)?;
+    buf.write_u32::<LittleEndian>(HASH_ALGORITHM_SHA256)?;
+    buf.write_u8(LOG2_BLOCK_SIZE)?;
+    write_bytes(&mut buf, &hashing_info.salt)?;
+    write_bytes(&mut buf, &hashing_info.root_hash)?;
+    write_bytes(&mut buf, &signing_info.apk_digest)?;
+    write_bytes(&mut buf, &signing_info.certificate)?;
+    write_bytes(&mut buf, &signing_info.additional_data)?;
+    let mut signed_data = Vec::with_capacity(buf.len() + 4);
+    signed_data.write_u32::<LittleEndian>(buf.len() as u32 + 4)?;
+    signed_data.extend_from_slice(&buf);
+    Ok(signed_data)
+}
+
+fn read_bytes(r: &mut impl Read) -> Result<Vec<u8>> {
+    let len = r.read_u32::<LittleEndian>()?;
+    let mut bytes = vec![0; len as usize];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    w.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    w.write_all(bytes)?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct HashingInfo {
+    salt: Vec<u8>,
+    root_hash: Vec<u8>,
+}
+
+impl HashingInfo {
+    fn read(r: &mut impl Read) -> Result<Self> {
+        let algorithm = r.read_u32::<LittleEndian>()?;
+        anyhow::ensure!(
+            algorithm == HASH_ALGORITHM_SHA256,
+            "unsupported hash algorithm {}",
+            algorithm
+        );
+        let log2_block_size = r.read_u8()?;
+        anyhow::ensure!(
+            log2_block_size == LOG2_BLOCK_SIZE,
+            "unsupported block size 2^{}",
+            log2_block_size
+        );
+        let salt = read_bytes(r)?;
+        let root_hash = read_bytes(r)?;
+        Ok(Self { salt, root_hash })
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(HASH_ALGORITHM_SHA256)?;
+        w.write_u8(LOG2_BLOCK_SIZE)?;
+        write_bytes(w, &self.salt)?;
+        write_bytes(w, &self.root_hash)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct SigningInfo {
+    apk_digest: Vec<u8>,
+    certificate: Vec<u8>,
+    additional_data: Vec<u8>,
+    public_key: Vec<u8>,
+    signature_algorithm: u32,
+    signature: Vec<u8>,
+}
+
+impl SigningInfo {
+    fn read(r: &mut impl Read) -> Result<Self> {
+        Ok(Self {
+            apk_digest: read_bytes(r)?,
+            certificate: read_bytes(r)?,
+            additional_data: read_bytes(r)?,
+            public_key: read_bytes(r)?,
+            signature_algorithm: r.read_u32::<LittleEndian>()?,
+            signature: read_bytes(r)?,
+        })
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        write_bytes(w, &self.apk_digest)?;
+        write_bytes(w, &self.certificate)?;
+        write_bytes(w, &self.additional_data)?;
+        write_bytes(w, &self.public_key)?;
+        w.write_u32::<LittleEndian>(self.signature_algorithm)?;
+        write_bytes(w, &self.signature)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xcommon::{Zip, ZipFileOptions};
+
+    #[test]
+    fn test_sign_verify() -> Result<()> {
+        let path = std::env::temp_dir().join("test_idsig_sign_verify.apk");
+        let mut zip = Zip::new(&path, false)?;
+        zip.create_file(
+            Path::new("hello.txt"),
+            ZipFileOptions::Compressed,
+            &vec![42; 3 * BLOCK_SIZE],
+        )?;
+        zip.finish()?;
+        let signer = Signer::new(crate::sign::DEBUG_PEM)?;
+        crate::sign::sign(&path, Some(signer.clone()), false)?;
+        sign(&path, &signer)?;
+        assert_eq!(verify(&path)?, *signer.cert());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merkle_tree() {
+        let (tree, root_hash) = merkle_tree(b"hello world");
+        assert_eq!(tree.len(), BLOCK_SIZE);
+        assert_eq!(root_hash, <[u8; 32]>::from(Sha256::digest(&tree)));
+
+        let data = vec![1; BLOCK_SIZE * (BLOCK_SIZE / 32 + 1)];
+        let (tree, root_hash) = merkle_tree(&data);
+        assert_eq!(tree.len(), 3 * BLOCK_SIZE);
+        assert_eq!(
+            root_hash,
+            <[u8; 32]>::from(Sha256::digest(&tree[..BLOCK_SIZE]))
+        );
+    }
+}
@@ -0,0 +1,523 @@
+//! JAR signing, also known as the v1 signature scheme.
+//!
+//! Android versions before 7.0 (api 24) only verify v1 signatures. App
+//! bundles uploaded to Play are signed the same way.
+use anyhow::Result;
+use rasn::prelude::*;
+use rasn_cms::{
+    AlgorithmIdentifier, CertificateChoices, ContentInfo, EncapsulatedContentInfo,
+    IssuerAndSerialNumber, SignedData, SignerIdentifier, SignerInfo, CONTENT_DATA,
+    CONTENT_SIGNED_DATA,
+};
+use rasn_pkix::Certificate;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use xcommon::{SignatureAlgorithm, Signer, Zip, ZipFileOptions};
+use zip::ZipArchive;
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+const SIGNATURE_FILE: &str = "META-INF/CERT.SF";
+/// Extensions of the signature block, which is named after the algorithm
+/// of the signing key.
+const SIGNATURE_BLOCK_EXTENSIONS: [&str; 3] = ["RSA", "EC", "DSA"];
+const CREATED_BY: &str = "1.0 (xbuild)";
+/// Lines are limited to 72 bytes including the line break.
+const MAX_LINE_LENGTH: usize = 70;
+const RSA_ENCRYPTION: ConstOid = ConstOid(&[1, 2, 840, 113549, 1, 1, 1]);
+const SHA256_WITH_RSA_ENCRYPTION: ConstOid = ConstOid(&[1, 2, 840, 113549, 1, 1, 11]);
+const ECDSA_WITH_SHA256: ConstOid = ConstOid(&[1, 2, 840, 10045, 4, 3, 2]);
+const ECDSA_WITH_SHA512: ConstOid = ConstOid(&[1, 2, 840, 10045, 4, 3, 4]);
+const SHA512: ConstOid = ConstOid(&[2, 16, 840, 1, 101, 3, 4, 2, 3]);
+
+/// Adds `MANIFEST.MF`, `CERT.SF` and `CERT.RSA` or `CERT.EC` to the zip at
+/// `path`.
+///
+/// When `apk_signed` is true the signature file declares that the apk is
+/// also signed using the v2 and v3 schemes, so that verifiers reject the apk
+/// if those signatures are stripped.
+pub fn sign(path: &Path, signer: &Signer, apk_signed: bool) -> Result<()> {
+    let digests = entry_digests(path, false)?;
+
+    let mut manifest = vec![];
+    write_attr(&mut manifest, "Manifest-Version", "1.0");
+    write_attr(&mut manifest, "Created-By", CREATED_BY);
+    manifest.extend_from_slice(b"\r\n");
+    let main_attributes_digest = base64::encode(Sha256::digest(&manifest));
+    let mut sections = Vec::with_capacity(digests.len());
+    for (name, digest) in &digests {
+        let mut section = vec![];
+        write_attr(&mut section, "Name", name);
+        write_attr(&mut section, "SHA-256-Digest", &base64::encode(digest));
+        section.extend_from_slice(b"\r\n");
+        manifest.extend_from_slice(&section);
+        sections.push((name, section));
+    }
+
+    let mut sf = vec![];
+    write_attr(&mut sf, "Signature-Version", "1.0");
+    write_attr(&mut sf, "Created-By", CREATED_BY);
+    write_attr(
+        &mut sf,
+        "SHA-256-Digest-Manifest",
+        &base64::encode(Sha256::digest(&manifest)),
+    );
+    write_attr(
+        &mut sf,
+        "SHA-256-Digest-Manifest-Main-Attributes",
+        &main_attributes_digest,
+    );
+    if apk_signed {
+        write_attr(&mut sf, "X-Android-APK-Signed", "2, 3");
+    }
+    sf.extend_from_slice(b"\r\n");
+    for (name, section) in &sections {
+        write_attr(&mut sf, "Name", name);
+        write_attr(
+            &mut sf,
+            "SHA-256-Digest",
+            &base64::encode(Sha256::digest(section)),
+        );
+        sf.extend_from_slice(b"\r\n");
+    }
+
+    let block = signature_block(signer, &sf)?;
+    let block_name = match signer.algorithm() {
+        SignatureAlgorithm::EcdsaP256Sha256 | SignatureAlgorithm::EcdsaP384Sha384 => {
+            "META-INF/CERT.EC"
+        }
+        _ => "META-INF/CERT.RSA",
+    };
+
+    let mut zip = Zip::append(path, true)?;
+    zip.create_file(Path::new(MANIFEST), ZipFileOptions::Compressed, &manifest)?;
+    zip.create_file(Path::new(SIGNATURE_FILE), ZipFileOptions::Compressed, &sf)?;
+    zip.create_file(Path::new(block_name), ZipFileOptions::Compressed, &block)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Verifies the v1 signature and returns the certificates of the signers.
+///
+/// Returns no certificates if the zip doesn't contain a v1 signature.
+pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
+    let f = File::open(path)?;
+    let mut zip = ZipArchive::new(BufReader::new(f))?;
+    let manifest = if let Some(manifest) = read_entry(&mut zip, MANIFEST)? {
+        manifest
+    } else {
+        return Ok(vec![]);
+    };
+    let sections = parse_sections(&manifest)?;
+    let mut entries = BTreeMap::new();
+    for (_, attrs) in &sections[1..] {
+        let name = attrs
+            .get("Name")
+            .ok_or_else(|| anyhow::anyhow!("manifest section without name"))?;
+        let digest = attrs
+            .get("SHA-256-Digest")
+            .ok_or_else(|| anyhow::anyhow!("missing SHA-256 digest for {}", name))?;
+        entries.insert(name.clone(), digest.clone());
+    }
+    for (name, digest) in entry_digests(path, true)? {
+        let expected = entries
+            .remove(&name)
+            .ok_or_else(|| anyhow::anyhow!("{} is not signed", name))?;
+        anyhow::ensure!(
+            expected == base64::encode(digest),
+            "digest of {} doesn't match",
+            name
+        );
+    }
+    anyhow::ensure!(
+        entries.is_empty(),
+        "signed entries missing from zip: {:?}",
+        entries.keys()
+    );
+
+    let signature_files = zip
+        .file_names()
+        .filter(|name| is_signature_file(name) && name.ends_with(".SF"))
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    anyhow::ensure!(!signature_files.is_empty(), "no signature file found");
+    let mut certificates = vec![];
+    for name in signature_files {
+        let sf = read_entry(&mut zip, &name)?.unwrap();
+        let stem = name.strip_suffix(".SF").unwrap();
+        let block = SIGNATURE_BLOCK_EXTENSIONS
+            .iter()
+            .map(|ext| format!("{}.{}", stem, ext))
+            .find(|block_name| zip.file_names().any(|name| name == block_name))
+            .ok_or_else(|| anyhow::anyhow!("missing signature block for {}", name))?;
+        let block = read_entry(&mut zip, &block)?.unwrap();
+        certificates.push(verify_signature_block(&block, &sf)?);
+        verify_signature_file(&sf, &manifest, &sections)?;
+    }
+    Ok(certificates)
+}
+
+fn is_signature_file(name: &str) -> bool {
+    if let Some(name) = name.strip_prefix("META-INF/") {
+        name == "MANIFEST.MF"
+            || [".SF", ".RSA", ".DSA", ".EC"]
+                .iter()
+                .any(|ext| name.ends_with(ext) && !name.contains('/'))
+    } else {
+        false
+    }
+}
+
+fn read_entry<R: Read + std::io::Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Option<Vec<u8>>> {
+    let mut f = match zip.by_name(name) {
+        Ok(f) => f,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut buf = vec![];
+    f.read_to_end(&mut buf)?;
+    Ok(Some(buf))
+}
+
+/// Computes the SHA-256 digest of every file in the zip, skipping
+/// directories. Signature files are skipped if `skip_signature_files` is
+/// true, otherwise they are rejected so a zip isn't signed twice.
+fn entry_digests(path: &Path, skip_signature_files: bool) -> Result<Vec<(String, [u8; 32])>> {
+    let f = File::open(path)?;
+    let mut zip = ZipArchive::new(BufReader::new(f))?;
+    let mut digests = Vec::with_capacity(zip.len());
+    let mut buf = vec![];
+    for i in 0..zip.len() {
+        let mut f = zip.by_index(i)?;
+        if f.is_dir() {
+            continue;
+        }
+        let name = f.name().to_string();
+        if is_signature_file(&name) {
+            anyhow::ensure!(skip_signature_files, "zip already contains a v1 signature");
+            continue;
+        }
+        buf.clear();
+        f.read_to_end(&mut buf)?;
+        digests.push((name, Sha256::digest(&buf).into()));
+    }
+    Ok(digests)
+}
+
+fn write_attr(buf: &mut Vec<u8>, name: &str, value: &str) {
+    let line = format!("{}: {}", name, value);
+    let mut line = line.as_bytes();
+    let mut max = MAX_LINE_LENGTH;
+    loop {
+        let len = std::cmp::min(max, line.len());
+        buf.extend_from_slice(&line[..len]);
+        buf.extend_from_slice(b"\r\n");
+        line = &line[len..];
+        if line.is_empty() {
+            break;
+        }
+        buf.push(b' ');
+        max = MAX_LINE_LENGTH - 1;
+    }
+}
+
+/// Raw bytes and attributes of a manifest or signature file section.
+type Section<'a> = (&'a [u8], BTreeMap<String, String>);
+
+/// Splits a manifest or signature file into sections. Each section is
+/// returned together with its raw bytes including the trailing empty line.
+fn parse_sections(bytes: &[u8]) -> Result<Vec<Section<'_>>> {
+    let mut sections = vec![];
+    let mut attrs = BTreeMap::new();
+    let mut current: Option<(String, String)> = None;
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let end = bytes[pos..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map(|i| pos + i + 1)
+            .unwrap_or(bytes.len());
+        let line = &bytes[pos..end];
+        let content = line
+            .strip_suffix(b"\n")
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .unwrap_or(line);
+        pos = end;
+        if let Some(continuation) = content.strip_prefix(b" ") {
+            let (_, value) = current
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("invalid continuation line"))?;
+            value.push_str(std::str::from_utf8(continuation)?);
+            continue;
+        }
+        if let Some((name, value)) = current.take() {
+            attrs.insert(name, value);
+        }
+        if content.is_empty() {
+            if !attrs.is_empty() {
+                sections.push((&bytes[start..pos], std::mem::take(&mut attrs)));
+            }
+            start = pos;
+            continue;
+        }
+        let content = std::str::from_utf8(content)?;
+        let (name, value) = content
+            .split_once(": ")
+            .ok_or_else(|| anyhow::anyhow!("invalid manifest line {}", content))?;
+        current = Some((name.to_string(), value.to_string()));
+    }
+    if let Some((name, value)) = current.take() {
+        attrs.insert(name, value);
+    }
+    if !attrs.is_empty() {
+        sections.push((&bytes[start..], attrs));
+    }
+    anyhow::ensure!(!sections.is_empty(), "empty manifest");
+    Ok(sections)
+}
+
+fn verify_signature_file(sf: &[u8], manifest: &[u8], manifest_sections: &[Section]) -> Result<()> {
+    let sections = parse_sections(sf)?;
+    let main = &sections[0].1;
+    if let Some(digest) = main.get("SHA-256-Digest-Manifest") {
+        if *digest == base64::encode(Sha256::digest(manifest)) {
+            return Ok(());
+        }
+    }
+    // the manifest may have changed, so each section needs to be checked.
+    for (_, attrs) in &sections[1..] {
+        let name = attrs
+            .get("Name")
+            .ok_or_else(|| anyhow::anyhow!("signature file section without name"))?;
+        let digest = attrs
+            .get("SHA-256-Digest")
+            .ok_or_else(|| anyhow::anyhow!("missing SHA-256 digest for {}", name))?;
+        let (section, _) = manifest_sections
+            .iter()
+            .find(|(_, attrs)| attrs.get("Name") == Some(name))
+            .ok_or_else(|| anyhow::anyhow!("{} not found in manifest", name))?;
+        anyhow::ensure!(
+            *digest == base64::encode(Sha256::digest(section)),
+            "manifest section of {} doesn't match the signature file",
+            name
+        );
+    }
+    Ok(())
+}
+
+fn sha256_algorithm() -> AlgorithmIdentifier {
+    AlgorithmIdentifier {
+        algorithm:
+            Oid::JOINT_ISO_ITU_T_COUNTRY_US_ORGANIZATION_GOV_CSOR_NIST_ALGORITHMS_HASH_SHA256.into(),
+        parameters: Some(Any::new(vec![5, 0])),
+    }
+}
+
+fn sha512_algorithm() -> AlgorithmIdentifier {
+    AlgorithmIdentifier {
+        algorithm: SHA512.into(),
+        parameters: Some(Any::new(vec![5, 0])),
+    }
+}
+
+/// Returns the signature algorithm and its identifier. P-384 keys sign
+/// using SHA-512 like in the apk signature schemes.
+fn signature_algorithm(signer: &Signer) -> Result<(SignatureAlgorithm, AlgorithmIdentifier)> {
+    Ok(match signer.algorithm() {
+        SignatureAlgorithm::RsaPkcs1Sha256 => (
+            SignatureAlgorithm::RsaPkcs1Sha256,
+            AlgorithmIdentifier {
+                algorithm: RSA_ENCRYPTION.into(),
+                parameters: Some(Any::new(vec![5, 0])),
+            },
+        ),
+        SignatureAlgorithm::EcdsaP256Sha256 => (
+            SignatureAlgorithm::EcdsaP256Sha256,
+            AlgorithmIdentifier {
+                algorithm: ECDSA_WITH_SHA256.into(),
+                parameters: None,
+            },
+        ),
+        SignatureAlgorithm::EcdsaP384Sha384 => (
+            SignatureAlgorithm::EcdsaP384Sha512,
+            AlgorithmIdentifier {
+                algorithm: ECDSA_WITH_SHA512.into(),
+                parameters: None,
+            },
+        ),
+        algorithm => anyhow::bail!("{:?} keys can't be used for jar signing", algorithm),
+    })
+}
+
+/// Digest algorithm matching the signature algorithm.
+fn digest_algorithm(algorithm: SignatureAlgorithm) -> AlgorithmIdentifier {
+    match algorithm {
+        SignatureAlgorithm::EcdsaP384Sha512 => sha512_algorithm(),
+        _ => sha256_algorithm(),
+    }
+}
+
+#[allow(clippy::mutable_key_type)]
+fn signature_block(signer: &Signer, sf: &[u8]) -> Result<Vec<u8>> {
+    let cert = signer.cert();
+    let (algorithm, signature_algorithm) = signature_algorithm(signer)?;
+    let signer_info = SignerInfo {
+        version: 1.into(),
+        sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: cert.tbs_certificate.issuer.clone(),
+            serial_number: cert.tbs_certificate.serial_number.clone(),
+        }),
+        digest_algorithm: digest_algorithm(algorithm),
+        signed_attrs: None,
+        signature_algorithm,
+        signature: OctetString::from(signer.sign_with(algorithm, sf)?),
+        unsigned_attrs: None,
+    };
+    let signed_data = SignedData {
+        version: 1.into(),
+        digest_algorithms: {
+            let mut digest_algorithms = SetOf::default();
+            digest_algorithms.insert(digest_algorithm(algorithm));
+            digest_algorithms
+        },
+        encap_content_info: EncapsulatedContentInfo {
+            content_type: CONTENT_DATA.into(),
+            content: None,
+        },
+        certificates: Some({
+            let mut certificates = SetOf::default();
+            certificates.insert(CertificateChoices::Certificate(cert.clone().into()));
+            certificates
+        }),
+        crls: None,
+        signer_infos: {
+            let mut signer_infos = SetOf::default();
+            signer_infos.insert(signer_info);
+            signer_infos
+        },
+    };
+    let content_info = ContentInfo {
+        content_type: CONTENT_SIGNED_DATA.into(),
+        content: Any::new(
+            rasn::der::encode(&signed_data).map_err(|err| anyhow::anyhow!("{}", err))?,
+        ),
+    };
+    rasn::der::encode(&content_info).map_err(|err| anyhow::anyhow!("{}", err))
+}
+
+fn verify_signature_block(block: &[u8], sf: &[u8]) -> Result<Certificate> {
+    let info = rasn::der::decode::<ContentInfo>(block).map_err(|err| anyhow::anyhow!("{}", err))?;
+    anyhow::ensure!(
+        CONTENT_SIGNED_DATA == info.content_type,
+        "expected signed data"
+    );
+    let data = rasn::der::decode::<SignedData>(info.content.as_bytes())
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+    let signer_info = data
+        .signer_infos
+        .iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no signer info found"))?;
+    let sid = if let SignerIdentifier::IssuerAndSerialNumber(sid) = &signer_info.sid {
+        sid
+    } else {
+        anyhow::bail!("unsupported signer identifier");
+    };
+    let cert = data
+        .certificates
+        .iter()
+        .flatten()
+        .find_map(|cert| match cert {
+            CertificateChoices::Certificate(cert)
+                if cert.tbs_certificate.issuer == sid.issuer
+                    && cert.tbs_certificate.serial_number == sid.serial_number =>
+            {
+                Some(Certificate::clone(cert))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("signer certificate not found"))?;
+    let sf_digest = if signer_info.digest_algorithm.algorithm == sha256_algorithm().algorithm {
+        Sha256::digest(sf).to_vec()
+    } else if signer_info.digest_algorithm.algorithm == sha512_algorithm().algorithm {
+        Sha512::digest(sf).to_vec()
+    } else {
+        anyhow::bail!(
+            "unsupported digest algorithm {:?}",
+            signer_info.digest_algorithm.algorithm
+        );
+    };
+    let signed = if let Some(attrs) = &signer_info.signed_attrs {
+        let digest = attrs
+            .iter()
+            .find(|attr| attr.r#type == *Oid::ISO_MEMBER_BODY_US_RSADSI_PKCS9_MESSAGE_DIGEST)
+            .ok_or_else(|| anyhow::anyhow!("missing message digest attribute"))?;
+        let digests = rasn::der::decode::<SetOf<OctetString>>(digest.value.as_bytes())
+            .map_err(|err| anyhow::anyhow!("{}", err))?;
+        anyhow::ensure!(
+            digests
+                .iter()
+                .any(|digest| digest.as_ref() == sf_digest.as_slice()),
+            "signature file digest doesn't match"
+        );
+        rasn::der::encode(attrs).map_err(|err| anyhow::anyhow!("{}", err))?
+    } else {
+        sf.to_vec()
+    };
+    let public_key = rasn::der::encode(&cert.tbs_certificate.subject_public_key_info)
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+    let oid = &signer_info.signature_algorithm.algorithm;
+    let algorithm = if RSA_ENCRYPTION == *oid || SHA256_WITH_RSA_ENCRYPTION == *oid {
+        SignatureAlgorithm::RsaPkcs1Sha256
+    } else if ECDSA_WITH_SHA256 == *oid {
+        SignatureAlgorithm::EcdsaP256Sha256
+    } else if ECDSA_WITH_SHA512 == *oid {
+        SignatureAlgorithm::EcdsaP384Sha512
+    } else {
+        anyhow::bail!("unsupported signature algorithm {:?}", oid);
+    };
+    algorithm.verify(&public_key, &signed, signer_info.signature.as_ref())?;
+    Ok(cert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_attr() -> Result<()> {
+        let name = "a".repeat(100);
+        let mut buf = vec![];
+        write_attr(&mut buf, "Name", &name);
+        for line in buf.split(|b| *b == b'\n') {
+            assert!(line.len() <= 71);
+        }
+        let sections = parse_sections(&buf)?;
+        assert_eq!(sections[0].1.get("Name"), Some(&name));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_verify() -> Result<()> {
+        let path = std::env::temp_dir().join("test_jar_sign_verify.aab");
+        let mut zip = Zip::new(&path, true)?;
+        zip.create_file(
+            Path::new("hello.txt"),
+            ZipFileOptions::Compressed,
+            b"hello world",
+        )?;
+        zip.finish()?;
+        assert!(verify(&path)?.is_empty());
+        let signer = Signer::new(crate::sign::DEBUG_PEM)?;
+        sign(&path, &signer, false)?;
+        assert_eq!(verify(&path)?, vec![signer.cert().clone()]);
+        assert!(sign(&path, &signer, false).is_err());
+        Ok(())
+    }
+}
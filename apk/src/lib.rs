@@ -1,71 +1,202 @@
-use crate::compiler::Table;
 use crate::res::Chunk;
 use anyhow::Result;
-use std::io::Cursor;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
 use std::path::{Path, PathBuf};
-use xcommon::{Scaler, ScalerOpts, Zip, ZipFile, ZipFileOptions};
+use xcommon::{Zip, ZipFile, ZipFileOptions};
+use zip::{CompressionMethod, ZipArchive};
 
+pub mod aab;
+mod aar;
 mod compiler;
+mod icon;
+mod idsig;
+mod jar;
 pub mod manifest;
+mod proguard;
 pub mod res;
+mod resources;
 mod sign;
+mod split;
 mod utils;
 
+pub use crate::aab::Aab;
+pub use crate::aar::Aar;
+pub use crate::compiler::{FileContents, Symbols, Table};
+pub use crate::icon::{AdaptiveIcon, IconBackground};
 pub use crate::manifest::AndroidManifest;
+pub use crate::resources::AppResources;
+pub use crate::sign::{Lineage, Verified};
 pub use crate::utils::{Target, VersionCode};
-pub use xcommon::{Certificate, Signer};
+pub use xcommon::{Certificate, SignatureAlgorithm, Signer};
 pub use zip;
 
 pub struct Apk {
     manifest: AndroidManifest,
     path: PathBuf,
     zip: Zip,
+    idsig: bool,
+    xml_resources: Vec<(String, String)>,
+    adaptive_icon: Option<AdaptiveIcon>,
+    lib_alignment: Option<u16>,
+    compress: bool,
+    splits: bool,
+    split_zips: BTreeMap<String, Zip>,
+    table: Table,
+    libraries: Vec<Aar>,
+    symbols: Symbols,
+    proguard_rules: String,
+    stable_ids: Symbols,
 }
 
 impl Apk {
     pub fn new(path: PathBuf, manifest: AndroidManifest, compress: bool) -> Result<Self> {
-        let zip = Zip::new(&path, compress)?;
+        let mut zip = Zip::new(&path, compress)?;
+        zip.set_stored_alignment(4);
         Ok(Self {
             manifest,
             path,
             zip,
+            idsig: false,
+            xml_resources: vec![],
+            adaptive_icon: None,
+            lib_alignment: None,
+            compress,
+            splits: false,
+            split_zips: BTreeMap::new(),
+            table: Table::default(),
+            libraries: vec![],
+            symbols: Symbols::default(),
+            proguard_rules: String::new(),
+            stable_ids: Symbols::default(),
         })
     }
 
-    pub fn add_res(&mut self, icon: Option<&Path>, android: &Path) -> Result<()> {
+    /// Writes a v4 signature to `<name>.apk.idsig` when finishing the apk,
+    /// which enables `adb install --incremental`.
+    pub fn set_idsig(&mut self, idsig: bool) {
+        self.idsig = idsig;
+    }
+
+    /// Uses an adaptive launcher icon on android 8 and later, the icon
+    /// passed to [`Apk::add_res`] is used on older versions.
+    pub fn set_adaptive_icon(&mut self, icon: AdaptiveIcon) {
+        self.adaptive_icon = Some(icon);
+    }
+
+    /// Stores native libraries uncompressed and aligned to `page_size`, so
+    /// they are mapped directly from the apk instead of being extracted on
+    /// install. Devices with 16 KB pages require a `page_size` of 16384.
+    ///
+    /// Sets `android:extractNativeLibs="false"`, so it has to be called
+    /// before [`Apk::add_res`].
+    pub fn set_uncompressed_libs(&mut self, page_size: u16) {
+        self.lib_alignment = Some(page_size);
+        self.manifest.application.extract_native_libs = Some(false);
+    }
+
+    /// Writes native libraries and density specific resources to configuration
+    /// splits like `helloworld.split_config.arm64_v8a.apk` next to
+    /// `helloworld.apk`, which are signed with the same signers and installed
+    /// with `adb install-multiple`.
+    /// Has to be called before [`Apk::add_res`].
+    pub fn set_splits(&mut self, splits: bool) {
+        self.splits = splits;
+    }
+
+    /// Keeps the resource ids of a previous build, see
+    /// [`Symbols::parse_stable_ids`]. Has to be called before [`Apk::add_res`].
+    pub fn set_stable_ids(&mut self, ids: Symbols) {
+        self.stable_ids = ids;
+    }
+
+    /// Adds an xml resource like `res/xml/file_paths.xml`. It is compiled
+    /// to binary xml by [`Apk::add_res`], which has to be called afterwards.
+    pub fn add_xml_resource(&mut self, path: &str, xml: &str) {
+        self.xml_resources.push((path.to_string(), xml.to_string()));
+    }
+
+    /// Merges the manifest and resources of an android library, see [`Aar`].
+    /// Has to be called before [`Apk::add_res`], resources and manifest
+    /// elements declared by the app take precedence.
+    pub fn add_library(&mut self, aar: Aar) {
+        self.libraries.push(aar);
+    }
+
+    /// Adds the resource table, the icon, the resources of an android `res`
+    /// directory and the compiled manifest, which may reference the
+    /// resources. The resource table is always written, the label of the
+    /// manifest is stored in it as `@string/app_name`.
+    pub fn add_res(
+        &mut self,
+        icon: Option<&Path>,
+        res: Option<&Path>,
+        android: &Path,
+    ) -> Result<()> {
         let mut buf = vec![];
         let mut table = Table::default();
         table.import_apk(android)?;
-        if let Some(path) = icon {
-            let mut scaler = Scaler::open(path)?;
-            scaler.optimize();
-            let package = if let Some(package) = self.manifest.package.as_ref() {
-                package
-            } else {
-                anyhow::bail!("missing manifest.package");
-            };
-            let mipmap = crate::compiler::compile_mipmap(package, "icon")?;
-
-            let mut cursor = Cursor::new(&mut buf);
-            mipmap.chunk().write(&mut cursor)?;
-            self.zip.create_file(
-                Path::new("resources.arsc"),
-                ZipFileOptions::Aligned(4),
-                &buf,
-            )?;
-
-            for (name, size) in mipmap.variants() {
+        let resources = collect_resources(
+            &mut self.manifest,
+            icon,
+            self.adaptive_icon.as_ref(),
+            res,
+            std::mem::take(&mut self.xml_resources),
+            &self.libraries,
+            &self.stable_ids,
+        )?;
+        let mut chunk = resources.compile(&mut table)?;
+        // files of density specific resources are moved to the density split
+        let mut split_files = BTreeMap::new();
+        if self.splits {
+            for (density, (split, files)) in crate::split::split_densities(&mut chunk)? {
                 buf.clear();
-                let mut cursor = Cursor::new(&mut buf);
-                scaler.write(&mut cursor, ScalerOpts::new(size))?;
-                self.zip
-                    .create_file(name.as_ref(), ZipFileOptions::Aligned(4), &buf)?;
+                split.write(&mut Cursor::new(&mut buf))?;
+                self.zip(Some(density))?.create_file(
+                    Path::new("resources.arsc"),
+                    ZipFileOptions::Aligned(4),
+                    &buf,
+                )?;
+                split_files.extend(files.into_iter().map(|file| (file, density)));
             }
-
-            table.import_chunk(mipmap.chunk());
-            self.manifest.application.icon = Some("@mipmap/icon".into());
         }
-        let manifest = crate::compiler::compile_manifest(&self.manifest, &table)?;
+        buf.clear();
+        let mut cursor = Cursor::new(&mut buf);
+        chunk.write(&mut cursor)?;
+        self.zip.create_file(
+            Path::new("resources.arsc"),
+            ZipFileOptions::Aligned(4),
+            &buf,
+        )?;
+        for (path, contents) in resources.files() {
+            let zip = self.zip(split_files.get(path).copied())?;
+            match contents {
+                FileContents::Xml(xml) => {
+                    let xml = crate::compiler::compile_xml(xml, &table)
+                        .map_err(|err| anyhow::anyhow!("{}: {}", path, err))?;
+                    buf.clear();
+                    let mut cursor = Cursor::new(&mut buf);
+                    xml.write(&mut cursor)?;
+                    zip.create_file(Path::new(path), ZipFileOptions::Compressed, &buf)?;
+                }
+                FileContents::Raw(source) => {
+                    zip.add_file(source, Path::new(path), ZipFileOptions::Aligned(4))?;
+                }
+            }
+        }
+        for (path, png) in resources.pngs() {
+            self.zip(split_files.get(path).copied())?.create_file(
+                Path::new(path),
+                ZipFileOptions::Aligned(4),
+                png,
+            )?;
+        }
+        self.symbols = resources.symbols(&table)?;
+        let manifest = crate::aar::merge_manifests(&self.manifest, &self.libraries)?;
+        let package = self.manifest.package.as_deref().unwrap_or_default();
+        self.proguard_rules = crate::proguard::keep_rules(package, &manifest, resources.files())?;
+        let manifest = crate::compiler::compile_xml(&manifest, &table)?;
         buf.clear();
         let mut cursor = Cursor::new(&mut buf);
         manifest.write(&mut cursor)?;
@@ -74,12 +205,44 @@ impl Apk {
             ZipFileOptions::Compressed,
             &buf,
         )?;
+        self.table = table;
         Ok(())
     }
 
+    /// Returns the resource ids for generating `R` classes, which are known
+    /// once [`Apk::add_res`] was called.
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// Returns keep rules for the classes referenced by the manifest and the
+    /// xml resources, which are known once [`Apk::add_res`] was called.
+    pub fn proguard_rules(&self) -> &str {
+        &self.proguard_rules
+    }
+
+    /// Returns the zip of a configuration split, or of the base apk when
+    /// splits are disabled.
+    fn zip(&mut self, config: Option<&str>) -> Result<&mut Zip> {
+        let config = match config {
+            Some(config) if self.splits => config,
+            _ => return Ok(&mut self.zip),
+        };
+        if !self.split_zips.contains_key(config) {
+            let path = crate::split::split_path(&self.path, config);
+            let mut zip = Zip::new(&path, self.compress)?;
+            zip.set_stored_alignment(4);
+            self.split_zips.insert(config.to_string(), zip);
+        }
+        Ok(self.split_zips.get_mut(config).unwrap())
+    }
+
+    /// Adds a dex file, multidex apps add `classes.dex`, `classes2.dex` and
+    /// so on.
     pub fn add_dex(&mut self, dex: &Path) -> Result<()> {
+        let name = dex_name(dex)?;
         self.zip
-            .add_file(dex, Path::new("classes.dex"), ZipFileOptions::Compressed)?;
+            .add_file(dex, Path::new(name), ZipFileOptions::Compressed)?;
         Ok(())
     }
 
@@ -93,10 +256,15 @@ impl Apk {
             .ok_or_else(|| anyhow::anyhow!("invalid path"))?
             .to_str()
             .ok_or_else(|| anyhow::anyhow!("invalid path"))?;
-        self.zip.add_file(
+        let opts = self
+            .lib_alignment
+            .map(ZipFileOptions::Aligned)
+            .unwrap_or(ZipFileOptions::Compressed);
+        let config = crate::split::abi_config(target.android_abi());
+        self.zip(Some(&config))?.add_file(
             path,
             &Path::new("lib").join(target.android_abi()).join(name),
-            ZipFileOptions::Compressed,
+            opts,
         )
     }
 
@@ -108,92 +276,229 @@ impl Apk {
         self.zip.add_directory(source, dest)
     }
 
+    /// Finishes and signs the apk. A v1 signature is only added when
+    /// `minSdkVersion` is lower than 24.
     pub fn finish(self, signer: Option<Signer>) -> Result<()> {
+        let signer = signer
+            .map(Ok)
+            .unwrap_or_else(|| Signer::new(crate::sign::DEBUG_PEM))?;
+        self.finish_with_lineage(&[signer])
+    }
+
+    /// Finishes the apk and signs it with the last signer, see [`Apk::sign_with_lineage`].
+    pub fn finish_with_lineage(self, signers: &[Signer]) -> Result<()> {
+        anyhow::ensure!(!signers.is_empty(), "no signer");
+        let v1 = self.requires_v1();
+        let mut apks = vec![self.path.clone()];
+        for (config, mut zip) in self.split_zips {
+            let manifest = crate::split::split_manifest(&self.manifest, &config);
+            let manifest = crate::compiler::compile_manifest(&manifest, &self.table)?;
+            let mut buf = vec![];
+            manifest.write(&mut Cursor::new(&mut buf))?;
+            zip.create_file(
+                Path::new("AndroidManifest.xml"),
+                ZipFileOptions::Compressed,
+                &buf,
+            )?;
+            zip.finish()?;
+            apks.push(crate::split::split_path(&self.path, &config));
+        }
         self.zip.finish()?;
-        crate::sign::sign(&self.path, signer)?;
+        for (_, split) in crate::split::find_splits(&self.path, None)? {
+            if !apks.contains(&split) {
+                // a stale split would be installed with the new apk.
+                std::fs::remove_file(&split)?;
+                let idsig = crate::idsig::idsig_path(&split);
+                if idsig.exists() {
+                    std::fs::remove_file(idsig)?;
+                }
+            }
+        }
+        for apk in &apks {
+            crate::sign::sign_with_lineage(apk, signers, v1)?;
+            let idsig = crate::idsig::idsig_path(apk);
+            if self.idsig {
+                crate::idsig::sign(apk, signers.last().unwrap())?;
+            } else if idsig.exists() {
+                // a stale signature would break incremental installs.
+                std::fs::remove_file(idsig)?;
+            }
+        }
         Ok(())
     }
 
+    fn requires_v1(&self) -> bool {
+        self.manifest.sdk.min_sdk_version.unwrap_or(1) < 24
+    }
+
+    /// Signs an unsigned apk using the v1, v2 and v3 signature schemes.
     pub fn sign(path: &Path, signer: Option<Signer>) -> Result<()> {
-        crate::sign::sign(path, signer)
+        crate::sign::sign(path, signer, true)
     }
 
-    pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
+    /// Signs the apk with the last signer and a proof-of-rotation from the
+    /// first signer. Signers are ordered from oldest to newest.
+    pub fn sign_with_lineage(path: &Path, signers: &[Signer]) -> Result<()> {
+        crate::sign::sign_with_lineage(path, signers, true)
+    }
+
+    pub fn verify(path: &Path) -> Result<Verified> {
         crate::sign::verify(path)
     }
 
-    pub fn entry_point(path: &Path) -> Result<EntryPoint> {
-        let manifest = xcommon::extract_zip_file(path, "AndroidManifest.xml")?;
-        let chunks = if let Chunk::Xml(chunks) = Chunk::parse(&mut Cursor::new(manifest))? {
-            chunks
-        } else {
-            anyhow::bail!("invalid manifest 0");
-        };
-        let strings = if let Chunk::StringPool(strings, _) = &chunks[0] {
-            strings
-        } else {
-            anyhow::bail!("invalid manifest 1");
-        };
-        let mut manifest = None;
-        let mut package = None;
-        let mut activity = None;
-        let mut name = None;
-        for (i, s) in strings.iter().enumerate() {
-            match s.as_str() {
-                "manifest" => {
-                    manifest = Some(i as i32);
-                }
-                "package" => {
-                    package = Some(i as i32);
-                }
-                "activity" => {
-                    activity = Some(i as i32);
-                }
-                "name" => {
-                    name = Some(i as i32);
-                }
-                _ => {}
+    /// Checks that uncompressed files are 4 byte aligned and uncompressed
+    /// native libraries are aligned to `page_size`, like `zipalign -c -p`.
+    pub fn verify_alignment(path: &Path, page_size: u16) -> Result<()> {
+        let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        let mut misaligned = vec![];
+        for i in 0..zip.len() {
+            let f = zip.by_index_raw(i)?;
+            if f.is_dir() || f.compression() != CompressionMethod::Stored {
+                continue;
             }
-        }
-        let (manifest, package, activity, name) =
-            if let (Some(manifest), Some(package), Some(activity), Some(name)) =
-                (manifest, package, activity, name)
-            {
-                (manifest, package, activity, name)
+            let alignment = if f.name().ends_with(".so") {
+                page_size as u64
             } else {
-                anyhow::bail!("invalid manifest 2");
+                4
             };
-        let mut package_value = None;
-        let mut name_value = None;
-        for chunk in &chunks[2..] {
-            if let Chunk::XmlStartElement(_, el, attrs) = chunk {
-                match el.name {
-                    x if x == manifest => {
-                        package_value = attrs
-                            .iter()
-                            .find(|attr| attr.name == package)
-                            .map(|attr| attr.raw_value);
-                    }
-                    x if x == activity => {
-                        name_value = attrs
-                            .iter()
-                            .find(|attr| attr.name == name)
-                            .map(|attr| attr.raw_value);
-                    }
-                    _ => {}
-                }
+            if f.data_start() % alignment != 0 {
+                misaligned.push(format!(
+                    "{} at offset {} is not aligned to {}",
+                    f.name(),
+                    f.data_start(),
+                    alignment
+                ));
             }
         }
-        let entry = if let (Some(package_value), Some(name_value)) = (package_value, name_value) {
-            EntryPoint {
-                package: strings[package_value as usize].clone(),
-                activity: strings[name_value as usize].clone(),
-            }
-        } else {
-            anyhow::bail!("invalid manifest 3");
-        };
-        Ok(entry)
+        anyhow::ensure!(misaligned.is_empty(), "{}", misaligned.join("\n"));
+        Ok(())
+    }
+
+    /// Returns the configuration splits written next to the apk by
+    /// [`Apk::set_splits`]. When an `abi` like `arm64-v8a` is given the
+    /// native libraries of other abis are skipped.
+    pub fn splits(path: &Path, abi: Option<&str>) -> Result<Vec<PathBuf>> {
+        Ok(crate::split::find_splits(path, abi)?
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect())
+    }
+
+    /// Returns the path of the v4 signature written by [`Apk::set_idsig`].
+    pub fn idsig_path(path: &Path) -> PathBuf {
+        crate::idsig::idsig_path(path)
+    }
+
+    /// Verifies the v4 signature and returns the certificate of the signer.
+    pub fn verify_idsig(path: &Path) -> Result<Certificate> {
+        crate::idsig::verify(path)
+    }
+
+    /// Returns the decompiled `AndroidManifest.xml`, references to resources
+    /// of the apk are resolved using its `resources.arsc`.
+    pub fn manifest(path: &Path) -> Result<String> {
+        let manifest = xcommon::extract_zip_file(path, "AndroidManifest.xml")?;
+        let chunk = Chunk::parse(&mut Cursor::new(manifest))?;
+        let mut table = Table::default();
+        if let Ok(resources) = xcommon::extract_zip_file(path, "resources.arsc") {
+            table.import_chunk(&Chunk::parse(&mut Cursor::new(resources))?);
+        }
+        crate::res::decompile_xml(&chunk, &table)
+    }
+
+    /// Returns the package and the activity started by the launcher.
+    pub fn entry_point(path: &Path) -> Result<EntryPoint> {
+        let manifest = xcommon::extract_zip_file(path, "AndroidManifest.xml")?;
+        let chunk = Chunk::parse(&mut Cursor::new(manifest))?;
+        let xml = crate::res::decompile_xml(&chunk, &Table::default())?;
+        EntryPoint::from_manifest(&xml)
+    }
+}
+
+/// Collects the resources of an app in the order [`Apk::add_res`] and
+/// [`Aab::add_res`] add them. The label and icon of the manifest are
+/// replaced with references to the resources.
+fn collect_resources(
+    manifest: &mut AndroidManifest,
+    icon: Option<&Path>,
+    adaptive_icon: Option<&AdaptiveIcon>,
+    res: Option<&Path>,
+    xml_resources: Vec<(String, String)>,
+    libraries: &[Aar],
+    stable_ids: &Symbols,
+) -> Result<AppResources> {
+    let package = manifest
+        .package
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("missing manifest.package"))?;
+    let mut resources = AppResources::new(package);
+    resources.set_stable_ids(stable_ids.clone());
+    if let Some(res) = res {
+        resources.add_res_dir(res)?;
+    }
+    for (path, xml) in xml_resources {
+        resources.add_xml(&path, &xml)?;
+    }
+    if let Some(label) = manifest.application.label.as_deref() {
+        manifest.application.label = Some(resources.add_label(label)?);
+    }
+    if let Some(icon) = resources.add_icon(icon, adaptive_icon)? {
+        manifest.application.icon = Some(icon);
     }
+    for library in libraries {
+        resources.add_library(library)?;
+    }
+    Ok(resources)
+}
+
+/// Returns the ids [`Apk::add_res`] assigns to the resources of an app, so
+/// native code can be compiled against them before the app is packaged.
+/// `xml_resources` are the resources passed to [`Apk::add_xml_resource`] and
+/// `stable_ids` the ids passed to [`Apk::set_stable_ids`]. Writing the
+/// returned ids with [`Symbols::to_stable_ids`] and passing them to the next
+/// build keeps the ids of existing resources when resources are added.
+#[allow(clippy::too_many_arguments)]
+pub fn resource_symbols(
+    manifest: &AndroidManifest,
+    icon: Option<&Path>,
+    adaptive_icon: Option<&AdaptiveIcon>,
+    res: Option<&Path>,
+    xml_resources: &[(String, String)],
+    libraries: &[Aar],
+    stable_ids: &Symbols,
+    android: &Path,
+) -> Result<Symbols> {
+    let mut table = Table::default();
+    table.import_apk(android)?;
+    let resources = collect_resources(
+        &mut manifest.clone(),
+        icon,
+        adaptive_icon,
+        res,
+        xml_resources.to_vec(),
+        libraries,
+        stable_ids,
+    )?;
+    resources.symbols(&table)
+}
+
+/// Returns the file name of a dex, which has to be `classes.dex` or
+/// `classes<n>.dex` for the runtime to load it.
+fn dex_name(dex: &Path) -> Result<&str> {
+    let name = dex
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow::anyhow!("invalid path"))?;
+    let n = name
+        .strip_prefix("classes")
+        .and_then(|name| name.strip_suffix(".dex"))
+        .ok_or_else(|| anyhow::anyhow!("invalid dex name {}", name))?;
+    anyhow::ensure!(
+        n.is_empty() || (n.parse::<u32>().map(|n| n > 1).unwrap_or(false) && !n.starts_with('0')),
+        "invalid dex name {}",
+        name
+    );
+    Ok(name)
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -202,6 +507,42 @@ pub struct EntryPoint {
     pub activity: String,
 }
 
+impl EntryPoint {
+    /// Finds the activity or activity alias with a `MAIN` action and a
+    /// `LAUNCHER` category in a decompiled manifest.
+    fn from_manifest(xml: &str) -> Result<Self> {
+        const NS_ANDROID: &str = "http://schemas.android.com/apk/res/android";
+        let doc = roxmltree::Document::parse(xml)?;
+        let manifest = doc.root_element();
+        let package = manifest
+            .attribute("package")
+            .ok_or_else(|| anyhow::anyhow!("manifest is missing a package"))?;
+        let has = |filter: roxmltree::Node, tag: &str, name: &str| {
+            filter.children().any(|node| {
+                node.has_tag_name(tag) && node.attribute((NS_ANDROID, "name")) == Some(name)
+            })
+        };
+        let activity = manifest
+            .descendants()
+            .filter(|node| node.has_tag_name("activity") || node.has_tag_name("activity-alias"))
+            .find(|activity| {
+                activity
+                    .children()
+                    .filter(|node| node.has_tag_name("intent-filter"))
+                    .any(|filter| {
+                        has(filter, "action", "android.intent.action.MAIN")
+                            && has(filter, "category", "android.intent.category.LAUNCHER")
+                    })
+            })
+            .and_then(|activity| activity.attribute((NS_ANDROID, "name")))
+            .ok_or_else(|| anyhow::anyhow!("manifest is missing a launcher activity"))?;
+        Ok(Self {
+            package: package.to_string(),
+            activity: activity.to_string(),
+        })
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
@@ -232,6 +573,73 @@ pub(crate) mod tests {
         Ok(jar.unwrap())
     }
 
+    #[test]
+    fn test_entry_point() -> Result<()> {
+        let xml = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.helloworld">
+            <application>
+                <activity android:name=".SettingsActivity">
+                    <intent-filter>
+                        <action android:name="android.intent.action.VIEW" />
+                    </intent-filter>
+                </activity>
+                <activity android:name=".MainActivity">
+                    <intent-filter>
+                        <action android:name="android.intent.action.MAIN" />
+                        <category android:name="android.intent.category.LAUNCHER" />
+                    </intent-filter>
+                </activity>
+            </application>
+        </manifest>"#;
+        let entry = EntryPoint::from_manifest(xml)?;
+        assert_eq!(entry.package, "com.example.helloworld");
+        assert_eq!(entry.activity, ".MainActivity");
+        Ok(())
+    }
+
+    #[test]
+    fn test_dex_name() {
+        assert_eq!(
+            dex_name(Path::new("dex/classes.dex")).unwrap(),
+            "classes.dex"
+        );
+        assert_eq!(dex_name(Path::new("classes2.dex")).unwrap(), "classes2.dex");
+        assert_eq!(
+            dex_name(Path::new("classes12.dex")).unwrap(),
+            "classes12.dex"
+        );
+        for name in ["classes1.dex", "classes02.dex", "classes_2.dex", "app.dex"] {
+            assert!(dex_name(Path::new(name)).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_verify_alignment() -> Result<()> {
+        use std::io::Write;
+        use zip::write::FileOptions;
+        let dir = std::env::temp_dir().join("apk_verify_alignment");
+        std::fs::create_dir_all(&dir)?;
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let write = |path: &Path, lib_alignment: u16| -> Result<()> {
+            let mut zip = zip::ZipWriter::new(File::create(path)?);
+            zip.start_file_aligned("resources.arsc", stored, 4)?;
+            zip.write_all(b"resources")?;
+            zip.start_file("classes.dex", FileOptions::default())?;
+            zip.write_all(b"dex")?;
+            zip.start_file_aligned("lib/arm64-v8a/libfoo.so", stored, lib_alignment)?;
+            zip.write_all(b"lib")?;
+            zip.finish()?;
+            Ok(())
+        };
+        let aligned = dir.join("aligned.apk");
+        write(&aligned, 16384)?;
+        Apk::verify_alignment(&aligned, 16384)?;
+        let misaligned = dir.join("misaligned.apk");
+        write(&misaligned, 4)?;
+        Apk::verify_alignment(&misaligned, 4)?;
+        assert!(Apk::verify_alignment(&misaligned, 16384).is_err());
+        Ok(())
+    }
+
     pub fn android_jar(platform: u16) -> Result<PathBuf> {
         let home = std::env::var("ANDROID_HOME")?;
         let android = Path::new(&home)
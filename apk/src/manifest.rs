@@ -9,6 +9,11 @@ pub struct AndroidManifest {
     #[serde(default = "default_namespace")]
     ns_android: String,
     pub package: Option<String>,
+    /// Name of a split apk like `config.arm64_v8a`, unset for the base apk.
+    pub split: Option<String>,
+    /// Name of the feature split a configuration split belongs to.
+    #[serde(rename(serialize = "configForSplit"))]
+    pub config_for_split: Option<String>,
     #[serde(rename(serialize = "android:versionCode"))]
     pub version_code: Option<u32>,
     #[serde(rename(serialize = "android:versionName"))]
@@ -30,6 +35,15 @@ pub struct AndroidManifest {
     #[serde(rename(serialize = "uses-permission"))]
     #[serde(default)]
     pub uses_permission: Vec<Permission>,
+    /// Permissions declared by the app for other apps to request.
+    #[serde(rename(serialize = "permission"))]
+    #[serde(default)]
+    pub permissions: Vec<PermissionDeclaration>,
+    #[serde(rename(serialize = "supports-screens"))]
+    pub supports_screens: Option<SupportsScreens>,
+    pub queries: Option<Queries>,
+    #[serde(default)]
+    pub instrumentation: Vec<Instrumentation>,
     #[serde(default)]
     pub application: Application,
 }
@@ -39,11 +53,17 @@ impl Default for AndroidManifest {
         Self {
             ns_android: default_namespace(),
             package: Default::default(),
+            split: Default::default(),
+            config_for_split: Default::default(),
             version_code: Default::default(),
             version_name: Default::default(),
             sdk: Default::default(),
             uses_feature: Default::default(),
             uses_permission: Default::default(),
+            permissions: Default::default(),
+            supports_screens: Default::default(),
+            queries: Default::default(),
+            instrumentation: Default::default(),
             application: Default::default(),
             compile_sdk_version: Default::default(),
             compile_sdk_version_codename: Default::default(),
@@ -62,6 +82,8 @@ impl std::fmt::Display for AndroidManifest {
 /// Android [application element](https://developer.android.com/guide/topics/manifest/application-element), containing an [`Activity`] element.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Application {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: Option<String>,
     #[serde(rename(serialize = "android:debuggable"))]
     pub debuggable: Option<bool>,
     #[serde(rename(serialize = "android:theme"))]
@@ -74,12 +96,36 @@ pub struct Application {
     pub label: Option<String>,
     #[serde(rename(serialize = "android:appComponentFactory"))]
     pub app_component_factory: Option<String>,
+    #[serde(rename(serialize = "android:extractNativeLibs"))]
+    pub extract_native_libs: Option<bool>,
+    #[serde(rename(serialize = "android:allowBackup"))]
+    pub allow_backup: Option<bool>,
+    #[serde(rename(serialize = "android:largeHeap"))]
+    pub large_heap: Option<bool>,
+    #[serde(rename(serialize = "android:supportsRtl"))]
+    pub supports_rtl: Option<bool>,
+    #[serde(rename(serialize = "android:usesCleartextTraffic"))]
+    pub uses_cleartext_traffic: Option<bool>,
+    #[serde(rename(serialize = "android:networkSecurityConfig"))]
+    pub network_security_config: Option<String>,
+    #[serde(rename(serialize = "uses-library"))]
+    #[serde(default)]
+    pub uses_library: Vec<UsesLibrary>,
     #[serde(rename(serialize = "meta-data"))]
     #[serde(default)]
     pub meta_data: Vec<MetaData>,
     #[serde(rename(serialize = "activity"))]
     #[serde(default)]
     pub activities: Vec<Activity>,
+    #[serde(rename(serialize = "service"))]
+    #[serde(default)]
+    pub services: Vec<Service>,
+    #[serde(rename(serialize = "receiver"))]
+    #[serde(default)]
+    pub receivers: Vec<Receiver>,
+    #[serde(rename(serialize = "provider"))]
+    #[serde(default)]
+    pub providers: Vec<Provider>,
 }
 
 /// Android [activity element](https://developer.android.com/guide/topics/manifest/activity-element).
@@ -101,6 +147,41 @@ pub struct Activity {
     pub exported: Option<bool>,
     #[serde(rename(serialize = "android:hardwareAccelerated"))]
     pub hardware_accelerated: Option<bool>,
+    #[serde(rename(serialize = "android:theme"))]
+    pub theme: Option<String>,
+    #[serde(rename(serialize = "android:icon"))]
+    pub icon: Option<String>,
+    #[serde(rename(serialize = "android:enabled"))]
+    pub enabled: Option<bool>,
+    #[serde(rename(serialize = "android:permission"))]
+    pub permission: Option<String>,
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
+    #[serde(rename(serialize = "android:taskAffinity"))]
+    pub task_affinity: Option<String>,
+    #[serde(rename(serialize = "android:parentActivityName"))]
+    pub parent_activity_name: Option<String>,
+    #[serde(rename(serialize = "android:documentLaunchMode"))]
+    pub document_launch_mode: Option<String>,
+    /// Like `hdr` or `wideColorGamut`.
+    #[serde(rename(serialize = "android:colorMode"))]
+    pub color_mode: Option<String>,
+    #[serde(rename(serialize = "android:resizeableActivity"))]
+    pub resizeable_activity: Option<bool>,
+    #[serde(rename(serialize = "android:supportsPictureInPicture"))]
+    pub supports_picture_in_picture: Option<bool>,
+    #[serde(rename(serialize = "android:maxAspectRatio"))]
+    pub max_aspect_ratio: Option<f32>,
+    #[serde(rename(serialize = "android:excludeFromRecents"))]
+    pub exclude_from_recents: Option<bool>,
+    #[serde(rename(serialize = "android:noHistory"))]
+    pub no_history: Option<bool>,
+    #[serde(rename(serialize = "android:showWhenLocked"))]
+    pub show_when_locked: Option<bool>,
+    #[serde(rename(serialize = "android:turnScreenOn"))]
+    pub turn_screen_on: Option<bool>,
+    #[serde(rename(serialize = "android:immersive"))]
+    pub immersive: Option<bool>,
     #[serde(rename(serialize = "meta-data"))]
     #[serde(default)]
     pub meta_data: Vec<MetaData>,
@@ -187,13 +268,109 @@ pub struct IntentFilterData {
     pub mime_type: Option<String>,
 }
 
+/// Android [service element](https://developer.android.com/guide/topics/manifest/service-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Service {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    #[serde(rename(serialize = "android:exported"))]
+    pub exported: Option<bool>,
+    #[serde(rename(serialize = "android:enabled"))]
+    pub enabled: Option<bool>,
+    #[serde(rename(serialize = "android:permission"))]
+    pub permission: Option<String>,
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
+    /// Flags like `location|mediaPlayback`, required by foreground services
+    /// targeting android 14 and later.
+    #[serde(rename(serialize = "android:foregroundServiceType"))]
+    pub foreground_service_type: Option<String>,
+    #[serde(rename(serialize = "android:isolatedProcess"))]
+    pub isolated_process: Option<bool>,
+    #[serde(rename(serialize = "android:directBootAware"))]
+    pub direct_boot_aware: Option<bool>,
+    #[serde(rename(serialize = "meta-data"))]
+    #[serde(default)]
+    pub meta_data: Vec<MetaData>,
+    #[serde(rename(serialize = "intent-filter"))]
+    #[serde(default)]
+    pub intent_filters: Vec<IntentFilter>,
+}
+
+/// Android [receiver element](https://developer.android.com/guide/topics/manifest/receiver-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Receiver {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    #[serde(rename(serialize = "android:exported"))]
+    pub exported: Option<bool>,
+    #[serde(rename(serialize = "android:enabled"))]
+    pub enabled: Option<bool>,
+    #[serde(rename(serialize = "android:permission"))]
+    pub permission: Option<String>,
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
+    #[serde(rename(serialize = "android:directBootAware"))]
+    pub direct_boot_aware: Option<bool>,
+    #[serde(rename(serialize = "meta-data"))]
+    #[serde(default)]
+    pub meta_data: Vec<MetaData>,
+    #[serde(rename(serialize = "intent-filter"))]
+    #[serde(default)]
+    pub intent_filters: Vec<IntentFilter>,
+}
+
+/// Android [provider element](https://developer.android.com/guide/topics/manifest/provider-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Provider {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    /// Semicolon separated list of authorities like
+    /// `com.example.helloworld.fileprovider`.
+    #[serde(rename(serialize = "android:authorities"))]
+    pub authorities: String,
+    #[serde(rename(serialize = "android:exported"))]
+    pub exported: Option<bool>,
+    #[serde(rename(serialize = "android:enabled"))]
+    pub enabled: Option<bool>,
+    #[serde(rename(serialize = "android:grantUriPermissions"))]
+    pub grant_uri_permissions: Option<bool>,
+    #[serde(rename(serialize = "android:permission"))]
+    pub permission: Option<String>,
+    #[serde(rename(serialize = "android:readPermission"))]
+    pub read_permission: Option<String>,
+    #[serde(rename(serialize = "android:writePermission"))]
+    pub write_permission: Option<String>,
+    #[serde(rename(serialize = "android:process"))]
+    pub process: Option<String>,
+    #[serde(rename(serialize = "android:initOrder"))]
+    pub init_order: Option<u32>,
+    #[serde(rename(serialize = "android:directBootAware"))]
+    pub direct_boot_aware: Option<bool>,
+    #[serde(rename(serialize = "meta-data"))]
+    #[serde(default)]
+    pub meta_data: Vec<MetaData>,
+}
+
 /// Android [meta-data element](https://developer.android.com/guide/topics/manifest/meta-data-element).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct MetaData {
     #[serde(rename(serialize = "android:name"))]
     pub name: String,
     #[serde(rename(serialize = "android:value"))]
-    pub value: String,
+    pub value: Option<String>,
+    /// Reference to a resource like `@xml/file_paths`.
+    #[serde(rename(serialize = "android:resource"))]
+    pub resource: Option<String>,
+}
+
+/// Android [uses-library element](https://developer.android.com/guide/topics/manifest/uses-library-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UsesLibrary {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    #[serde(rename(serialize = "android:required"))]
+    pub required: Option<bool>,
 }
 
 /// Android [uses-feature element](https://developer.android.com/guide/topics/manifest/uses-feature-element).
@@ -245,6 +422,121 @@ pub struct Permission {
     pub max_sdk_version: Option<u32>,
 }
 
+/// Android [permission element](https://developer.android.com/guide/topics/manifest/permission-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PermissionDeclaration {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    #[serde(rename(serialize = "android:label"))]
+    pub label: Option<String>,
+    #[serde(rename(serialize = "android:description"))]
+    pub description: Option<String>,
+    #[serde(rename(serialize = "android:icon"))]
+    pub icon: Option<String>,
+    #[serde(rename(serialize = "android:permissionGroup"))]
+    pub permission_group: Option<String>,
+    /// Like `normal`, `dangerous` or `signature`.
+    #[serde(rename(serialize = "android:protectionLevel"))]
+    pub protection_level: Option<String>,
+}
+
+/// Android [supports-screens element](https://developer.android.com/guide/topics/manifest/supports-screens-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SupportsScreens {
+    #[serde(rename(serialize = "android:resizeable"))]
+    pub resizeable: Option<bool>,
+    #[serde(rename(serialize = "android:smallScreens"))]
+    pub small_screens: Option<bool>,
+    #[serde(rename(serialize = "android:normalScreens"))]
+    pub normal_screens: Option<bool>,
+    #[serde(rename(serialize = "android:largeScreens"))]
+    pub large_screens: Option<bool>,
+    #[serde(rename(serialize = "android:xlargeScreens"))]
+    pub xlarge_screens: Option<bool>,
+    #[serde(rename(serialize = "android:anyDensity"))]
+    pub any_density: Option<bool>,
+    #[serde(rename(serialize = "android:requiresSmallestWidthDp"))]
+    pub requires_smallest_width_dp: Option<u32>,
+    #[serde(rename(serialize = "android:compatibleWidthLimitDp"))]
+    pub compatible_width_limit_dp: Option<u32>,
+    #[serde(rename(serialize = "android:largestWidthLimitDp"))]
+    pub largest_width_limit_dp: Option<u32>,
+}
+
+/// Android [queries element](https://developer.android.com/guide/topics/manifest/queries-element),
+/// declaring the other apps an app interacts with.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Queries {
+    /// Serialize strings wrapped in `<package android:name="..." />`
+    #[serde(serialize_with = "serialize_packages")]
+    #[serde(rename(serialize = "package"))]
+    #[serde(default)]
+    pub packages: Vec<String>,
+    #[serde(rename(serialize = "intent"))]
+    #[serde(default)]
+    pub intents: Vec<IntentFilter>,
+    /// Serialize strings wrapped in `<provider android:authorities="..." />`
+    #[serde(serialize_with = "serialize_authorities")]
+    #[serde(rename(serialize = "provider"))]
+    #[serde(default)]
+    pub providers: Vec<String>,
+}
+
+fn serialize_packages<S>(packages: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    use serde::ser::SerializeSeq;
+
+    #[derive(Serialize)]
+    struct Package {
+        #[serde(rename = "android:name")]
+        name: String,
+    }
+    let mut seq = serializer.serialize_seq(Some(packages.len()))?;
+    for package in packages {
+        seq.serialize_element(&Package {
+            name: package.clone(),
+        })?;
+    }
+    seq.end()
+}
+
+fn serialize_authorities<S>(authorities: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    use serde::ser::SerializeSeq;
+
+    #[derive(Serialize)]
+    struct Provider {
+        #[serde(rename = "android:authorities")]
+        authorities: String,
+    }
+    let mut seq = serializer.serialize_seq(Some(authorities.len()))?;
+    for authorities in authorities {
+        seq.serialize_element(&Provider {
+            authorities: authorities.clone(),
+        })?;
+    }
+    seq.end()
+}
+
+/// Android [instrumentation element](https://developer.android.com/guide/topics/manifest/instrumentation-element).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Instrumentation {
+    #[serde(rename(serialize = "android:name"))]
+    pub name: String,
+    #[serde(rename(serialize = "android:targetPackage"))]
+    pub target_package: String,
+    #[serde(rename(serialize = "android:label"))]
+    pub label: Option<String>,
+    #[serde(rename(serialize = "android:functionalTest"))]
+    pub functional_test: Option<bool>,
+    #[serde(rename(serialize = "android:handleProfiling"))]
+    pub handle_profiling: Option<bool>,
+}
+
 /// Android [uses-sdk element](https://developer.android.com/guide/topics/manifest/uses-sdk-element).
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Sdk {
@@ -269,3 +561,35 @@ impl Default for Sdk {
 fn default_namespace() -> String {
     "http://schemas.android.com/apk/res/android".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_components() {
+        let mut manifest = AndroidManifest {
+            queries: Some(Queries {
+                packages: vec!["com.example.other".into()],
+                intents: vec![],
+                providers: vec!["com.example.other.provider".into()],
+            }),
+            ..Default::default()
+        };
+        manifest.application.providers.push(Provider {
+            name: "androidx.core.content.FileProvider".into(),
+            authorities: "com.example.fileprovider".into(),
+            exported: Some(false),
+            meta_data: vec![MetaData {
+                name: "android.support.FILE_PROVIDER_PATHS".into(),
+                resource: Some("@xml/file_paths".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let xml = manifest.to_string();
+        assert!(!xml.contains("supports-screens"), "{}", xml);
+        assert!(xml.contains(r#"<queries><package android:name="com.example.other"/><provider android:authorities="com.example.other.provider"/></queries>"#), "{}", xml);
+        assert!(xml.contains(r#"<provider android:name="androidx.core.content.FileProvider" android:authorities="com.example.fileprovider" android:exported="false"><meta-data android:name="android.support.FILE_PROVIDER_PATHS" android:resource="@xml/file_paths"/></provider>"#), "{}", xml);
+    }
+}
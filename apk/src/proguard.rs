@@ -0,0 +1,227 @@
+//! Keep rules for classes which are only referenced by the manifest and xml
+//! resources, like `aapt2 link --proguard` generates them. Without them r8
+//! removes classes that are instantiated through reflection.
+use crate::aar::class_name;
+use crate::compiler::FileContents;
+use anyhow::Result;
+use roxmltree::{Document, Node};
+use std::collections::BTreeSet;
+
+const NS_ANDROID: &str = "http://schemas.android.com/apk/res/android";
+
+/// Attributes of `<application>` whose value is a class.
+const APPLICATION_CLASSES: [&str; 4] = [
+    "name",
+    "backupAgent",
+    "appComponentFactory",
+    "zygotePreloadName",
+];
+
+/// Elements of the manifest whose `android:name` is a class.
+const MANIFEST_COMPONENTS: [&str; 5] = [
+    "activity",
+    "instrumentation",
+    "provider",
+    "receiver",
+    "service",
+];
+
+/// Returns the keep rules for the merged manifest of the app and its xml
+/// resources. Relative class names are resolved using `package`.
+pub(crate) fn keep_rules(
+    package: &str,
+    manifest: &str,
+    files: &[(String, FileContents)],
+) -> Result<String> {
+    let mut rules = BTreeSet::new();
+    let doc = Document::parse(manifest)?;
+    for node in doc.descendants() {
+        let name = node.tag_name().name();
+        let attrs: &[&str] = if name == "application" {
+            &APPLICATION_CLASSES
+        } else if MANIFEST_COMPONENTS.contains(&name) {
+            &["name"]
+        } else {
+            continue;
+        };
+        for attr in attrs {
+            if let Some(class) = node.attribute((NS_ANDROID, *attr)) {
+                keep(&mut rules, &class_name(package, class), "<init>()");
+            }
+        }
+    }
+    for (path, contents) in files {
+        let xml = if let FileContents::Xml(xml) = contents {
+            xml
+        } else {
+            continue;
+        };
+        let ty = path
+            .strip_prefix("res/")
+            .and_then(|path| path.split(&['-', '/'][..]).next())
+            .unwrap_or_default();
+        if !matches!(ty, "layout" | "menu" | "xml") {
+            continue;
+        }
+        let doc = Document::parse(xml).map_err(|err| anyhow::anyhow!("{}: {}", path, err))?;
+        for node in doc.descendants().filter(|node| node.is_element()) {
+            match ty {
+                "layout" => layout_rules(&mut rules, package, node),
+                "menu" => menu_rules(&mut rules, node),
+                _ => xml_rules(&mut rules, package, node),
+            }
+        }
+    }
+    let mut out = String::new();
+    for rule in rules {
+        out.push_str(&rule);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn keep(rules: &mut BTreeSet<String>, class: &str, members: &str) {
+    rules.insert(format!("-keep class {} {{ {}; }}", class, members));
+}
+
+fn keep_method(rules: &mut BTreeSet<String>, method: &str, parameter: &str) {
+    rules.insert(format!(
+        "-keepclassmembers class * {{ *** {}({}); }}",
+        method, parameter
+    ));
+}
+
+/// Custom views are inflated using their two argument constructor and
+/// fragments using their default constructor.
+fn layout_rules(rules: &mut BTreeSet<String>, package: &str, node: Node) {
+    let name = node.tag_name().name();
+    if name.contains('.') {
+        keep(rules, name, "<init>(...)");
+    } else if name == "view" {
+        if let Some(class) = node.attribute("class") {
+            keep(rules, class, "<init>(...)");
+        }
+    } else if name == "fragment" {
+        let class = node
+            .attribute((NS_ANDROID, "name"))
+            .or_else(|| node.attribute("class"));
+        if let Some(class) = class {
+            keep(rules, &class_name(package, class), "<init>()");
+        }
+    }
+    if let Some(method) = node.attribute((NS_ANDROID, "onClick")) {
+        keep_method(rules, method, "android.view.View");
+    }
+}
+
+/// Action views and providers are declared with the `android` namespace or
+/// the namespace of the app when using appcompat.
+fn menu_rules(rules: &mut BTreeSet<String>, node: Node) {
+    for attr in node.attributes() {
+        match attr.name() {
+            "actionViewClass" | "actionProviderClass" => {
+                keep(rules, attr.value(), "<init>(...)");
+            }
+            "onClick" if attr.namespace() == Some(NS_ANDROID) => {
+                keep_method(rules, attr.value(), "android.view.MenuItem");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Custom preferences are inflated like views, fragments of preference
+/// headers using their default constructor.
+fn xml_rules(rules: &mut BTreeSet<String>, package: &str, node: Node) {
+    let name = node.tag_name().name();
+    if name.contains('.') {
+        keep(rules, name, "<init>(...)");
+    }
+    if let Some(class) = node.attribute((NS_ANDROID, "fragment")) {
+        keep(rules, &class_name(package, class), "<init>()");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_rules() -> Result<()> {
+        let manifest = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.app">
+            <application android:name=".App" android:backupAgent="com.example.Backup">
+                <activity android:name=".MainActivity" />
+                <activity-alias android:name=".Alias" android:targetActivity=".MainActivity" />
+                <service android:name="Service" />
+                <receiver android:name="androidx.core.Receiver" />
+                <provider android:name="androidx.core.content.FileProvider" />
+                <meta-data android:name="com.example.key" android:value="value" />
+            </application>
+            <instrumentation android:name=".Test" />
+        </manifest>"#;
+        let rules = keep_rules("com.example.app", manifest, &[])?;
+        assert_eq!(
+            rules.lines().collect::<Vec<_>>(),
+            [
+                "-keep class androidx.core.Receiver { <init>(); }",
+                "-keep class androidx.core.content.FileProvider { <init>(); }",
+                "-keep class com.example.Backup { <init>(); }",
+                "-keep class com.example.app.App { <init>(); }",
+                "-keep class com.example.app.MainActivity { <init>(); }",
+                "-keep class com.example.app.Service { <init>(); }",
+                "-keep class com.example.app.Test { <init>(); }",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_rules() -> Result<()> {
+        let manifest = r#"<manifest package="com.example.app"><application /></manifest>"#;
+        let layout = r#"<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android">
+            <com.example.app.CustomView android:onClick="onCustomClick" />
+            <view class="com.example.app.OtherView" />
+            <fragment android:name=".MainFragment" />
+            <Button android:onClick="onButtonClick" />
+        </LinearLayout>"#;
+        let menu = r#"<menu xmlns:android="http://schemas.android.com/apk/res/android" xmlns:app="http://schemas.android.com/apk/res-auto">
+            <item app:actionViewClass="androidx.appcompat.widget.SearchView" android:onClick="onSearch" />
+            <item android:actionProviderClass="com.example.app.ShareProvider" />
+        </menu>"#;
+        let headers = r#"<preference-headers xmlns:android="http://schemas.android.com/apk/res/android">
+            <header android:fragment=".SettingsFragment" />
+        </preference-headers>"#;
+        let files = [
+            (
+                "res/layout-land/main.xml".to_string(),
+                FileContents::Xml(layout.into()),
+            ),
+            ("res/menu/main.xml".into(), FileContents::Xml(menu.into())),
+            (
+                "res/xml/headers.xml".into(),
+                FileContents::Xml(headers.into()),
+            ),
+            // values and other resource types don't reference classes
+            (
+                "res/drawable/icon.xml".into(),
+                FileContents::Xml("<vector class=\"com.example.Unused\" />".into()),
+            ),
+        ];
+        let rules = keep_rules("com.example.app", manifest, &files)?;
+        assert_eq!(
+            rules.lines().collect::<Vec<_>>(),
+            [
+                "-keep class androidx.appcompat.widget.SearchView { <init>(...); }",
+                "-keep class com.example.app.CustomView { <init>(...); }",
+                "-keep class com.example.app.MainFragment { <init>(); }",
+                "-keep class com.example.app.OtherView { <init>(...); }",
+                "-keep class com.example.app.SettingsFragment { <init>(); }",
+                "-keep class com.example.app.ShareProvider { <init>(...); }",
+                "-keepclassmembers class * { *** onButtonClick(android.view.View); }",
+                "-keepclassmembers class * { *** onCustomClick(android.view.View); }",
+                "-keepclassmembers class * { *** onSearch(android.view.MenuItem); }",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::compiler::{Entry, Table};
 use anyhow::Result;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -13,13 +14,16 @@ pub enum ChunkType {
     XmlEndNamespace = 0x0101,
     XmlStartElement = 0x0102,
     XmlEndElement = 0x0103,
-    //XmlCdata = 0x0104,
+    XmlCdata = 0x0104,
     //XmlLastChunk = 0x017f,
     XmlResourceMap = 0x0180,
     TablePackage = 0x0200,
     TableType = 0x0201,
     TableTypeSpec = 0x0202,
-    Unknown = 0x0206,
+    TableLibrary = 0x0203,
+    TableOverlayable = 0x0204,
+    TableOverlayablePolicy = 0x0205,
+    TableStagedAlias = 0x0206,
 }
 
 impl ChunkType {
@@ -33,13 +37,18 @@ impl ChunkType {
             ty if ty == ChunkType::XmlEndNamespace as u16 => ChunkType::XmlEndNamespace,
             ty if ty == ChunkType::XmlStartElement as u16 => ChunkType::XmlStartElement,
             ty if ty == ChunkType::XmlEndElement as u16 => ChunkType::XmlEndElement,
-            //ty if ty == ChunkType::XmlCdata as u16 => ChunkType::XmlCdata,
+            ty if ty == ChunkType::XmlCdata as u16 => ChunkType::XmlCdata,
             //ty if ty == ChunkType::XmlLastChunk as u16 => ChunkType::XmlLastChunk,
             ty if ty == ChunkType::XmlResourceMap as u16 => ChunkType::XmlResourceMap,
             ty if ty == ChunkType::TablePackage as u16 => ChunkType::TablePackage,
             ty if ty == ChunkType::TableType as u16 => ChunkType::TableType,
             ty if ty == ChunkType::TableTypeSpec as u16 => ChunkType::TableTypeSpec,
-            ty if ty == ChunkType::Unknown as u16 => ChunkType::Unknown,
+            ty if ty == ChunkType::TableLibrary as u16 => ChunkType::TableLibrary,
+            ty if ty == ChunkType::TableOverlayable as u16 => ChunkType::TableOverlayable,
+            ty if ty == ChunkType::TableOverlayablePolicy as u16 => {
+                ChunkType::TableOverlayablePolicy
+            }
+            ty if ty == ChunkType::TableStagedAlias as u16 => ChunkType::TableStagedAlias,
             _ => return None,
         })
     }
@@ -317,6 +326,28 @@ impl ResXmlEndElement {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResXmlCdata {
+    /// The raw character data.
+    pub data: i32,
+    /// The typed value of the character data if this is a CDATA node.
+    pub typed_data: ResValue,
+}
+
+impl ResXmlCdata {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let data = r.read_i32::<LittleEndian>()?;
+        let typed_data = ResValue::read(r)?;
+        Ok(Self { data, typed_data })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_i32::<LittleEndian>(self.data)?;
+        self.typed_data.write(w)?;
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct ResTableRef(u32);
 
@@ -385,20 +416,7 @@ pub struct ResTablePackageHeader {
 impl ResTablePackageHeader {
     pub fn read<R: Read + Seek>(r: &mut R) -> Result<Self> {
         let id = r.read_u32::<LittleEndian>()?;
-        let mut name = [0; 128];
-        let mut name_len = 0xff;
-        for (i, item) in name.iter_mut().enumerate() {
-            let c = r.read_u16::<LittleEndian>()?;
-            if name_len < 128 {
-                continue;
-            }
-            if c == 0 {
-                name_len = i;
-            } else {
-                *item = c;
-            }
-        }
-        let name = String::from_utf16(&name[..name_len])?;
+        let name = read_fixed_utf16(r, 128)?;
         let type_strings = r.read_u32::<LittleEndian>()?;
         let last_public_type = r.read_u32::<LittleEndian>()?;
         let key_strings = r.read_u32::<LittleEndian>()?;
@@ -417,13 +435,7 @@ impl ResTablePackageHeader {
 
     pub fn write(&self, w: &mut impl Write) -> Result<()> {
         w.write_u32::<LittleEndian>(self.id)?;
-        let mut name = [0; 128];
-        for (i, c) in self.name.encode_utf16().enumerate() {
-            name[i] = c;
-        }
-        for c in name {
-            w.write_u16::<LittleEndian>(c)?;
-        }
+        write_fixed_utf16(w, &self.name, 128)?;
         w.write_u32::<LittleEndian>(self.type_strings)?;
         w.write_u32::<LittleEndian>(self.last_public_type)?;
         w.write_u32::<LittleEndian>(self.key_strings)?;
@@ -476,11 +488,12 @@ pub struct ResTableTypeHeader {
     /// at 1 (corresponding to the value of the type bits in a
     /// resource identifier). 0 is invalid.
     pub id: u8,
-    /// Must be 0.
-    pub res0: u8,
+    /// `FLAG_SPARSE` or `FLAG_OFFSET16`.
+    pub flags: u8,
     /// Must be 0.
     pub res1: u16,
-    /// Number of u32 entry indices that follow.
+    /// Number of entry indices that follow. Sparse types only index
+    /// the entries which are present.
     pub entry_count: u32,
     /// Offset from header where ResTableEntry data starts.
     pub entries_start: u32,
@@ -489,16 +502,22 @@ pub struct ResTableTypeHeader {
 }
 
 impl ResTableTypeHeader {
+    /// The index consists of `(entry id, offset / 4)` u16 pairs of the
+    /// entries which are present.
+    pub const FLAG_SPARSE: u8 = 0x01;
+    /// The index consists of u16 `offset / 4`, `0xffff` marks a missing entry.
+    pub const FLAG_OFFSET16: u8 = 0x02;
+
     pub fn read(r: &mut impl Read) -> Result<Self> {
         let id = r.read_u8()?;
-        let res0 = r.read_u8()?;
+        let flags = r.read_u8()?;
         let res1 = r.read_u16::<LittleEndian>()?;
         let entry_count = r.read_u32::<LittleEndian>()?;
         let entries_start = r.read_u32::<LittleEndian>()?;
         let config = ResTableConfig::read(r)?;
         Ok(Self {
             id,
-            res0,
+            flags,
             res1,
             entry_count,
             entries_start,
@@ -508,7 +527,7 @@ impl ResTableTypeHeader {
 
     pub fn write(&self, w: &mut impl Write) -> Result<()> {
         w.write_u8(self.id)?;
-        w.write_u8(self.res0)?;
+        w.write_u8(self.flags)?;
         w.write_u16::<LittleEndian>(self.res1)?;
         w.write_u32::<LittleEndian>(self.entry_count)?;
         w.write_u32::<LittleEndian>(self.entries_start)?;
@@ -517,80 +536,340 @@ impl ResTableTypeHeader {
     }
 }
 
+/// Describes the device configuration a set of resources is designed for.
+/// Zero means any value matches.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ResTableConfig {
+    /// Size of the config on disk. Fields beyond the size are zero.
     pub size: u32,
-    pub imsi: u32,
-    pub locale: u32,
-    pub screen_type: ScreenType,
-    pub input: u32,
-    pub screen_size: u32,
-    pub version: u32,
+    /// Mobile country code from the SIM.
+    pub mcc: u16,
+    /// Mobile network code from the SIM.
+    pub mnc: u16,
+    /// Two ascii letters, three letter codes are packed into two bytes.
+    pub language: [u8; 2],
+    /// Two ascii letters, three digit codes are packed into two bytes.
+    pub region: [u8; 2],
+    pub orientation: u8,
+    pub touchscreen: u8,
+    pub density: u16,
+    pub keyboard: u8,
+    pub navigation: u8,
+    /// Keyboard and navigation availability.
+    pub input_flags: u8,
+    pub input_pad0: u8,
+    pub screen_width: u16,
+    pub screen_height: u16,
+    pub sdk_version: u16,
+    /// Must be 0.
+    pub minor_version: u16,
+    /// Screen size, long screen and layout direction.
+    pub screen_layout: u8,
+    /// Ui mode type and night mode.
+    pub ui_mode: u8,
+    pub smallest_screen_width_dp: u16,
+    pub screen_width_dp: u16,
+    pub screen_height_dp: u16,
+    pub locale_script: [u8; 4],
+    pub locale_variant: [u8; 8],
+    /// Round screen.
+    pub screen_layout2: u8,
+    /// Wide color gamut and HDR.
+    pub color_mode: u8,
+    pub screen_config_pad2: u16,
+    pub locale_script_was_computed: bool,
+    pub locale_numbering_system: [u8; 8],
+    /// Trailing bytes not covered by the fields above.
     pub unknown: Vec<u8>,
 }
 
+impl Default for ResTableConfig {
+    fn default() -> Self {
+        Self {
+            size: Self::SIZE,
+            mcc: 0,
+            mnc: 0,
+            language: [0; 2],
+            region: [0; 2],
+            orientation: 0,
+            touchscreen: 0,
+            density: 0,
+            keyboard: 0,
+            navigation: 0,
+            input_flags: 0,
+            input_pad0: 0,
+            screen_width: 0,
+            screen_height: 0,
+            sdk_version: 0,
+            minor_version: 0,
+            screen_layout: 0,
+            ui_mode: 0,
+            smallest_screen_width_dp: 0,
+            screen_width_dp: 0,
+            screen_height_dp: 0,
+            locale_script: [0; 4],
+            locale_variant: [0; 8],
+            screen_layout2: 0,
+            color_mode: 0,
+            screen_config_pad2: 0,
+            locale_script_was_computed: false,
+            locale_numbering_system: [0; 8],
+            unknown: vec![0; (Self::SIZE - Self::KNOWN_SIZE) as usize],
+        }
+    }
+}
+
 impl ResTableConfig {
+    /// Size written by current versions of aapt2.
+    pub const SIZE: u32 = 64;
+    /// Size of the fields including `size`.
+    const KNOWN_SIZE: u32 = 61;
+
+    pub const CONFIG_MCC: u32 = 0x0001;
+    pub const CONFIG_MNC: u32 = 0x0002;
+    pub const CONFIG_LOCALE: u32 = 0x0004;
+    pub const CONFIG_TOUCHSCREEN: u32 = 0x0008;
+    pub const CONFIG_KEYBOARD: u32 = 0x0010;
+    pub const CONFIG_KEYBOARD_HIDDEN: u32 = 0x0020;
+    pub const CONFIG_NAVIGATION: u32 = 0x0040;
+    pub const CONFIG_ORIENTATION: u32 = 0x0080;
+    pub const CONFIG_DENSITY: u32 = 0x0100;
+    pub const CONFIG_SCREEN_SIZE: u32 = 0x0200;
+    pub const CONFIG_VERSION: u32 = 0x0400;
+    pub const CONFIG_SCREEN_LAYOUT: u32 = 0x0800;
+    pub const CONFIG_UI_MODE: u32 = 0x1000;
+    pub const CONFIG_SMALLEST_SCREEN_SIZE: u32 = 0x2000;
+    pub const CONFIG_LAYOUTDIR: u32 = 0x4000;
+    pub const CONFIG_SCREEN_ROUND: u32 = 0x8000;
+    pub const CONFIG_COLOR_MODE: u32 = 0x10000;
+
+    pub const MASK_KEYSHIDDEN: u8 = 0x03;
+    pub const MASK_NAVHIDDEN: u8 = 0x0c;
+    pub const MASK_SCREENSIZE: u8 = 0x0f;
+    pub const MASK_SCREENLONG: u8 = 0x30;
+    pub const MASK_LAYOUTDIR: u8 = 0xc0;
+    pub const MASK_UI_MODE_TYPE: u8 = 0x0f;
+    pub const MASK_UI_MODE_NIGHT: u8 = 0x30;
+    pub const MASK_SCREENROUND: u8 = 0x03;
+    pub const MASK_WIDE_COLOR_GAMUT: u8 = 0x03;
+    pub const MASK_HDR: u8 = 0x0c;
+
+    pub const DENSITY_ANY: u16 = 0xfffe;
+    pub const DENSITY_NONE: u16 = 0xffff;
+
     pub fn read(r: &mut impl Read) -> Result<Self> {
         let size = r.read_u32::<LittleEndian>()?;
-        let imsi = r.read_u32::<LittleEndian>()?;
-        let locale = r.read_u32::<LittleEndian>()?;
-        let screen_type = ScreenType::read(r)?;
-        let input = r.read_u32::<LittleEndian>()?;
-        let screen_size = r.read_u32::<LittleEndian>()?;
-        let version = r.read_u32::<LittleEndian>()?;
-        let unknown_len = size as usize - 28;
-        let mut unknown = vec![0; unknown_len];
-        r.read_exact(&mut unknown)?;
-        Ok(Self {
+        anyhow::ensure!(size >= 28, "invalid config size {}", size);
+        let mut buf = vec![0; size as usize - 4];
+        r.read_exact(&mut buf)?;
+        let known = Self::KNOWN_SIZE as usize - 4;
+        let unknown = if buf.len() > known {
+            buf.split_off(known)
+        } else {
+            vec![]
+        };
+        buf.resize(known, 0);
+        let r = &mut &buf[..];
+        let mut config = Self {
             size,
-            imsi,
-            locale,
-            screen_type,
-            input,
-            screen_size,
-            version,
+            mcc: r.read_u16::<LittleEndian>()?,
+            mnc: r.read_u16::<LittleEndian>()?,
             unknown,
-        })
+            ..Default::default()
+        };
+        r.read_exact(&mut config.language)?;
+        r.read_exact(&mut config.region)?;
+        config.orientation = r.read_u8()?;
+        config.touchscreen = r.read_u8()?;
+        config.density = r.read_u16::<LittleEndian>()?;
+        config.keyboard = r.read_u8()?;
+        config.navigation = r.read_u8()?;
+        config.input_flags = r.read_u8()?;
+        config.input_pad0 = r.read_u8()?;
+        config.screen_width = r.read_u16::<LittleEndian>()?;
+        config.screen_height = r.read_u16::<LittleEndian>()?;
+        config.sdk_version = r.read_u16::<LittleEndian>()?;
+        config.minor_version = r.read_u16::<LittleEndian>()?;
+        config.screen_layout = r.read_u8()?;
+        config.ui_mode = r.read_u8()?;
+        config.smallest_screen_width_dp = r.read_u16::<LittleEndian>()?;
+        config.screen_width_dp = r.read_u16::<LittleEndian>()?;
+        config.screen_height_dp = r.read_u16::<LittleEndian>()?;
+        r.read_exact(&mut config.locale_script)?;
+        r.read_exact(&mut config.locale_variant)?;
+        config.screen_layout2 = r.read_u8()?;
+        config.color_mode = r.read_u8()?;
+        config.screen_config_pad2 = r.read_u16::<LittleEndian>()?;
+        config.locale_script_was_computed = r.read_u8()? != 0;
+        r.read_exact(&mut config.locale_numbering_system)?;
+        Ok(config)
     }
 
     pub fn write(&self, w: &mut impl Write) -> Result<()> {
-        w.write_u32::<LittleEndian>(self.size)?;
-        w.write_u32::<LittleEndian>(self.imsi)?;
-        w.write_u32::<LittleEndian>(self.locale)?;
-        self.screen_type.write(w)?;
-        w.write_u32::<LittleEndian>(self.input)?;
-        w.write_u32::<LittleEndian>(self.screen_size)?;
-        w.write_u32::<LittleEndian>(self.version)?;
+        let mut buf = Vec::with_capacity(Self::KNOWN_SIZE as usize);
+        buf.write_u32::<LittleEndian>(self.size)?;
+        buf.write_u16::<LittleEndian>(self.mcc)?;
+        buf.write_u16::<LittleEndian>(self.mnc)?;
+        buf.write_all(&self.language)?;
+        buf.write_all(&self.region)?;
+        buf.write_u8(self.orientation)?;
+        buf.write_u8(self.touchscreen)?;
+        buf.write_u16::<LittleEndian>(self.density)?;
+        buf.write_u8(self.keyboard)?;
+        buf.write_u8(self.navigation)?;
+        buf.write_u8(self.input_flags)?;
+        buf.write_u8(self.input_pad0)?;
+        buf.write_u16::<LittleEndian>(self.screen_width)?;
+        buf.write_u16::<LittleEndian>(self.screen_height)?;
+        buf.write_u16::<LittleEndian>(self.sdk_version)?;
+        buf.write_u16::<LittleEndian>(self.minor_version)?;
+        buf.write_u8(self.screen_layout)?;
+        buf.write_u8(self.ui_mode)?;
+        buf.write_u16::<LittleEndian>(self.smallest_screen_width_dp)?;
+        buf.write_u16::<LittleEndian>(self.screen_width_dp)?;
+        buf.write_u16::<LittleEndian>(self.screen_height_dp)?;
+        buf.write_all(&self.locale_script)?;
+        buf.write_all(&self.locale_variant)?;
+        buf.write_u8(self.screen_layout2)?;
+        buf.write_u8(self.color_mode)?;
+        buf.write_u16::<LittleEndian>(self.screen_config_pad2)?;
+        buf.write_u8(self.locale_script_was_computed as u8)?;
+        buf.write_all(&self.locale_numbering_system)?;
+        buf.truncate(self.size as usize);
+        w.write_all(&buf)?;
         w.write_all(&self.unknown)?;
         Ok(())
     }
-}
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct ScreenType {
-    pub orientation: u8,
-    pub touchscreen: u8,
-    pub density: u16,
+    /// Returns the `CONFIG_*` flags of the fields that differ.
+    pub fn diff(&self, other: &Self) -> u32 {
+        let mut diff = 0;
+        if self.mcc != other.mcc {
+            diff |= Self::CONFIG_MCC;
+        }
+        if self.mnc != other.mnc {
+            diff |= Self::CONFIG_MNC;
+        }
+        if self.language != other.language
+            || self.region != other.region
+            || self.locale_script != other.locale_script
+            || self.locale_variant != other.locale_variant
+            || self.locale_numbering_system != other.locale_numbering_system
+        {
+            diff |= Self::CONFIG_LOCALE;
+        }
+        if self.orientation != other.orientation {
+            diff |= Self::CONFIG_ORIENTATION;
+        }
+        if self.density != other.density {
+            diff |= Self::CONFIG_DENSITY;
+        }
+        if self.touchscreen != other.touchscreen {
+            diff |= Self::CONFIG_TOUCHSCREEN;
+        }
+        if (self.input_flags ^ other.input_flags) & (Self::MASK_KEYSHIDDEN | Self::MASK_NAVHIDDEN)
+            != 0
+        {
+            diff |= Self::CONFIG_KEYBOARD_HIDDEN;
+        }
+        if self.keyboard != other.keyboard {
+            diff |= Self::CONFIG_KEYBOARD;
+        }
+        if self.navigation != other.navigation {
+            diff |= Self::CONFIG_NAVIGATION;
+        }
+        if self.screen_width != other.screen_width
+            || self.screen_height != other.screen_height
+            || self.screen_width_dp != other.screen_width_dp
+            || self.screen_height_dp != other.screen_height_dp
+        {
+            diff |= Self::CONFIG_SCREEN_SIZE;
+        }
+        if self.sdk_version != other.sdk_version || self.minor_version != other.minor_version {
+            diff |= Self::CONFIG_VERSION;
+        }
+        if (self.screen_layout ^ other.screen_layout) & Self::MASK_LAYOUTDIR != 0 {
+            diff |= Self::CONFIG_LAYOUTDIR;
+        }
+        if (self.screen_layout ^ other.screen_layout) & !Self::MASK_LAYOUTDIR != 0 {
+            diff |= Self::CONFIG_SCREEN_LAYOUT;
+        }
+        if (self.screen_layout2 ^ other.screen_layout2) & Self::MASK_SCREENROUND != 0 {
+            diff |= Self::CONFIG_SCREEN_ROUND;
+        }
+        if (self.color_mode ^ other.color_mode) & (Self::MASK_WIDE_COLOR_GAMUT | Self::MASK_HDR)
+            != 0
+        {
+            diff |= Self::CONFIG_COLOR_MODE;
+        }
+        if self.ui_mode != other.ui_mode {
+            diff |= Self::CONFIG_UI_MODE;
+        }
+        if self.smallest_screen_width_dp != other.smallest_screen_width_dp {
+            diff |= Self::CONFIG_SMALLEST_SCREEN_SIZE;
+        }
+        diff
+    }
+
+    /// Returns the locale as a BCP 47 language tag like `sr-Latn-RS`.
+    pub fn locale(&self) -> Option<String> {
+        let language = unpack_language_or_region(self.language, b'a')?;
+        let mut tag = language;
+        let script = ascii(&self.locale_script);
+        if !script.is_empty() && !self.locale_script_was_computed {
+            tag.push('-');
+            tag.push_str(&script);
+        }
+        if let Some(region) = unpack_language_or_region(self.region, b'0') {
+            tag.push('-');
+            tag.push_str(&region);
+        }
+        let variant = ascii(&self.locale_variant);
+        if !variant.is_empty() {
+            tag.push('-');
+            tag.push_str(&variant);
+        }
+        Some(tag)
+    }
 }
 
-impl ScreenType {
-    pub fn read(r: &mut impl Read) -> Result<Self> {
-        let orientation = r.read_u8()?;
-        let touchscreen = r.read_u8()?;
-        let density = r.read_u16::<LittleEndian>()?;
-        Ok(Self {
-            orientation,
-            touchscreen,
-            density,
-        })
+/// Packs a two or three letter language or region code into two bytes.
+pub fn pack_language_or_region(s: &str, base: u8) -> Result<[u8; 2]> {
+    let b = s.as_bytes();
+    match b.len() {
+        0 => Ok([0; 2]),
+        2 => Ok([b[0], b[1]]),
+        3 => {
+            let first = b[0].wrapping_sub(base) & 0x7f;
+            let second = b[1].wrapping_sub(base) & 0x7f;
+            let third = b[2].wrapping_sub(base) & 0x7f;
+            Ok([0x80 | (third << 2) | (second >> 3), (second << 5) | first])
+        }
+        _ => anyhow::bail!("invalid language or region {}", s),
     }
+}
 
-    pub fn write(&self, w: &mut impl Write) -> Result<()> {
-        w.write_u8(self.orientation)?;
-        w.write_u8(self.touchscreen)?;
-        w.write_u16::<LittleEndian>(self.density)?;
-        Ok(())
+fn unpack_language_or_region(b: [u8; 2], base: u8) -> Option<String> {
+    if b[0] == 0 {
+        return None;
     }
+    if b[0] & 0x80 == 0 {
+        return Some(String::from_utf8_lossy(&b).into_owned());
+    }
+    let first = b[1] & 0x1f;
+    let second = ((b[1] & 0xe0) >> 5) | ((b[0] & 0x03) << 3);
+    let third = (b[0] & 0x7c) >> 2;
+    Some(
+        [first, second, third]
+            .iter()
+            .map(|c| (c + base) as char)
+            .collect(),
+    )
+}
+
+fn ascii(b: &[u8]) -> String {
+    let len = b.iter().position(|b| *b == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..len]).into_owned()
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -602,6 +881,10 @@ pub struct ResTableEntry {
 }
 
 impl ResTableEntry {
+    /// The entry is a u16 key, the flags with the value type in the high
+    /// byte and the u32 value data.
+    pub const FLAG_COMPACT: u16 = 0x8;
+
     pub fn is_complex(&self) -> bool {
         self.flags & 0x1 > 0
     }
@@ -610,10 +893,28 @@ impl ResTableEntry {
         self.flags & 0x2 > 0
     }
 
+    pub fn is_compact(&self) -> bool {
+        self.flags & Self::FLAG_COMPACT > 0
+    }
+
     pub fn read(r: &mut impl Read) -> Result<Self> {
         let size = r.read_u16::<LittleEndian>()?;
         let flags = r.read_u16::<LittleEndian>()?;
         let key = r.read_u32::<LittleEndian>()?;
+        if flags & Self::FLAG_COMPACT > 0 {
+            // the value type in the high byte of the flags is part of the value
+            return Ok(Self {
+                size: 8,
+                flags: flags & 0xff,
+                key: size as u32,
+                value: ResTableValue::Simple(ResValue {
+                    size: 8,
+                    res0: 0,
+                    data_type: (flags >> 8) as u8,
+                    data: key,
+                }),
+            });
+        }
         let is_complex = flags & 0x1 > 0;
         if is_complex {
             debug_assert_eq!(size, 16);
@@ -630,6 +931,17 @@ impl ResTableEntry {
     }
 
     pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        if self.is_compact() {
+            let value = match &self.value {
+                ResTableValue::Simple(value) => value,
+                ResTableValue::Complex(_, _) => anyhow::bail!("compact entries can't be complex"),
+            };
+            anyhow::ensure!(self.key <= 0xffff, "key of compact entry out of range");
+            w.write_u16::<LittleEndian>(self.key as u16)?;
+            w.write_u16::<LittleEndian>(self.flags & 0xff | (value.data_type as u16) << 8)?;
+            w.write_u32::<LittleEndian>(value.data)?;
+            return Ok(());
+        }
         w.write_u16::<LittleEndian>(self.size)?;
         w.write_u16::<LittleEndian>(self.flags)?;
         w.write_u32::<LittleEndian>(self.key)?;
@@ -851,21 +1163,176 @@ impl ResSpan {
     }
 }
 
+/// Maps the package ids of shared libraries, which are assigned at runtime,
+/// to their package names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResTableLibraryHeader {
+    /// Number of `ResTableLibraryEntry`s that follow.
+    pub count: u32,
+}
+
+impl ResTableLibraryHeader {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let count = r.read_u32::<LittleEndian>()?;
+        Ok(Self { count })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.count)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResTableLibraryEntry {
+    /// Package id the library was compiled with.
+    pub package_id: u32,
+    /// Name of the package, at most 127 utf16 code units.
+    pub package_name: String,
+}
+
+impl ResTableLibraryEntry {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let package_id = r.read_u32::<LittleEndian>()?;
+        let package_name = read_fixed_utf16(r, 128)?;
+        Ok(Self {
+            package_id,
+            package_name,
+        })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.package_id)?;
+        write_fixed_utf16(w, &self.package_name, 128)?;
+        Ok(())
+    }
+}
+
+/// Declares a set of resources which can be overlaid by runtime resource
+/// overlays, followed by `Chunk::TableOverlayablePolicy` chunks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResTableOverlayableHeader {
+    /// Name of the overlayable set, at most 255 utf16 code units.
+    pub name: String,
+    /// Actor allowed to overlay the resources, at most 255 utf16 code units.
+    pub actor: String,
+}
+
+impl ResTableOverlayableHeader {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let name = read_fixed_utf16(r, 256)?;
+        let actor = read_fixed_utf16(r, 256)?;
+        Ok(Self { name, actor })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        write_fixed_utf16(w, &self.name, 256)?;
+        write_fixed_utf16(w, &self.actor, 256)?;
+        Ok(())
+    }
+}
+
+/// Policies restricting which overlays may overlay the resources that follow.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResTableOverlayablePolicyHeader {
+    pub policy_flags: u32,
+    /// Number of resource ids that follow.
+    pub entry_count: u32,
+}
+
+impl ResTableOverlayablePolicyHeader {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let policy_flags = r.read_u32::<LittleEndian>()?;
+        let entry_count = r.read_u32::<LittleEndian>()?;
+        Ok(Self {
+            policy_flags,
+            entry_count,
+        })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.policy_flags)?;
+        w.write_u32::<LittleEndian>(self.entry_count)?;
+        Ok(())
+    }
+}
+
+/// Maps the ids of resources staged for the next platform release to their
+/// finalized ids.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResTableStagedAliasHeader {
+    /// Number of `ResTableStagedAliasEntry`s that follow.
+    pub count: u32,
+}
+
+impl ResTableStagedAliasHeader {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let count = r.read_u32::<LittleEndian>()?;
+        Ok(Self { count })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.count)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResTableStagedAliasEntry {
+    pub staged_res_id: u32,
+    pub finalized_res_id: u32,
+}
+
+impl ResTableStagedAliasEntry {
+    pub fn read(r: &mut impl Read) -> Result<Self> {
+        let staged_res_id = r.read_u32::<LittleEndian>()?;
+        let finalized_res_id = r.read_u32::<LittleEndian>()?;
+        Ok(Self {
+            staged_res_id,
+            finalized_res_id,
+        })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.staged_res_id)?;
+        w.write_u32::<LittleEndian>(self.finalized_res_id)?;
+        Ok(())
+    }
+}
+
+/// Offset of a missing entry in the index of a `Chunk::TableType`.
+pub const NO_ENTRY: u32 = 0xffff_ffff;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Chunk {
     Null,
-    StringPool(Vec<String>, Vec<Vec<ResSpan>>),
+    /// The `ResStringPoolHeader` flags, strings and the spans of the first
+    /// strings. Pools are written in utf16 unless the `UTF8_FLAG` is set.
+    /// The last field holds the offsets of the strings when they don't follow
+    /// each other, like when duplicate strings share their data, and is empty
+    /// otherwise.
+    StringPool(u32, Vec<String>, Vec<Vec<ResSpan>>, Vec<u32>),
     Table(ResTableHeader, Vec<Chunk>),
     Xml(Vec<Chunk>),
     XmlStartNamespace(ResXmlNodeHeader, ResXmlNamespace),
     XmlEndNamespace(ResXmlNodeHeader, ResXmlNamespace),
     XmlStartElement(ResXmlNodeHeader, ResXmlStartElement, Vec<ResXmlAttribute>),
     XmlEndElement(ResXmlNodeHeader, ResXmlEndElement),
+    XmlCdata(ResXmlNodeHeader, ResXmlCdata),
     XmlResourceMap(Vec<u32>),
     TablePackage(ResTablePackageHeader, Vec<Chunk>),
+    /// The index holds the offset of each entry relative to the start of the
+    /// entries or `NO_ENTRY`, both are indexed by entry id regardless of the
+    /// index encoding selected by the flags of the header.
     TableType(ResTableTypeHeader, Vec<u32>, Vec<Option<ResTableEntry>>),
     TableTypeSpec(ResTableTypeSpecHeader, Vec<u32>),
-    Unknown,
+    TableLibrary(ResTableLibraryHeader, Vec<ResTableLibraryEntry>),
+    TableOverlayable(ResTableOverlayableHeader, Vec<Chunk>),
+    TableOverlayablePolicy(ResTableOverlayablePolicyHeader, Vec<ResTableRef>),
+    TableStagedAlias(ResTableStagedAliasHeader, Vec<ResTableStagedAliasEntry>),
+    /// Chunks which aren't understood are kept as raw bytes including the
+    /// chunk header.
+    Unknown(Vec<u8>),
 }
 
 impl Chunk {
@@ -881,75 +1348,50 @@ impl Chunk {
             Some(ChunkType::StringPool) => {
                 tracing::trace!("string pool");
                 let string_pool_header = ResStringPoolHeader::read(r)?;
-                let count =
-                    string_pool_header.string_count as i64 + string_pool_header.style_count as i64;
-                r.seek(SeekFrom::Current(count * 4))?;
-                /*let mut string_indices = Vec::with_capacity(string_pool_header.string_count);
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
+                let mut string_indices =
+                    Vec::with_capacity(string_pool_header.string_count as usize);
                 for _ in 0..string_pool_header.string_count {
                     string_indices.push(r.read_u32::<LittleEndian>()?);
                 }
-                let mut style_indices = Vec::with_capacity(string_pool_header.style_count);
+                let mut style_indices = Vec::with_capacity(string_pool_header.style_count as usize);
                 for _ in 0..string_pool_header.style_count {
                     style_indices.push(r.read_u32::<LittleEndian>()?);
-                }*/
-                let mut strings = Vec::with_capacity(string_pool_header.string_count as usize);
-                for _ in 0..string_pool_header.string_count {
+                }
+                let strings_start = start_pos + string_pool_header.strings_start as u64;
+                let mut strings = Vec::with_capacity(string_indices.len());
+                let mut sequential = true;
+                let mut next = 0;
+                for index in &string_indices {
+                    sequential &= *index == next;
+                    r.seek(SeekFrom::Start(strings_start + *index as u64))?;
                     if string_pool_header.is_utf8() {
-                        let charsh = r.read_u8()? as u16;
-                        let _chars = if charsh > 0x7f {
-                            charsh & 0x7f | r.read_u8()? as u16
-                        } else {
-                            charsh
-                        };
-                        let bytesh = r.read_u8()? as u16;
-                        let bytes = if bytesh > 0x7f {
-                            bytesh & 0x7f | r.read_u8()? as u16
-                        } else {
-                            bytesh
-                        };
-                        let mut buf = vec![0; bytes as usize];
-                        r.read_exact(&mut buf)?;
-                        let s = String::from_utf8(buf)?;
-                        strings.push(s);
-                        if r.read_u8()? != 0 {
-                            // fails to read some files otherwise
-                            r.seek(SeekFrom::Start(end_pos))?;
-                        }
+                        strings.push(read_utf8_string(r)?);
                     } else {
-                        let charsh = r.read_u16::<LittleEndian>()? as u32;
-                        let chars = if charsh > 0x7fff {
-                            charsh & 0x7fff | r.read_u16::<LittleEndian>()? as u32
-                        } else {
-                            charsh
-                        };
-                        let mut buf = Vec::with_capacity(chars as usize * 2);
-                        loop {
-                            let code = r.read_u16::<LittleEndian>()?;
-                            if code != 0 {
-                                buf.push(code);
-                            } else {
-                                break;
-                            }
-                        }
-                        let s = String::from_utf16(unsafe { std::mem::transmute(buf.as_slice()) })?;
-                        strings.push(s);
+                        strings.push(read_utf16_string(r)?);
                     }
+                    next = (r.seek(SeekFrom::Current(0))? - strings_start) as u32;
                 }
-                let pos = r.seek(SeekFrom::Current(0))? as i64;
-                if pos % 4 != 0 {
-                    r.seek(SeekFrom::Current(4 - pos % 4))?;
+                if sequential {
+                    string_indices.clear();
                 }
-                let mut styles = Vec::with_capacity(string_pool_header.style_count as usize);
-                for _ in 0..string_pool_header.style_count {
+                let styles_start = start_pos + string_pool_header.styles_start as u64;
+                let mut styles = Vec::with_capacity(style_indices.len());
+                for index in style_indices {
+                    r.seek(SeekFrom::Start(styles_start + index as u64))?;
                     let mut spans = vec![];
                     while let Some(span) = ResSpan::read(r)? {
                         spans.push(span);
                     }
                     styles.push(spans);
                 }
-                // FIXME: skip some unparsable parts
                 r.seek(SeekFrom::Start(end_pos))?;
-                Ok(Chunk::StringPool(strings, styles))
+                Ok(Chunk::StringPool(
+                    string_pool_header.flags,
+                    strings,
+                    styles,
+                    string_indices,
+                ))
             }
             Some(ChunkType::Table) => {
                 tracing::trace!("table");
@@ -1000,6 +1442,12 @@ impl Chunk {
                 let end_element = ResXmlEndElement::read(r)?;
                 Ok(Chunk::XmlEndElement(node_header, end_element))
             }
+            Some(ChunkType::XmlCdata) => {
+                tracing::trace!("xml cdata");
+                let node_header = ResXmlNodeHeader::read(r)?;
+                let cdata = ResXmlCdata::read(r)?;
+                Ok(Chunk::XmlCdata(node_header, cdata))
+            }
             Some(ChunkType::XmlResourceMap) => {
                 tracing::trace!("xml resource map");
                 let mut resource_map =
@@ -1012,6 +1460,7 @@ impl Chunk {
             Some(ChunkType::TablePackage) => {
                 tracing::trace!("table package");
                 let package_header = ResTablePackageHeader::read(r)?;
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
                 let mut chunks = vec![];
                 while r.seek(SeekFrom::Current(0))? < end_pos {
                     chunks.push(Chunk::parse(r)?);
@@ -1021,20 +1470,42 @@ impl Chunk {
             Some(ChunkType::TableType) => {
                 tracing::trace!("table type");
                 let type_header = ResTableTypeHeader::read(r)?;
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
                 let mut index = Vec::with_capacity(type_header.entry_count as usize);
-                for _ in 0..type_header.entry_count {
-                    let entry = r.read_u32::<LittleEndian>()?;
-                    index.push(entry);
+                if type_header.flags & ResTableTypeHeader::FLAG_SPARSE != 0 {
+                    for _ in 0..type_header.entry_count {
+                        let id = r.read_u16::<LittleEndian>()? as usize;
+                        let offset = r.read_u16::<LittleEndian>()? as u32 * 4;
+                        if index.len() <= id {
+                            index.resize(id + 1, NO_ENTRY);
+                        }
+                        index[id] = offset;
+                    }
+                } else if type_header.flags & ResTableTypeHeader::FLAG_OFFSET16 != 0 {
+                    for _ in 0..type_header.entry_count {
+                        let offset = r.read_u16::<LittleEndian>()?;
+                        if offset == 0xffff {
+                            index.push(NO_ENTRY);
+                        } else {
+                            index.push(offset as u32 * 4);
+                        }
+                    }
+                } else {
+                    for _ in 0..type_header.entry_count {
+                        index.push(r.read_u32::<LittleEndian>()?);
+                    }
                 }
-                let mut entries = Vec::with_capacity(type_header.entry_count as usize);
+                let entries_start = start_pos + type_header.entries_start as u64;
+                let mut entries = Vec::with_capacity(index.len());
                 for offset in &index {
-                    if *offset == 0xffff_ffff {
+                    if *offset == NO_ENTRY {
                         entries.push(None);
                     } else {
-                        let entry = ResTableEntry::read(r)?;
-                        entries.push(Some(entry));
+                        r.seek(SeekFrom::Start(entries_start + *offset as u64))?;
+                        entries.push(Some(ResTableEntry::read(r)?));
                     }
                 }
+                r.seek(SeekFrom::Start(end_pos))?;
                 Ok(Chunk::TableType(type_header, index, entries))
             }
             Some(ChunkType::TableTypeSpec) => {
@@ -1046,14 +1517,56 @@ impl Chunk {
                 }
                 Ok(Chunk::TableTypeSpec(type_spec_header, type_spec))
             }
-            Some(ChunkType::Unknown) => {
-                tracing::trace!("unknown");
-                // FIXME: skip some unparsable parts
+            Some(ChunkType::TableLibrary) => {
+                tracing::trace!("table library");
+                let library_header = ResTableLibraryHeader::read(r)?;
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
+                let mut entries = Vec::with_capacity(library_header.count as usize);
+                for _ in 0..library_header.count {
+                    entries.push(ResTableLibraryEntry::read(r)?);
+                }
                 r.seek(SeekFrom::Start(end_pos))?;
-                Ok(Chunk::Unknown)
+                Ok(Chunk::TableLibrary(library_header, entries))
+            }
+            Some(ChunkType::TableOverlayable) => {
+                tracing::trace!("table overlayable");
+                let overlayable_header = ResTableOverlayableHeader::read(r)?;
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
+                let mut chunks = vec![];
+                while r.seek(SeekFrom::Current(0))? < end_pos {
+                    chunks.push(Chunk::parse(r)?);
+                }
+                Ok(Chunk::TableOverlayable(overlayable_header, chunks))
+            }
+            Some(ChunkType::TableOverlayablePolicy) => {
+                tracing::trace!("table overlayable policy");
+                let policy_header = ResTableOverlayablePolicyHeader::read(r)?;
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
+                let mut refs = Vec::with_capacity(policy_header.entry_count as usize);
+                for _ in 0..policy_header.entry_count {
+                    refs.push(ResTableRef::from(r.read_u32::<LittleEndian>()?));
+                }
+                r.seek(SeekFrom::Start(end_pos))?;
+                Ok(Chunk::TableOverlayablePolicy(policy_header, refs))
+            }
+            Some(ChunkType::TableStagedAlias) => {
+                tracing::trace!("table staged alias");
+                let alias_header = ResTableStagedAliasHeader::read(r)?;
+                r.seek(SeekFrom::Start(start_pos + header.header_size as u64))?;
+                let mut entries = Vec::with_capacity(alias_header.count as usize);
+                for _ in 0..alias_header.count {
+                    entries.push(ResTableStagedAliasEntry::read(r)?);
+                }
+                r.seek(SeekFrom::Start(end_pos))?;
+                Ok(Chunk::TableStagedAlias(alias_header, entries))
             }
             None => {
-                anyhow::bail!("unrecognized chunk {:?}", header);
+                tracing::trace!("unknown chunk {:?}", header);
+                anyhow::ensure!(header.size >= 8, "invalid chunk {:?}", header);
+                r.seek(SeekFrom::Start(start_pos))?;
+                let mut chunk = vec![0; header.size as usize];
+                r.read_exact(&mut chunk)?;
+                Ok(Chunk::Unknown(chunk))
             }
         }
     }
@@ -1096,24 +1609,48 @@ impl Chunk {
         }
         match self {
             Chunk::Null => {}
-            Chunk::StringPool(strings, styles) => {
+            Chunk::StringPool(flags, strings, styles, offsets) => {
+                let utf8 = flags & ResStringPoolHeader::UTF8_FLAG != 0;
                 let mut chunk = ChunkWriter::start_chunk(ChunkType::StringPool, w)?;
                 ResStringPoolHeader::default().write(w)?;
                 chunk.end_header(w)?;
-                let indices_count = strings.len() + styles.len();
-                let mut indices = Vec::with_capacity(indices_count);
-                for _ in 0..indices_count {
+                anyhow::ensure!(
+                    offsets.is_empty() || offsets.len() == strings.len(),
+                    "expected an offset for each string"
+                );
+                for _ in 0..strings.len() + styles.len() {
                     w.write_u32::<LittleEndian>(0)?;
                 }
                 let strings_start = w.seek(SeekFrom::Current(0))?;
-                for string in strings {
-                    indices.push(w.seek(SeekFrom::Current(0))? - strings_start);
-                    assert!(string.len() < 0x7f);
-                    let chars = string.chars().count();
-                    w.write_u8(chars as u8)?;
-                    w.write_u8(string.len() as u8)?;
-                    w.write_all(string.as_bytes())?;
-                    w.write_u8(0)?;
+                // strings are written in the order of their original offsets
+                // and strings which shared an offset share it again
+                let mut order = (0..strings.len()).collect::<Vec<_>>();
+                order.sort_by_key(|i| offsets.get(*i));
+                let mut indices = vec![0; strings.len()];
+                let mut prev: Option<usize> = None;
+                for i in order {
+                    if let Some(prev) =
+                        prev.filter(|prev| !offsets.is_empty() && offsets[*prev] == offsets[i])
+                    {
+                        indices[i] = indices[prev];
+                        continue;
+                    }
+                    prev = Some(i);
+                    let string = &strings[i];
+                    indices[i] = w.seek(SeekFrom::Current(0))? - strings_start;
+                    if utf8 {
+                        write_utf8_len(w, string.encode_utf16().count())?;
+                        write_utf8_len(w, string.len())?;
+                        w.write_all(string.as_bytes())?;
+                        w.write_u8(0)?;
+                    } else {
+                        let units = string.encode_utf16().collect::<Vec<_>>();
+                        write_utf16_len(w, units.len())?;
+                        for unit in units {
+                            w.write_u16::<LittleEndian>(unit)?;
+                        }
+                        w.write_u16::<LittleEndian>(0)?;
+                    }
                 }
                 while w.seek(SeekFrom::Current(0))? % 4 != 0 {
                     w.write_u8(0)?;
@@ -1126,15 +1663,25 @@ impl Chunk {
                     }
                     w.write_i32::<LittleEndian>(-1)?;
                 }
+                if !styles.is_empty() {
+                    // the platform expects a whole span of end markers after
+                    // the last style
+                    w.write_i32::<LittleEndian>(-1)?;
+                    w.write_i32::<LittleEndian>(-1)?;
+                }
                 let (start_chunk, end_chunk) = chunk.end_chunk(w)?;
 
                 w.seek(SeekFrom::Start(start_chunk + 8))?;
                 ResStringPoolHeader {
                     string_count: strings.len() as u32,
                     style_count: styles.len() as u32,
-                    flags: ResStringPoolHeader::UTF8_FLAG,
+                    flags: *flags,
                     strings_start: (strings_start - start_chunk) as u32,
-                    styles_start: (styles_start - start_chunk) as u32,
+                    styles_start: if styles.is_empty() {
+                        0
+                    } else {
+                        (styles_start - start_chunk) as u32
+                    },
                 }
                 .write(w)?;
                 for index in indices {
@@ -1190,6 +1737,13 @@ impl Chunk {
                 end_element.write(w)?;
                 chunk.end_chunk(w)?;
             }
+            Chunk::XmlCdata(node_header, cdata) => {
+                let mut chunk = ChunkWriter::start_chunk(ChunkType::XmlCdata, w)?;
+                node_header.write(w)?;
+                chunk.end_header(w)?;
+                cdata.write(w)?;
+                chunk.end_chunk(w)?;
+            }
             Chunk::XmlResourceMap(resource_map) => {
                 let mut chunk = ChunkWriter::start_chunk(ChunkType::XmlResourceMap, w)?;
                 chunk.end_header(w)?;
@@ -1225,16 +1779,76 @@ impl Chunk {
                 w.seek(SeekFrom::Start(end))?;
             }
             Chunk::TableType(type_header, index, entries) => {
+                anyhow::ensure!(
+                    index.len() == entries.len(),
+                    "table type index doesn't match the entries"
+                );
+                let sparse = type_header.flags & ResTableTypeHeader::FLAG_SPARSE != 0;
+                let offset16 = type_header.flags & ResTableTypeHeader::FLAG_OFFSET16 != 0;
                 let mut chunk = ChunkWriter::start_chunk(ChunkType::TableType, w)?;
+                let header_start = w.seek(SeekFrom::Current(0))?;
                 type_header.write(w)?;
                 chunk.end_header(w)?;
-                for offset in index {
-                    w.write_u32::<LittleEndian>(*offset)?;
+                let mut entry_count = 0;
+                for (id, (offset, entry)) in index.iter().zip(entries).enumerate() {
+                    anyhow::ensure!(
+                        (*offset == NO_ENTRY) == entry.is_none(),
+                        "table type index doesn't match the entries"
+                    );
+                    if !sparse && !offset16 {
+                        w.write_u32::<LittleEndian>(*offset)?;
+                        entry_count += 1;
+                        continue;
+                    }
+                    if *offset == NO_ENTRY {
+                        if offset16 && !sparse {
+                            w.write_u16::<LittleEndian>(0xffff)?;
+                            entry_count += 1;
+                        }
+                        continue;
+                    }
+                    anyhow::ensure!(
+                        offset % 4 == 0 && offset / 4 < 0xffff && id <= 0xffff,
+                        "entry {} can't be indexed with u16 offsets",
+                        id
+                    );
+                    if sparse {
+                        w.write_u16::<LittleEndian>(id as u16)?;
+                    }
+                    w.write_u16::<LittleEndian>((offset / 4) as u16)?;
+                    entry_count += 1;
+                }
+                while w.seek(SeekFrom::Current(0))? % 4 != 0 {
+                    w.write_u8(0)?;
                 }
-                for entry in entries.iter().flatten() {
+                // entries are written at their offset, entries may share an offset
+                let entries_start = w.seek(SeekFrom::Current(0))?;
+                let mut offsets = index
+                    .iter()
+                    .zip(entries)
+                    .filter_map(|(offset, entry)| Some((*offset, entry.as_ref()?)))
+                    .collect::<Vec<_>>();
+                offsets.sort_by_key(|(offset, _)| *offset);
+                offsets.dedup_by_key(|(offset, _)| *offset);
+                for (offset, entry) in offsets {
+                    let pos = w.seek(SeekFrom::Current(0))?;
+                    let offset = entries_start + offset as u64;
+                    anyhow::ensure!(pos <= offset, "overlapping table type entries");
+                    for _ in pos..offset {
+                        w.write_u8(0)?;
+                    }
                     entry.write(w)?;
                 }
-                chunk.end_chunk(w)?;
+                let (start_chunk, end_chunk) = chunk.end_chunk(w)?;
+
+                w.seek(SeekFrom::Start(header_start))?;
+                ResTableTypeHeader {
+                    entry_count,
+                    entries_start: (entries_start - start_chunk) as u32,
+                    ..type_header.clone()
+                }
+                .write(w)?;
+                w.seek(SeekFrom::Start(end_chunk))?;
             }
             Chunk::TableTypeSpec(type_spec_header, type_spec) => {
                 let mut chunk = ChunkWriter::start_chunk(ChunkType::TableTypeSpec, w)?;
@@ -1245,12 +1859,377 @@ impl Chunk {
                 }
                 chunk.end_chunk(w)?;
             }
-            Chunk::Unknown => {}
+            Chunk::TableLibrary(library_header, entries) => {
+                let mut chunk = ChunkWriter::start_chunk(ChunkType::TableLibrary, w)?;
+                library_header.write(w)?;
+                chunk.end_header(w)?;
+                for entry in entries {
+                    entry.write(w)?;
+                }
+                chunk.end_chunk(w)?;
+            }
+            Chunk::TableOverlayable(overlayable_header, chunks) => {
+                let mut chunk = ChunkWriter::start_chunk(ChunkType::TableOverlayable, w)?;
+                overlayable_header.write(w)?;
+                chunk.end_header(w)?;
+                for chunk in chunks {
+                    chunk.write(w)?;
+                }
+                chunk.end_chunk(w)?;
+            }
+            Chunk::TableOverlayablePolicy(policy_header, refs) => {
+                let mut chunk = ChunkWriter::start_chunk(ChunkType::TableOverlayablePolicy, w)?;
+                policy_header.write(w)?;
+                chunk.end_header(w)?;
+                for r in refs {
+                    w.write_u32::<LittleEndian>(u32::from(*r))?;
+                }
+                chunk.end_chunk(w)?;
+            }
+            Chunk::TableStagedAlias(alias_header, entries) => {
+                let mut chunk = ChunkWriter::start_chunk(ChunkType::TableStagedAlias, w)?;
+                alias_header.write(w)?;
+                chunk.end_header(w)?;
+                for entry in entries {
+                    entry.write(w)?;
+                }
+                chunk.end_chunk(w)?;
+            }
+            Chunk::Unknown(chunk) => {
+                w.write_all(chunk)?;
+            }
         }
         Ok(())
     }
 }
 
+/// Decompiles a `Chunk::Xml` to readable xml. Attribute ids are resolved to
+/// their names using `table`, typed values like references, dimensions and
+/// enums are rendered the way they are written in source xml.
+pub fn decompile_xml(chunk: &Chunk, table: &Table) -> Result<String> {
+    let chunks = if let Chunk::Xml(chunks) = chunk {
+        chunks
+    } else {
+        anyhow::bail!("expected xml chunk");
+    };
+    let mut strings: &[String] = &[];
+    let mut resource_map: &[u32] = &[];
+    for chunk in chunks {
+        match chunk {
+            Chunk::StringPool(_, s, _, _) => strings = s,
+            Chunk::XmlResourceMap(map) => resource_map = map,
+            _ => {}
+        }
+    }
+    let string = |id: i32| -> Result<&str> {
+        strings
+            .get(id as usize)
+            .map(|s| s.as_str())
+            .ok_or_else(|| anyhow::anyhow!("invalid string id {}", id))
+    };
+    let mut prefixes: Vec<(&str, &str)> = vec![];
+    let prefixed = |prefixes: &[(&str, &str)], ns: i32, name: &str| -> Result<String> {
+        if ns == -1 {
+            return Ok(name.to_string());
+        }
+        let uri = string(ns)?;
+        Ok(match prefixes.iter().rev().find(|(_, u)| *u == uri) {
+            Some((prefix, _)) => format!("{}:{}", prefix, name),
+            None => name.to_string(),
+        })
+    };
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    let mut pending_ns = vec![];
+    let mut depth = 0;
+    let mut open = false;
+    for chunk in chunks {
+        match chunk {
+            Chunk::XmlStartNamespace(_, ns) => {
+                let prefix = if ns.prefix == -1 {
+                    ""
+                } else {
+                    string(ns.prefix)?
+                };
+                let uri = string(ns.uri)?;
+                prefixes.push((prefix, uri));
+                pending_ns.push((prefix, uri));
+            }
+            Chunk::XmlEndNamespace(_, _) => {
+                prefixes.pop();
+            }
+            Chunk::XmlStartElement(_, el, attrs) => {
+                if open {
+                    xml.push_str(">\n");
+                }
+                let name = prefixed(&prefixes, el.namespace, string(el.name)?)?;
+                xml.push_str(&"    ".repeat(depth));
+                xml.push('<');
+                xml.push_str(&name);
+                for (prefix, uri) in pending_ns.drain(..) {
+                    if prefix.is_empty() {
+                        xml.push_str(&format!(" xmlns=\"{}\"", escape(uri)));
+                    } else {
+                        xml.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape(uri)));
+                    }
+                }
+                for attr in attrs {
+                    let id = resource_map.get(attr.name as usize).copied();
+                    let entry = id.and_then(|id| table.entry(ResTableRef::from(id)).ok());
+                    let name = match id.and_then(|id| table.entry_name(ResTableRef::from(id)).ok())
+                    {
+                        Some((_, _, name)) => name,
+                        None => string(attr.name)?,
+                    };
+                    let name = prefixed(&prefixes, attr.namespace, name)?;
+                    let value = if attr.raw_value != -1 {
+                        string(attr.raw_value)?.to_string()
+                    } else {
+                        format_value(&attr.typed_value, entry, strings, table)
+                    };
+                    xml.push_str(&format!(" {}=\"{}\"", name, escape(&value)));
+                }
+                depth += 1;
+                open = true;
+            }
+            Chunk::XmlCdata(_, cdata) => {
+                anyhow::ensure!(depth > 0, "text outside of the root element");
+                if open {
+                    xml.push_str(">\n");
+                    open = false;
+                }
+                xml.push_str(&"    ".repeat(depth));
+                xml.push_str(&escape(string(cdata.data)?));
+                xml.push('\n');
+            }
+            Chunk::XmlEndElement(_, el) => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| anyhow::anyhow!("unbalanced xml end element"))?;
+                if open {
+                    xml.push_str(" />\n");
+                } else {
+                    let name = prefixed(&prefixes, el.namespace, string(el.name)?)?;
+                    xml.push_str(&"    ".repeat(depth));
+                    xml.push_str(&format!("</{}>\n", name));
+                }
+                open = false;
+            }
+            _ => {}
+        }
+    }
+    Ok(xml)
+}
+
+/// Formats a typed value, `attr` is used to look up enum and flag names.
+fn format_value(
+    value: &ResValue,
+    attr: Option<Entry>,
+    strings: &[String],
+    table: &Table,
+) -> String {
+    let reference = |prefix: char| {
+        if value.data == 0 {
+            return "@null".to_string();
+        }
+        let id = ResTableRef::from(value.data);
+        match table.entry_name(id) {
+            // app resources are referenced without a package
+            Ok((_, ty, name)) if id.package() == 0x7f => format!("{}{}/{}", prefix, ty, name),
+            Ok((package, ty, name)) => format!("{}{}:{}/{}", prefix, package, ty, name),
+            Err(_) => format!("{}0x{:08x}", prefix, value.data),
+        }
+    };
+    let ty = if let Some(ty) = ResValueType::from_u8(value.data_type) {
+        ty
+    } else {
+        return format!("0x{:08x}", value.data);
+    };
+    match ty {
+        ResValueType::Null if value.data == 1 => "@empty".into(),
+        ResValueType::Null => "@null".into(),
+        ResValueType::Reference => reference('@'),
+        ResValueType::Attribute => reference('?'),
+        ResValueType::String => strings
+            .get(value.data as usize)
+            .cloned()
+            .unwrap_or_default(),
+        ResValueType::Float => f32::from_bits(value.data).to_string(),
+        ResValueType::Dimension | ResValueType::Fraction => format_complex(ty, value.data),
+        ResValueType::IntDec | ResValueType::IntHex => {
+            if let Some(symbols) = attr.and_then(|attr| format_symbols(attr, value.data, table)) {
+                symbols
+            } else if ty == ResValueType::IntHex {
+                format!("0x{:x}", value.data)
+            } else {
+                (value.data as i32).to_string()
+            }
+        }
+        ResValueType::IntBoolean => (value.data != 0).to_string(),
+        ResValueType::IntColorArgb8 | ResValueType::IntColorArgb4 => {
+            format!("#{:08x}", value.data)
+        }
+        ResValueType::IntColorRgb8 | ResValueType::IntColorRgb4 => {
+            format!("#{:06x}", value.data & 0xff_ffff)
+        }
+    }
+}
+
+/// Formats the value of an enum attribute as its symbol and the value of a
+/// flags attribute as the symbols combined with `|`.
+fn format_symbols(attr: Entry, data: u32, table: &Table) -> Option<String> {
+    let format = attr.attribute_format()?;
+    let symbols = attr
+        .attribute_symbols()
+        .filter_map(|(id, value)| Some((table.entry_name(id).ok()?.2, value.data)))
+        .collect::<Vec<_>>();
+    if format & ResAttributeType::Enum as u32 != 0 {
+        if let Some((name, _)) = symbols.iter().find(|(_, value)| *value == data) {
+            return Some(name.to_string());
+        }
+    }
+    if format & ResAttributeType::Flags as u32 != 0 {
+        if let Some((name, _)) = symbols.iter().find(|(_, value)| *value == data) {
+            return Some(name.to_string());
+        }
+        let mut flags = vec![];
+        let mut covered = 0;
+        for (name, value) in &symbols {
+            if *value != 0 && data & value == *value && covered & value != *value {
+                flags.push(*name);
+                covered |= value;
+            }
+        }
+        if covered == data && !flags.is_empty() {
+            return Some(flags.join("|"));
+        }
+    }
+    None
+}
+
+/// Formats a dimension like `16dp` or a fraction like `50%p`.
+fn format_complex(ty: ResValueType, data: u32) -> String {
+    // the mantissa is a signed 24 bit integer with a radix of 23p0, 16p7,
+    // 8p15 or 0p23.
+    let mantissa = (data as i32 >> 8) as f32;
+    let shift = [0, 7, 15, 23][(data >> 4 & 0x3) as usize];
+    let value = mantissa / (1u32 << shift) as f32;
+    let unit = data & 0xf;
+    if ty == ResValueType::Fraction {
+        let unit = if unit == 1 { "%p" } else { "%" };
+        format!("{}{}", value * 100.0, unit)
+    } else {
+        let unit = ["px", "dp", "sp", "pt", "in", "mm"]
+            .get(unit as usize)
+            .copied()
+            .unwrap_or_default();
+        format!("{}{}", value, unit)
+    }
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Reads a string length of a utf8 string pool.
+fn read_utf8_len(r: &mut impl Read) -> Result<usize> {
+    let len = r.read_u8()? as usize;
+    if len > 0x7f {
+        Ok((len & 0x7f) << 8 | r.read_u8()? as usize)
+    } else {
+        Ok(len)
+    }
+}
+
+/// Writes a string length of a utf8 string pool, lengths above 0x7f take two
+/// bytes. Longer lengths than 0x7fff can't be encoded, they are truncated and
+/// readers find the end of the string at the nul byte like the platform does.
+fn write_utf8_len(w: &mut impl Write, len: usize) -> Result<()> {
+    let len = len.min(0x7fff);
+    if len > 0x7f {
+        w.write_u8((len >> 8) as u8 | 0x80)?;
+    }
+    w.write_u8(len as u8)?;
+    Ok(())
+}
+
+fn read_utf8_string(r: &mut impl Read) -> Result<String> {
+    let _chars = read_utf8_len(r)?;
+    let bytes = read_utf8_len(r)?;
+    let mut buf = vec![0; bytes];
+    r.read_exact(&mut buf)?;
+    // some tools write truncated lengths for long strings, the string ends
+    // at the nul byte either way
+    loop {
+        match r.read_u8()? {
+            0 => break,
+            b => buf.push(b),
+        }
+    }
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Reads a string length of a utf16 string pool.
+fn read_utf16_len(r: &mut impl Read) -> Result<usize> {
+    let len = r.read_u16::<LittleEndian>()? as usize;
+    if len > 0x7fff {
+        Ok((len & 0x7fff) << 16 | r.read_u16::<LittleEndian>()? as usize)
+    } else {
+        Ok(len)
+    }
+}
+
+/// Writes a string length of a utf16 string pool, lengths above 0x7fff take
+/// two u16.
+fn write_utf16_len(w: &mut impl Write, len: usize) -> Result<()> {
+    anyhow::ensure!(len <= 0x7fff_ffff, "string too long");
+    if len > 0x7fff {
+        w.write_u16::<LittleEndian>((len >> 16) as u16 | 0x8000)?;
+    }
+    w.write_u16::<LittleEndian>(len as u16)?;
+    Ok(())
+}
+
+fn read_utf16_string(r: &mut impl Read) -> Result<String> {
+    let len = read_utf16_len(r)?;
+    let mut buf = Vec::with_capacity(len);
+    loop {
+        match r.read_u16::<LittleEndian>()? {
+            0 => break,
+            c => buf.push(c),
+        }
+    }
+    Ok(String::from_utf16(&buf)?)
+}
+
+/// Reads a nul terminated string from a fixed size utf16 array of `len` code units.
+fn read_fixed_utf16(r: &mut impl Read, len: usize) -> Result<String> {
+    let mut buf = Vec::with_capacity(len);
+    for _ in 0..len {
+        buf.push(r.read_u16::<LittleEndian>()?);
+    }
+    let end = buf.iter().position(|c| *c == 0).unwrap_or(len);
+    Ok(String::from_utf16(&buf[..end])?)
+}
+
+/// Writes `s` to a fixed size utf16 array of `len` code units padded with nul.
+fn write_fixed_utf16(w: &mut impl Write, s: &str, len: usize) -> Result<()> {
+    let mut buf = s.encode_utf16().collect::<Vec<_>>();
+    anyhow::ensure!(
+        buf.len() < len,
+        "{} is longer than {} code units",
+        s,
+        len - 1
+    );
+    buf.resize(len, 0);
+    for c in buf {
+        w.write_u16::<LittleEndian>(c)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1259,6 +2238,250 @@ mod tests {
     use std::path::Path;
     use zip::ZipArchive;
 
+    /// Writes a chunk, checks that it parses to the same chunk and that
+    /// writing the parsed chunk produces the same bytes.
+    fn round_trip(chunk: &Chunk) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        chunk.write(&mut Cursor::new(&mut buf))?;
+        let parsed = Chunk::parse(&mut Cursor::new(&buf))?;
+        assert_eq!(&parsed, chunk);
+        let mut buf2 = vec![];
+        parsed.write(&mut Cursor::new(&mut buf2))?;
+        assert_eq!(buf, buf2);
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_string_pool() -> Result<()> {
+        let strings = vec!["a".repeat(300), "héllo".into(), "b".repeat(40000)];
+        let styles = vec![
+            vec![],
+            vec![ResSpan {
+                name: 0,
+                first_char: 1,
+                last_char: 3,
+            }],
+        ];
+        for flags in [0, ResStringPoolHeader::UTF8_FLAG] {
+            round_trip(&Chunk::StringPool(
+                flags,
+                strings[..2].to_vec(),
+                vec![],
+                vec![],
+            ))?;
+            round_trip(&Chunk::StringPool(
+                flags,
+                strings[..2].to_vec(),
+                styles.clone(),
+                vec![],
+            ))?;
+            round_trip(&Chunk::StringPool(flags, strings.clone(), vec![], vec![]))?;
+        }
+        round_trip(&Chunk::StringPool(0, strings, styles, vec![]))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_pool_shared_offsets() -> Result<()> {
+        #[rustfmt::skip]
+        let pool = [
+            0x01, 0x00, 0x1c, 0x00, 0x30, 0x00, 0x00, 0x00,
+            // string count, style count, flags, strings start, styles start
+            0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+            // the first and the last string share their data
+            0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x04, 0x00, 0x00, 0x00,
+            0x01, 0x01, b'b', 0x00, 0x01, 0x01, b'a', 0x00,
+        ];
+        let chunk = Chunk::parse(&mut Cursor::new(&pool))?;
+        assert_eq!(
+            chunk,
+            Chunk::StringPool(
+                ResStringPoolHeader::UTF8_FLAG,
+                vec!["a".into(), "b".into(), "a".into()],
+                vec![],
+                vec![4, 0, 4],
+            )
+        );
+        assert_eq!(round_trip(&chunk)?, pool);
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_type() -> Result<()> {
+        let entry = |key: u32, data: u32| ResTableEntry {
+            size: 8,
+            flags: 0,
+            key,
+            value: ResTableValue::Simple(ResValue {
+                size: 8,
+                res0: 0,
+                data_type: ResValueType::IntDec as u8,
+                data,
+            }),
+        };
+        let compact = ResTableEntry {
+            flags: ResTableEntry::FLAG_COMPACT,
+            ..entry(2, 42)
+        };
+        let entries = vec![
+            Some(entry(0, 1)),
+            None,
+            Some(compact),
+            Some(entry(0, 1)),
+            None,
+        ];
+        // the fourth entry shares the value of the first one
+        let index = vec![0, NO_ENTRY, 16, 0, NO_ENTRY];
+        for flags in [
+            0,
+            ResTableTypeHeader::FLAG_SPARSE,
+            ResTableTypeHeader::FLAG_OFFSET16,
+        ] {
+            let header = ResTableTypeHeader {
+                id: 1,
+                flags,
+                res1: 0,
+                entry_count: 0,
+                entries_start: 0,
+                config: Default::default(),
+            };
+            let chunk = Chunk::TableType(header.clone(), index.clone(), entries.clone());
+            let mut buf = vec![];
+            chunk.write(&mut Cursor::new(&mut buf))?;
+            let parsed = Chunk::parse(&mut Cursor::new(&buf))?;
+            let (header, index2, entries2) = match &parsed {
+                Chunk::TableType(header, index, entries) => (header, index, entries),
+                _ => unreachable!(),
+            };
+            // sparse types don't store trailing missing entries
+            let len = if flags == ResTableTypeHeader::FLAG_SPARSE {
+                4
+            } else {
+                5
+            };
+            assert_eq!(index2[..], index[..len]);
+            assert_eq!(entries2[..], entries[..len]);
+            assert_eq!(header.entry_count, if len == 4 { 3 } else { 5 });
+            round_trip(&parsed)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_chunks() -> Result<()> {
+        round_trip(&Chunk::TableLibrary(
+            ResTableLibraryHeader { count: 1 },
+            vec![ResTableLibraryEntry {
+                package_id: 2,
+                package_name: "com.example.library".into(),
+            }],
+        ))?;
+        round_trip(&Chunk::TableOverlayable(
+            ResTableOverlayableHeader {
+                name: "Theme".into(),
+                actor: "overlay://theme".into(),
+            },
+            vec![Chunk::TableOverlayablePolicy(
+                ResTableOverlayablePolicyHeader {
+                    policy_flags: 1,
+                    entry_count: 2,
+                },
+                vec![0x7f01_0000.into(), 0x7f01_0001.into()],
+            )],
+        ))?;
+        round_trip(&Chunk::TableStagedAlias(
+            ResTableStagedAliasHeader { count: 1 },
+            vec![ResTableStagedAliasEntry {
+                staged_res_id: 0x0101_ffff,
+                finalized_res_id: 0x0101_0660,
+            }],
+        ))?;
+        let unknown = [
+            0x08, 0x02, 0x0c, 0x00, 0x10, 0x00, 0x00, 0x00, 1, 2, 3, 4, 5, 6, 7, 8,
+        ];
+        let chunk = Chunk::parse(&mut Cursor::new(&unknown))?;
+        assert_eq!(chunk, Chunk::Unknown(unknown.to_vec()));
+        assert_eq!(round_trip(&chunk)?, unknown);
+        Ok(())
+    }
+
+    fn xml_chunk(body: Vec<Chunk>) -> Chunk {
+        let mut chunks = vec![Chunk::StringPool(
+            ResStringPoolHeader::UTF8_FLAG,
+            vec!["text".into(), "hello & world".into()],
+            vec![],
+            vec![],
+        )];
+        chunks.extend(body);
+        Chunk::Xml(chunks)
+    }
+
+    #[test]
+    fn test_decompile_xml_cdata() -> Result<()> {
+        let node = ResXmlNodeHeader {
+            line_number: 1,
+            comment: -1,
+        };
+        let chunk = xml_chunk(vec![
+            Chunk::XmlStartElement(
+                node,
+                ResXmlStartElement {
+                    name: 0,
+                    ..Default::default()
+                },
+                vec![],
+            ),
+            Chunk::XmlCdata(
+                node,
+                ResXmlCdata {
+                    data: 1,
+                    typed_data: ResValue {
+                        size: 8,
+                        res0: 0,
+                        data_type: 0,
+                        data: 0,
+                    },
+                },
+            ),
+            Chunk::XmlEndElement(
+                node,
+                ResXmlEndElement {
+                    namespace: -1,
+                    name: 0,
+                },
+            ),
+        ]);
+        let mut buf = vec![];
+        chunk.write(&mut Cursor::new(&mut buf))?;
+        assert_eq!(Chunk::parse(&mut Cursor::new(&buf))?, chunk);
+        let xml = decompile_xml(&chunk, &Table::default())?;
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<text>\n    hello &amp; world\n</text>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompile_xml_unbalanced() {
+        let node = ResXmlNodeHeader {
+            line_number: 1,
+            comment: -1,
+        };
+        let end = Chunk::XmlEndElement(
+            node,
+            ResXmlEndElement {
+                namespace: -1,
+                name: 0,
+            },
+        );
+        let chunk = xml_chunk(vec![end]);
+        assert!(decompile_xml(&chunk, &Table::default()).is_err());
+    }
+
     #[test]
     fn test_parse_android_resources() -> Result<()> {
         crate::tests::init_logger();
@@ -1276,7 +2499,10 @@ mod tests {
             f.read_to_end(&mut buf)?;
             let mut cursor = Cursor::new(&buf);
             tracing::info!("parsing {}", android.display());
-            Chunk::parse(&mut cursor)?;
+            let chunk = Chunk::parse(&mut cursor)?;
+            let mut written = vec![];
+            chunk.write(&mut Cursor::new(&mut written))?;
+            assert!(written == buf, "{} doesn't round trip", android.display());
         }
         Ok(())
     }
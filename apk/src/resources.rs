@@ -0,0 +1,161 @@
+//! Pipeline compiling the resources of an app into a resource table with
+//! package id 0x7f.
+use crate::aar::Aar;
+use crate::compiler::{FileContents, Resources, Symbols, Table};
+use crate::icon::AdaptiveIcon;
+use crate::res::Chunk;
+use anyhow::Result;
+use std::path::Path;
+
+/// Name of the string the label of the app is stored as.
+const LABEL: &str = "app_name";
+
+/// Resources of an app, which are always compiled into a resource table even
+/// if no resources are added, so the manifest can reference them.
+///
+/// Ids are assigned in sorted order, so the same resources always get the
+/// same ids regardless of the order they are added in, and ids of a previous
+/// build are kept with [`AppResources::set_stable_ids`]. Resources declared
+/// by the app take precedence over the ones of its libraries, which have to
+/// be added last.
+pub struct AppResources {
+    resources: Resources,
+    pngs: Vec<(String, Vec<u8>)>,
+}
+
+impl AppResources {
+    pub fn new(package: &str) -> Self {
+        Self {
+            resources: Resources::new(package),
+            pngs: vec![],
+        }
+    }
+
+    /// Keeps the resource ids of a previous build, so adding a resource
+    /// doesn't change the ids of the existing ones.
+    pub fn set_stable_ids(&mut self, ids: Symbols) {
+        self.resources.set_stable_ids(ids);
+    }
+
+    /// Adds the label of the app as `@string/app_name` and returns the
+    /// reference to use in the manifest. Labels which already are a
+    /// reference are returned as is.
+    pub fn add_label(&mut self, label: &str) -> Result<String> {
+        if label.starts_with('@') {
+            return Ok(label.to_string());
+        }
+        anyhow::ensure!(
+            !self.resources.contains("string", LABEL),
+            "string/{0} is declared by the app, use `@string/{0}` as label",
+            LABEL
+        );
+        self.resources.add_string(LABEL, label)?;
+        Ok(format!("@string/{}", LABEL))
+    }
+
+    /// Adds the `icon` mipmap and returns the reference to use in the
+    /// manifest, `None` if neither icon is set. The pngs of the mipmap are
+    /// returned by [`AppResources::pngs`].
+    pub fn add_icon(
+        &mut self,
+        icon: Option<&Path>,
+        adaptive_icon: Option<&AdaptiveIcon>,
+    ) -> Result<Option<String>> {
+        let pngs = crate::icon::add_icon(&mut self.resources, icon, adaptive_icon)?;
+        if pngs.is_empty() {
+            return Ok(None);
+        }
+        self.pngs.extend(pngs);
+        Ok(Some("@mipmap/icon".into()))
+    }
+
+    /// Adds the resources of an android `res` directory.
+    pub fn add_res_dir(&mut self, dir: &Path) -> Result<()> {
+        self.resources.add_res_dir(dir)
+    }
+
+    /// Adds the contents of a `res/values` file like
+    /// `<resources><color name="background">#ffffff</color></resources>`.
+    pub fn add_values(&mut self, xml: &str) -> Result<()> {
+        self.resources.add_values(Default::default(), xml)
+    }
+
+    /// Adds an xml resource like `res/xml/file_paths.xml`.
+    pub fn add_xml(&mut self, path: &str, xml: &str) -> Result<()> {
+        self.resources.add_xml(path, xml.to_string())
+    }
+
+    /// Adds the resources of an android library.
+    pub fn add_library(&mut self, aar: &Aar) -> Result<()> {
+        if let Some(res) = aar.res() {
+            self.resources.add_library_res_dir(&res)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the ids of the resources. Framework attributes referenced by
+    /// styleables are resolved using `table`.
+    pub fn symbols(&self, table: &Table) -> Result<Symbols> {
+        self.resources.symbols(table)
+    }
+
+    /// Compiles the resource table and imports it into `table`, which has to
+    /// contain the framework resources. The manifest and xml files compiled
+    /// afterwards can reference the resources of the app.
+    pub fn compile(&self, table: &mut Table) -> Result<Chunk> {
+        let chunk = self.resources.compile(table)?;
+        table.import_chunk(&chunk);
+        Ok(chunk)
+    }
+
+    /// Returns the file resources keyed by their path in the package. Xml
+    /// files need to be compiled once the table is known.
+    pub fn files(&self) -> &[(String, FileContents)] {
+        self.resources.files()
+    }
+
+    /// Returns the generated pngs of the icon keyed by their path in the
+    /// package.
+    pub fn pngs(&self) -> &[(String, Vec<u8>)] {
+        &self.pngs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label() -> Result<()> {
+        let mut resources = AppResources::new("com.example.helloworld");
+        assert_eq!(
+            resources.add_label(r#"Hello \ "world""#)?,
+            "@string/app_name"
+        );
+        assert_eq!(resources.add_label("@string/title")?, "@string/title");
+        let mut table = Table::default();
+        let chunk = resources.compile(&mut table)?;
+        let strings = if let Chunk::Table(_, chunks) = &chunk {
+            match chunks.first() {
+                Some(Chunk::StringPool(_, strings, _, _)) => strings.clone(),
+                _ => vec![],
+            }
+        } else {
+            vec![]
+        };
+        assert_eq!(strings, [r#"Hello \ "world""#]);
+        assert!(resources
+            .symbols(&table)?
+            .to_java("com.example.helloworld")
+            .contains("public static final int app_name = 0x7f010000;"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty() -> Result<()> {
+        let resources = AppResources::new("com.example.helloworld");
+        let chunk = resources.compile(&mut Table::default())?;
+        assert!(matches!(chunk, Chunk::Table(_, _)));
+        Ok(())
+    }
+}
@@ -1,28 +1,54 @@
 use anyhow::Result;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use rasn_pkix::Certificate;
-use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
-use rsa::{Hash, PaddingScheme, PublicKey, RsaPublicKey};
-use sha2::{Digest as _, Sha256};
+use sha2::{Sha256, Sha512};
 use std::fs::File;
 use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-use xcommon::{Signer, ZipInfo};
+use xcommon::{SignatureAlgorithm, Signer, ZipInfo};
 
-const DEBUG_PEM: &str = include_str!("../assets/debug.pem");
+pub(crate) const DEBUG_PEM: &str = include_str!("../assets/debug.pem");
 
 const APK_SIGNING_BLOCK_MAGIC: &[u8] = b"APK Sig Block 42";
 const APK_SIGNING_BLOCK_V2_ID: u32 = 0x7109871a;
 const APK_SIGNING_BLOCK_V3_ID: u32 = 0xf05368c0;
 const APK_SIGNING_BLOCK_V4_ID: u32 = 0x42726577;
 const RSA_PKCS1V15_SHA2_256: u32 = 0x0103;
+const ECDSA_SHA2_256: u32 = 0x0201;
+const ECDSA_SHA2_512: u32 = 0x0202;
 const MAX_CHUNK_SIZE: usize = 1024 * 1024;
 
-pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
+const PROOF_OF_ROTATION_ATTR_ID: u32 = 0x3ba06f8c;
+const STRIPPING_PROTECTION_ATTR_ID: u32 = 0xbeeff00d;
+const PROOF_OF_ROTATION_VERSION: u32 = 1;
+/// Capabilities granted to previous certificates in the lineage, the same
+/// as apksigner grants by default: installed data (1), shared user id (2),
+/// permission (4) and auth (16). The rollback capability (8) isn't granted.
+const DEFAULT_LINEAGE_FLAGS: u32 = 0b10111;
+/// v3 signatures are only verified on android 9 (P) or later.
+const V3_MIN_SDK: u32 = 28;
+const V3_MAX_SDK: u32 = i32::MAX as u32;
+
+/// Certificates found in the signing blocks of an apk.
+#[derive(Clone, Debug, Default)]
+pub struct Verified {
+    /// Certificates of the v1 (jar) signers.
+    pub v1: Vec<Certificate>,
+    /// Certificates of the v2 signers.
+    pub v2: Vec<Certificate>,
+    /// Certificates of the v3 signers.
+    pub v3: Vec<Certificate>,
+    /// Proof-of-rotation lineage of the v3 signer, oldest certificate first.
+    pub lineage: Vec<Certificate>,
+}
+
+pub fn verify(path: &Path) -> Result<Verified> {
+    let v1 = crate::jar::verify(path)?;
     let f = File::open(path)?;
     let mut r = BufReader::new(f);
     let sblock = parse_apk_signing_block(&mut r)?;
     let mut sblockv2 = None;
+    let mut sblockv3 = None;
     for block in &sblock.blocks {
         match block.id {
             APK_SIGNING_BLOCK_V2_ID => {
@@ -31,6 +57,7 @@ pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
             }
             APK_SIGNING_BLOCK_V3_ID => {
                 tracing::debug!("v3 signing block");
+                sblockv3 = Some(*block);
             }
             APK_SIGNING_BLOCK_V4_ID => {
                 tracing::debug!("v4 signing block");
@@ -40,68 +67,168 @@ pub fn verify(path: &Path) -> Result<Vec<Certificate>> {
             }
         }
     }
-    let block = if let Some(block) = sblockv2 {
+    anyhow::ensure!(
+        sblockv2.is_some() || sblockv3.is_some(),
+        "no signing block v2 or v3 found"
+    );
+    let v2 = if let Some(block) = sblockv2 {
         r.seek(SeekFrom::Start(block.start))?;
-        ApkSignatureBlockV2::read(&mut r)?
+        Some(ApkSignatureBlockV2::read(&mut r)?)
     } else {
-        anyhow::bail!("no signing block v2 found");
+        None
     };
-    let zip_hash = compute_digest(&mut r, sblock.sb_start, sblock.cd_start, sblock.cde_start)?;
-    let mut certificates = vec![];
-    for signer in &block.signers {
-        anyhow::ensure!(
-            !signer.signatures.is_empty(),
-            "found no signatures in v2 block"
-        );
-        for sig in &signer.signatures {
-            anyhow::ensure!(
-                sig.algorithm == RSA_PKCS1V15_SHA2_256,
-                "found unsupported signature algorithm 0x{:x}",
-                sig.algorithm
-            );
-            let pubkey = RsaPublicKey::from_public_key_der(&signer.public_key)?;
-            let digest = Sha256::digest(&signer.signed_data);
-            let padding = PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256));
-            pubkey.verify(padding, &digest, &sig.signature)?;
+    let v3 = if let Some(block) = sblockv3 {
+        r.seek(SeekFrom::Start(block.start))?;
+        Some(ApkSignatureBlockV3::read(&mut r)?)
+    } else {
+        None
+    };
+    // signers using different algorithms sign different content digests.
+    let mut content_digests: Vec<(u32, Vec<u8>)> = vec![];
+    let mut content_digest = |algorithm: u32| -> Result<Vec<u8>> {
+        if let Some((_, digest)) = content_digests.iter().find(|(id, _)| *id == algorithm) {
+            return Ok(digest.clone());
         }
-        let mut r = Cursor::new(&signer.signed_data[..]);
-        let signed_data = SignedData::read(&mut r)?;
-        anyhow::ensure!(
-            !signed_data.digests.is_empty(),
-            "found no digests in v2 block"
-        );
-        for digest in &signed_data.digests {
+        let digest = compute_digest(&mut r, &sblock, algorithm)?;
+        content_digests.push((algorithm, digest.clone()));
+        Ok(digest)
+    };
+    let mut verified = Verified {
+        v1,
+        ..Default::default()
+    };
+    if let Some(block) = v2 {
+        for signer in &block.signers {
+            let signed_data = signer.verify(&mut content_digest, "v2")?;
+            for attr in &signed_data.additional_attributes {
+                if attr.0 == STRIPPING_PROTECTION_ATTR_ID {
+                    anyhow::ensure!(
+                        sblockv3.is_some(),
+                        "v2 block requires a v3 block which was stripped"
+                    );
+                } else {
+                    tracing::debug!("v2: additional attribute: 0x{:x} {:?}", attr.0, &attr.1);
+                }
+            }
+            verified
+                .v2
+                .extend(decode_certificates(&signed_data.certificates)?);
+        }
+    }
+    if let Some(block) = v3 {
+        for signer in &block.signers {
+            let signed_data = signer.verify(&mut content_digest, "v3")?;
             anyhow::ensure!(
-                digest.algorithm == RSA_PKCS1V15_SHA2_256,
-                "found unsupported digest algorithm 0x{:x}",
-                digest.algorithm
+                signer.sdk_range == signed_data.sdk_range,
+                "v3 signer sdk range doesn't match signed sdk range"
             );
-            anyhow::ensure!(
-                digest.digest == zip_hash,
-                "computed hash doesn't match signed hash."
-            );
-        }
-        for cert in &signed_data.certificates {
-            let cert =
-                rasn::der::decode::<Certificate>(cert).map_err(|err| anyhow::anyhow!("{}", err))?;
-            certificates.push(cert);
-        }
-        for attr in &signed_data.additional_attributes {
-            tracing::debug!("v2: additional attribute: 0x{:x} {:?}", attr.0, &attr.1);
+            let certificates = decode_certificates(&signed_data.certificates)?;
+            for attr in &signed_data.additional_attributes {
+                if attr.0 == PROOF_OF_ROTATION_ATTR_ID {
+                    let lineage = Lineage::parse(&attr.1)?;
+                    anyhow::ensure!(
+                        lineage.nodes.last().map(|node| &node.cert)
+                            == signed_data.certificates.first(),
+                        "v3 signer certificate isn't the last certificate of the lineage"
+                    );
+                    verified.lineage = lineage.certificates()?;
+                } else {
+                    tracing::debug!("v3: additional attribute: 0x{:x} {:?}", attr.0, &attr.1);
+                }
+            }
+            verified.v3.extend(certificates);
         }
     }
-    Ok(certificates)
+    Ok(verified)
+}
+
+fn decode_certificates(certificates: &[Vec<u8>]) -> Result<Vec<Certificate>> {
+    certificates
+        .iter()
+        .map(|cert| {
+            rasn::der::decode::<Certificate>(cert).map_err(|err| anyhow::anyhow!("{}", err))
+        })
+        .collect()
+}
+
+/// Returns the apk signature algorithm id of a signer. RSA and ECDSA keys
+/// are supported by the apk signature schemes, P-384 keys sign using SHA-512.
+pub(crate) fn algorithm_id(signer: &Signer) -> Result<u32> {
+    Ok(match signer.algorithm() {
+        SignatureAlgorithm::RsaPkcs1Sha256 => RSA_PKCS1V15_SHA2_256,
+        SignatureAlgorithm::EcdsaP256Sha256 => ECDSA_SHA2_256,
+        SignatureAlgorithm::EcdsaP384Sha384 => ECDSA_SHA2_512,
+        algorithm => anyhow::bail!("{:?} keys can't be used to sign apks", algorithm),
+    })
+}
+
+fn signature_algorithm(id: u32) -> Result<SignatureAlgorithm> {
+    Ok(match id {
+        RSA_PKCS1V15_SHA2_256 => SignatureAlgorithm::RsaPkcs1Sha256,
+        ECDSA_SHA2_256 => SignatureAlgorithm::EcdsaP256Sha256,
+        ECDSA_SHA2_512 => SignatureAlgorithm::EcdsaP384Sha512,
+        id => anyhow::bail!("found unsupported signature algorithm 0x{:x}", id),
+    })
 }
 
-pub fn sign(path: &Path, signer: Option<Signer>) -> Result<()> {
+/// Signs `data` using the apk signature algorithm of the signer.
+pub(crate) fn sign_data(signer: &Signer, data: &[u8]) -> Result<Vec<u8>> {
+    signer.sign_with(signature_algorithm(algorithm_id(signer)?)?, data)
+}
+
+pub(crate) fn verify_signature(
+    algorithm: u32,
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    signature_algorithm(algorithm)?.verify(public_key, data, signature)
+}
+
+/// Signs the apk. When `v1` is true a jar signature is added, which is
+/// required by devices older than android 7.0 (api 24).
+pub fn sign(path: &Path, signer: Option<Signer>, v1: bool) -> Result<()> {
     let signer = signer.map(Ok).unwrap_or_else(|| Signer::new(DEBUG_PEM))?;
+    sign_with_lineage(path, &[signer], v1)
+}
+
+/// Signs the apk with the last signer. The v1 and v2 signatures are made by
+/// the first signer, so that devices older than android 9 accept updates, and
+/// the v3 block contains a proof-of-rotation from the first to the last signer.
+pub fn sign_with_lineage(path: &Path, signers: &[Signer], v1: bool) -> Result<()> {
+    let (original, signer) = match (signers.first(), signers.last()) {
+        (Some(original), Some(signer)) => (original, signer),
+        _ => anyhow::bail!("no signer"),
+    };
+    // the jar signature covers the zip entries and must be added before the
+    // apk signing block.
+    if v1 {
+        crate::jar::sign(path, original, true)?;
+    }
+    let lineage = if signers.len() > 1 {
+        Some(Lineage::new(signers)?)
+    } else {
+        None
+    };
     let apk = std::fs::read(path)?;
     let mut r = Cursor::new(&apk);
     let block = parse_apk_signing_block(&mut r)?;
-    let zip_hash = compute_digest(&mut r, block.sb_start, block.cd_start, block.cde_start)?;
+    let original_digest = compute_digest(&mut r, &block, algorithm_id(original)?)?;
+    let digest = if algorithm_id(signer)? == algorithm_id(original)? {
+        original_digest.clone()
+    } else {
+        compute_digest(&mut r, &block, algorithm_id(signer)?)?
+    };
     let mut nblock = vec![];
     let mut w = Cursor::new(&mut nblock);
-    write_apk_signing_block(&mut w, zip_hash, &signer)?;
+    write_apk_signing_block(
+        &mut w,
+        original_digest,
+        digest,
+        original,
+        signer,
+        lineage.as_ref(),
+    )?;
     let mut f = File::create(path)?;
     f.write_all(&apk[..(block.sb_start as usize)])?;
     f.write_all(&nblock)?;
@@ -114,14 +241,36 @@ pub fn sign(path: &Path, signer: Option<Signer>) -> Result<()> {
     Ok(())
 }
 
+/// Returns the content digest signed by v2 and v3 signers using `algorithm`.
+pub(crate) fn apk_digest(path: &Path, algorithm: u32) -> Result<Vec<u8>> {
+    let f = File::open(path)?;
+    let mut r = BufReader::new(f);
+    let block = parse_apk_signing_block(&mut r)?;
+    compute_digest(&mut r, &block, algorithm)
+}
+
+/// ECDSA with SHA-512 signs a chunked SHA-512 digest, the other algorithms
+/// a chunked SHA-256 digest.
 fn compute_digest<R: Read + Seek>(
+    r: &mut R,
+    block: &ApkSignatureBlock,
+    algorithm: u32,
+) -> Result<Vec<u8>> {
+    let (sb_start, cd_start, cde_start) = (block.sb_start, block.cd_start, block.cde_start);
+    match algorithm {
+        ECDSA_SHA2_512 => chunked_digest::<Sha512, _>(r, sb_start, cd_start, cde_start),
+        _ => chunked_digest::<Sha256, _>(r, sb_start, cd_start, cde_start),
+    }
+}
+
+fn chunked_digest<D: sha2::Digest, R: Read + Seek>(
     r: &mut R,
     sb_start: u64,
     cd_start: u64,
     cde_start: u64,
-) -> Result<[u8; 32]> {
+) -> Result<Vec<u8>> {
     let mut chunks = vec![];
-    let mut hasher = Sha256::new();
+    let mut hasher = D::new();
     let mut chunk = vec![0u8; MAX_CHUNK_SIZE];
 
     // chunk contents
@@ -146,7 +295,7 @@ fn compute_digest<R: Read + Seek>(
     assert!(chunk.len() <= MAX_CHUNK_SIZE);
     hasher.update(&(chunk.len() as u32).to_le_bytes());
     hasher.update(&chunk);
-    chunks.push(hasher.finalize_reset().into());
+    chunks.push(std::mem::replace(&mut hasher, D::new()).finalize().to_vec());
 
     // compute root
     hasher.update(&[0x5a]);
@@ -154,14 +303,14 @@ fn compute_digest<R: Read + Seek>(
     for chunk in &chunks {
         hasher.update(&chunk);
     }
-    Ok(hasher.finalize().into())
+    Ok(hasher.finalize().to_vec())
 }
 
-fn hash_chunk<R: Read + Seek>(
-    chunks: &mut Vec<[u8; 32]>,
+fn hash_chunk<D: sha2::Digest, R: Read + Seek>(
+    chunks: &mut Vec<Vec<u8>>,
     r: &mut R,
     size: u64,
-    hasher: &mut Sha256,
+    hasher: &mut D,
     buffer: &mut Vec<u8>,
     pos: &mut u64,
 ) -> Result<()> {
@@ -172,7 +321,7 @@ fn hash_chunk<R: Read + Seek>(
     hasher.update(&[0xa5]);
     hasher.update(&(len as u32).to_le_bytes());
     hasher.update(&buffer);
-    chunks.push(hasher.finalize_reset().into());
+    chunks.push(std::mem::replace(hasher, D::new()).finalize().to_vec());
     *pos = end;
     Ok(())
 }
@@ -184,11 +333,8 @@ struct Digest {
 }
 
 impl Digest {
-    fn new(hash: [u8; 32]) -> Self {
-        Self {
-            algorithm: RSA_PKCS1V15_SHA2_256,
-            digest: hash.to_vec(),
-        }
+    fn new(digest: Vec<u8>, algorithm: u32) -> Self {
+        Self { algorithm, digest }
     }
 
     fn size(&self) -> u32 {
@@ -217,21 +363,24 @@ impl Digest {
 struct SignedData {
     pub digests: Vec<Digest>,
     pub certificates: Vec<Vec<u8>>,
+    /// Only present in v3 blocks.
+    pub sdk_range: Option<(u32, u32)>,
     pub additional_attributes: Vec<(u32, Vec<u8>)>,
 }
 
 impl SignedData {
-    fn new(hash: [u8; 32], signer: &Signer) -> Result<Self> {
+    fn new(digest: Vec<u8>, signer: &Signer) -> Result<Self> {
         Ok(Self {
-            digests: vec![Digest::new(hash)],
+            digests: vec![Digest::new(digest, algorithm_id(signer)?)],
             certificates: vec![
                 rasn::der::encode(signer.cert()).map_err(|err| anyhow::anyhow!("{}", err))?
             ],
+            sdk_range: None,
             additional_attributes: vec![],
         })
     }
 
-    fn read(r: &mut impl Read) -> Result<Self> {
+    fn read(r: &mut impl Read, v3: bool) -> Result<Self> {
         let mut signed_data = SignedData::default();
         let mut remaining_digests_size = r.read_u32::<LittleEndian>()?;
         while remaining_digests_size > 0 {
@@ -247,6 +396,11 @@ impl SignedData {
             signed_data.certificates.push(cert);
             remaining_certificates_size -= length + 4;
         }
+        if v3 {
+            let min_sdk = r.read_u32::<LittleEndian>()?;
+            let max_sdk = r.read_u32::<LittleEndian>()?;
+            signed_data.sdk_range = Some((min_sdk, max_sdk));
+        }
         let mut remaining_additional_attributes_size = r.read_u32::<LittleEndian>()?;
         while remaining_additional_attributes_size > 0 {
             let length = r.read_u32::<LittleEndian>()?;
@@ -269,6 +423,10 @@ impl SignedData {
             w.write_u32::<LittleEndian>(cert.len() as u32)?;
             w.write_all(cert)?;
         }
+        if let Some((min_sdk, max_sdk)) = self.sdk_range {
+            w.write_u32::<LittleEndian>(min_sdk)?;
+            w.write_u32::<LittleEndian>(max_sdk)?;
+        }
         w.write_u32::<LittleEndian>(
             self.additional_attributes
                 .iter()
@@ -289,9 +447,16 @@ struct ApkSignatureBlockV2 {
     pub signers: Vec<ApkSigner>,
 }
 
+#[derive(Debug)]
+struct ApkSignatureBlockV3 {
+    pub signers: Vec<ApkSigner>,
+}
+
 #[derive(Debug)]
 struct ApkSigner {
     pub signed_data: Vec<u8>,
+    /// Only present in v3 blocks.
+    pub sdk_range: Option<(u32, u32)>,
     pub signatures: Vec<ApkSignature>,
     pub public_key: Vec<u8>,
 }
@@ -302,86 +467,331 @@ struct ApkSignature {
     pub signature: Vec<u8>,
 }
 
-impl ApkSignatureBlockV2 {
-    fn new(hash: [u8; 32], signer: &Signer) -> Result<Self> {
-        let mut signed_data = vec![];
-        SignedData::new(hash, signer)?.write(&mut signed_data)?;
-        let signature = signer.sign(&signed_data);
+impl ApkSigner {
+    fn new(signed_data: SignedData, signer: &Signer) -> Result<Self> {
+        let sdk_range = signed_data.sdk_range;
+        let mut buf = vec![];
+        signed_data.write(&mut buf)?;
+        let signature = sign_data(signer, &buf)?;
         Ok(Self {
-            signers: vec![ApkSigner {
-                signed_data,
-                signatures: vec![ApkSignature {
-                    algorithm: RSA_PKCS1V15_SHA2_256,
-                    signature,
-                }],
-                public_key: signer.pubkey().to_public_key_der()?.as_ref().to_vec(),
+            signed_data: buf,
+            sdk_range,
+            signatures: vec![ApkSignature {
+                algorithm: algorithm_id(signer)?,
+                signature,
             }],
+            public_key: signer.pubkey().to_vec(),
         })
     }
 
-    fn read(r: &mut impl Read) -> Result<Self> {
-        let mut signers = vec![];
-        let mut remaining_size = r.read_u32::<LittleEndian>()? as u64;
-        while remaining_size > 0 {
-            let signer_size = r.read_u32::<LittleEndian>()?;
+    /// Checks the signatures and the digests and returns the signed data.
+    fn verify(
+        &self,
+        content_digest: &mut impl FnMut(u32) -> Result<Vec<u8>>,
+        version: &str,
+    ) -> Result<SignedData> {
+        anyhow::ensure!(
+            !self.signatures.is_empty(),
+            "found no signatures in {} block",
+            version
+        );
+        for sig in &self.signatures {
+            verify_signature(
+                sig.algorithm,
+                &self.public_key,
+                &self.signed_data,
+                &sig.signature,
+            )?;
+        }
+        let mut r = Cursor::new(&self.signed_data[..]);
+        let signed_data = SignedData::read(&mut r, self.sdk_range.is_some())?;
+        anyhow::ensure!(
+            !signed_data.digests.is_empty(),
+            "found no digests in {} block",
+            version
+        );
+        for digest in &signed_data.digests {
+            anyhow::ensure!(
+                signature_algorithm(digest.algorithm).is_ok(),
+                "found unsupported digest algorithm 0x{:x}",
+                digest.algorithm
+            );
+            anyhow::ensure!(
+                digest.digest == content_digest(digest.algorithm)?,
+                "computed hash doesn't match signed hash."
+            );
+        }
+        Ok(signed_data)
+    }
 
-            let signed_data_size = r.read_u32::<LittleEndian>()?;
-            let mut signed_data = vec![0; signed_data_size as _];
-            r.read_exact(&mut signed_data)?;
-
-            let mut signatures = vec![];
-            let mut remaining_signature_size = r.read_u32::<LittleEndian>()?;
-            while remaining_signature_size > 0 {
-                let signature_size = r.read_u32::<LittleEndian>()?;
-                let algorithm = r.read_u32::<LittleEndian>()?;
-                let size = r.read_u32::<LittleEndian>()?;
-                let mut signature = vec![0; size as usize];
-                r.read_exact(&mut signature)?;
-                signatures.push(ApkSignature {
-                    algorithm,
-                    signature,
-                });
-                remaining_signature_size -= signature_size + 4;
-            }
+    fn read(r: &mut impl Read, v3: bool) -> Result<Self> {
+        let signed_data_size = r.read_u32::<LittleEndian>()?;
+        let mut signed_data = vec![0; signed_data_size as _];
+        r.read_exact(&mut signed_data)?;
+
+        let sdk_range = if v3 {
+            let min_sdk = r.read_u32::<LittleEndian>()?;
+            let max_sdk = r.read_u32::<LittleEndian>()?;
+            Some((min_sdk, max_sdk))
+        } else {
+            None
+        };
+
+        let mut signatures = vec![];
+        let mut remaining_signature_size = r.read_u32::<LittleEndian>()?;
+        while remaining_signature_size > 0 {
+            let signature_size = r.read_u32::<LittleEndian>()?;
+            let algorithm = r.read_u32::<LittleEndian>()?;
+            let size = r.read_u32::<LittleEndian>()?;
+            let mut signature = vec![0; size as usize];
+            r.read_exact(&mut signature)?;
+            signatures.push(ApkSignature {
+                algorithm,
+                signature,
+            });
+            remaining_signature_size -= signature_size + 4;
+        }
 
-            let public_key_size = r.read_u32::<LittleEndian>()?;
-            let mut public_key = vec![0; public_key_size as _];
-            r.read_exact(&mut public_key)?;
+        let public_key_size = r.read_u32::<LittleEndian>()?;
+        let mut public_key = vec![0; public_key_size as _];
+        r.read_exact(&mut public_key)?;
 
-            signers.push(ApkSigner {
-                signed_data,
-                signatures,
-                public_key,
-            });
-            remaining_size -= signer_size as u64 + 4;
+        Ok(Self {
+            signed_data,
+            sdk_range,
+            signatures,
+            public_key,
+        })
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.signed_data.len() as u32)?;
+        w.write_all(&self.signed_data)?;
+        if let Some((min_sdk, max_sdk)) = self.sdk_range {
+            w.write_u32::<LittleEndian>(min_sdk)?;
+            w.write_u32::<LittleEndian>(max_sdk)?;
         }
-        Ok(ApkSignatureBlockV2 { signers })
+        let mut sig_buffer = vec![];
+        for sig in &self.signatures {
+            sig_buffer.write_u32::<LittleEndian>(sig.signature.len() as u32 + 8)?;
+            sig_buffer.write_u32::<LittleEndian>(sig.algorithm)?;
+            sig_buffer.write_u32::<LittleEndian>(sig.signature.len() as u32)?;
+            sig_buffer.write_all(&sig.signature)?;
+        }
+        w.write_u32::<LittleEndian>(sig_buffer.len() as u32)?;
+        w.write_all(&sig_buffer)?;
+        w.write_u32::<LittleEndian>(self.public_key.len() as u32)?;
+        w.write_all(&self.public_key)?;
+        Ok(())
+    }
+}
+
+fn read_signers(r: &mut impl Read, v3: bool) -> Result<Vec<ApkSigner>> {
+    let mut signers = vec![];
+    let mut remaining_size = r.read_u32::<LittleEndian>()? as u64;
+    while remaining_size > 0 {
+        let signer_size = r.read_u32::<LittleEndian>()?;
+        signers.push(ApkSigner::read(r, v3)?);
+        remaining_size -= signer_size as u64 + 4;
+    }
+    Ok(signers)
+}
+
+fn write_signers(w: &mut impl Write, signers: &[ApkSigner]) -> Result<()> {
+    let mut buffer = vec![];
+    for signer in signers {
+        let mut signer_buffer = vec![];
+        signer.write(&mut signer_buffer)?;
+        buffer.write_u32::<LittleEndian>(signer_buffer.len() as u32)?;
+        buffer.write_all(&signer_buffer)?;
+    }
+    w.write_u32::<LittleEndian>(buffer.len() as u32)?;
+    w.write_all(&buffer)?;
+    Ok(())
+}
+
+impl ApkSignatureBlockV2 {
+    fn new(digest: Vec<u8>, signer: &Signer) -> Result<Self> {
+        let mut signed_data = SignedData::new(digest, signer)?;
+        // tells verifiers to reject the apk if the v3 block was removed.
+        signed_data
+            .additional_attributes
+            .push((STRIPPING_PROTECTION_ATTR_ID, 3u32.to_le_bytes().to_vec()));
+        Ok(Self {
+            signers: vec![ApkSigner::new(signed_data, signer)?],
+        })
+    }
+
+    fn read(r: &mut impl Read) -> Result<Self> {
+        Ok(Self {
+            signers: read_signers(r, false)?,
+        })
     }
 
     fn write(&self, w: &mut impl Write) -> Result<()> {
-        let mut buffer = vec![];
-        for signer in &self.signers {
-            let mut signer_buffer = vec![];
-            signer_buffer.write_u32::<LittleEndian>(signer.signed_data.len() as u32)?;
-            signer_buffer.write_all(&signer.signed_data)?;
-            let mut sig_buffer = vec![];
-            for sig in &signer.signatures {
-                sig_buffer.write_u32::<LittleEndian>(sig.signature.len() as u32 + 8)?;
-                sig_buffer.write_u32::<LittleEndian>(sig.algorithm)?;
-                sig_buffer.write_u32::<LittleEndian>(sig.signature.len() as u32)?;
-                sig_buffer.write_all(&sig.signature)?;
+        write_signers(w, &self.signers)
+    }
+}
+
+impl ApkSignatureBlockV3 {
+    fn new(digest: Vec<u8>, signer: &Signer, lineage: Option<&Lineage>) -> Result<Self> {
+        let mut signed_data = SignedData::new(digest, signer)?;
+        signed_data.sdk_range = Some((V3_MIN_SDK, V3_MAX_SDK));
+        if let Some(lineage) = lineage {
+            let mut buf = vec![];
+            lineage.write(&mut buf)?;
+            signed_data
+                .additional_attributes
+                .push((PROOF_OF_ROTATION_ATTR_ID, buf));
+        }
+        Ok(Self {
+            signers: vec![ApkSigner::new(signed_data, signer)?],
+        })
+    }
+
+    fn read(r: &mut impl Read) -> Result<Self> {
+        Ok(Self {
+            signers: read_signers(r, true)?,
+        })
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        write_signers(w, &self.signers)
+    }
+}
+
+/// Proof-of-rotation from the original signing certificate to the current one.
+///
+/// Each certificate is signed by the key of the previous certificate, which
+/// allows devices to accept updates signed with a new key.
+#[derive(Clone, Debug, Default)]
+pub struct Lineage {
+    nodes: Vec<LineageNode>,
+}
+
+#[derive(Clone, Debug)]
+struct LineageNode {
+    /// DER encoded certificate.
+    cert: Vec<u8>,
+    /// Algorithm the previous key used to sign this node.
+    parent_algorithm: u32,
+    flags: u32,
+    /// Algorithm this key uses to sign the next node.
+    algorithm: u32,
+    signature: Vec<u8>,
+}
+
+impl LineageNode {
+    fn signed_data(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        buf.write_u32::<LittleEndian>(self.cert.len() as u32)?;
+        buf.write_all(&self.cert)?;
+        buf.write_u32::<LittleEndian>(self.parent_algorithm)?;
+        Ok(buf)
+    }
+}
+
+impl Lineage {
+    /// Creates a lineage from signers ordered from oldest to newest.
+    pub fn new(signers: &[Signer]) -> Result<Self> {
+        let mut nodes: Vec<LineageNode> = Vec::with_capacity(signers.len());
+        for (i, signer) in signers.iter().enumerate() {
+            let mut node = LineageNode {
+                cert: rasn::der::encode(signer.cert()).map_err(|err| anyhow::anyhow!("{}", err))?,
+                parent_algorithm: 0,
+                flags: DEFAULT_LINEAGE_FLAGS,
+                algorithm: algorithm_id(signer)?,
+                signature: vec![],
+            };
+            if i > 0 {
+                node.parent_algorithm = nodes[i - 1].algorithm;
+                node.signature = sign_data(&signers[i - 1], &node.signed_data()?)?;
+            }
+            nodes.push(node);
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Parses a proof-of-rotation attribute and verifies the signatures.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut r = Cursor::new(bytes);
+        let version = r.read_u32::<LittleEndian>()?;
+        anyhow::ensure!(
+            version == PROOF_OF_ROTATION_VERSION,
+            "unsupported proof-of-rotation version {}",
+            version
+        );
+        let mut nodes: Vec<LineageNode> = vec![];
+        while (r.position() as usize) < bytes.len() {
+            let _node_size = r.read_u32::<LittleEndian>()?;
+            let signed_data_size = r.read_u32::<LittleEndian>()?;
+            let cert_size = r.read_u32::<LittleEndian>()?;
+            anyhow::ensure!(
+                signed_data_size == cert_size + 8,
+                "invalid proof-of-rotation signed data"
+            );
+            let mut cert = vec![0; cert_size as usize];
+            r.read_exact(&mut cert)?;
+            let parent_algorithm = r.read_u32::<LittleEndian>()?;
+            let flags = r.read_u32::<LittleEndian>()?;
+            let algorithm = r.read_u32::<LittleEndian>()?;
+            let signature_size = r.read_u32::<LittleEndian>()?;
+            let mut signature = vec![0; signature_size as usize];
+            r.read_exact(&mut signature)?;
+            let node = LineageNode {
+                cert,
+                parent_algorithm,
+                flags,
+                algorithm,
+                signature,
+            };
+            if let Some(parent) = nodes.last() {
+                anyhow::ensure!(
+                    parent.algorithm == node.parent_algorithm,
+                    "proof-of-rotation algorithm mismatch"
+                );
+                let cert = rasn::der::decode::<Certificate>(&parent.cert)
+                    .map_err(|err| anyhow::anyhow!("{}", err))?;
+                let public_key = rasn::der::encode(&cert.tbs_certificate.subject_public_key_info)
+                    .map_err(|err| anyhow::anyhow!("{}", err))?;
+                verify_signature(
+                    parent.algorithm,
+                    &public_key,
+                    &node.signed_data()?,
+                    &node.signature,
+                )?;
             }
-            signer_buffer.write_u32::<LittleEndian>(sig_buffer.len() as u32)?;
-            signer_buffer.write_all(&sig_buffer)?;
-            signer_buffer.write_u32::<LittleEndian>(signer.public_key.len() as u32)?;
-            signer_buffer.write_all(&signer.public_key)?;
-            buffer.write_u32::<LittleEndian>(signer_buffer.len() as u32)?;
-            buffer.write_all(&signer_buffer)?;
+            nodes.push(node);
+        }
+        Ok(Self { nodes })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_u32::<LittleEndian>(PROOF_OF_ROTATION_VERSION)?;
+        for node in &self.nodes {
+            let mut buf = vec![];
+            let signed_data = node.signed_data()?;
+            buf.write_u32::<LittleEndian>(signed_data.len() as u32)?;
+            buf.write_all(&signed_data)?;
+            buf.write_u32::<LittleEndian>(node.flags)?;
+            buf.write_u32::<LittleEndian>(node.algorithm)?;
+            buf.write_u32::<LittleEndian>(node.signature.len() as u32)?;
+            buf.write_all(&node.signature)?;
+            w.write_u32::<LittleEndian>(buf.len() as u32)?;
+            w.write_all(&buf)?;
         }
-        w.write_u32::<LittleEndian>(buffer.len() as u32)?;
-        w.write_all(&buffer)?;
         Ok(())
     }
+
+    /// Certificates of the lineage, oldest first.
+    pub fn certificates(&self) -> Result<Vec<Certificate>> {
+        let certs = self
+            .nodes
+            .iter()
+            .map(|node| node.cert.clone())
+            .collect::<Vec<_>>();
+        decode_certificates(&certs)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -400,16 +810,23 @@ struct ApkOpaqueBlock {
 
 fn write_apk_signing_block<W: Write + Seek>(
     w: &mut W,
-    hash: [u8; 32],
+    original_digest: Vec<u8>,
+    digest: Vec<u8>,
+    original: &Signer,
     signer: &Signer,
+    lineage: Option<&Lineage>,
 ) -> Result<()> {
-    let mut buf = vec![];
-    ApkSignatureBlockV2::new(hash, signer)?.write(&mut buf)?;
-    let size = buf.len() as u64 + 36;
+    let mut v2 = vec![];
+    ApkSignatureBlockV2::new(original_digest, original)?.write(&mut v2)?;
+    let mut v3 = vec![];
+    ApkSignatureBlockV3::new(digest, signer, lineage)?.write(&mut v3)?;
+    let size = (v2.len() as u64 + 12) + (v3.len() as u64 + 12) + 24;
     w.write_u64::<LittleEndian>(size)?;
-    w.write_u64::<LittleEndian>(buf.len() as u64 + 4)?;
-    w.write_u32::<LittleEndian>(APK_SIGNING_BLOCK_V2_ID)?;
-    w.write_all(&buf)?;
+    for (id, block) in [(APK_SIGNING_BLOCK_V2_ID, v2), (APK_SIGNING_BLOCK_V3_ID, v3)] {
+        w.write_u64::<LittleEndian>(block.len() as u64 + 4)?;
+        w.write_u32::<LittleEndian>(id)?;
+        w.write_all(&block)?;
+    }
     w.write_u64::<LittleEndian>(size)?;
     w.write_all(APK_SIGNING_BLOCK_MAGIC)?;
     Ok(())
@@ -444,3 +861,119 @@ fn parse_apk_signing_block<R: Read + Seek>(r: &mut R) -> Result<ApkSignatureBloc
     }
     Ok(block)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xcommon::{Zip, ZipFileOptions};
+
+    const TEST_PEM: &str = include_str!("../../xcommon/assets/test.pem");
+    const P256_PEM: &str = include_str!("../../xcommon/assets/test_p256.pem");
+    const P384_PEM: &str = include_str!("../../xcommon/assets/test_p384.pem");
+    const ED25519_PEM: &str = include_str!("../../xcommon/assets/test_ed25519.pem");
+
+    fn create_zip(name: &str) -> Result<std::path::PathBuf> {
+        let path = std::env::temp_dir().join(name);
+        let mut zip = Zip::new(&path, true)?;
+        zip.create_file(
+            Path::new("hello.txt"),
+            ZipFileOptions::Compressed,
+            b"hello world",
+        )?;
+        zip.finish()?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_sign_verify() -> Result<()> {
+        let path = create_zip("test_sign_verify.apk")?;
+        sign(&path, None, false)?;
+        let verified = verify(&path)?;
+        assert!(verified.v1.is_empty());
+        assert_eq!(verified.v2.len(), 1);
+        assert_eq!(verified.v2, verified.v3);
+        assert!(verified.lineage.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_verify_lineage() -> Result<()> {
+        let path = create_zip("test_sign_verify_lineage.apk")?;
+        let original = Signer::new(DEBUG_PEM)?;
+        let signer = Signer::new(TEST_PEM)?;
+        sign_with_lineage(&path, &[original.clone(), signer.clone()], true)?;
+        let verified = verify(&path)?;
+        assert_eq!(verified.v1, vec![original.cert().clone()]);
+        assert_eq!(verified.v2, vec![original.cert().clone()]);
+        assert_eq!(verified.v3, vec![signer.cert().clone()]);
+        assert_eq!(
+            verified.lineage,
+            vec![original.cert().clone(), signer.cert().clone()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_verify_v1() -> Result<()> {
+        let path = create_zip("test_sign_verify_v1.apk")?;
+        sign(&path, None, true)?;
+        let verified = verify(&path)?;
+        assert_eq!(verified.v1.len(), 1);
+        assert_eq!(verified.v1, verified.v2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_verify_ecdsa() -> Result<()> {
+        let path = create_zip("test_sign_verify_ecdsa.apk")?;
+        let signer = Signer::new(P256_PEM)?;
+        sign(&path, Some(signer.clone()), true)?;
+        let verified = verify(&path)?;
+        assert_eq!(verified.v1, vec![signer.cert().clone()]);
+        assert_eq!(verified.v2, verified.v1);
+        assert_eq!(verified.v3, verified.v1);
+        let zip = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+        assert!(zip.file_names().any(|name| name == "META-INF/CERT.EC"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_verify_p384() -> Result<()> {
+        let path = create_zip("test_sign_verify_p384.apk")?;
+        let signer = Signer::new(P384_PEM)?;
+        assert_eq!(algorithm_id(&signer)?, ECDSA_SHA2_512);
+        sign(&path, Some(signer.clone()), true)?;
+        let verified = verify(&path)?;
+        assert_eq!(verified.v1, vec![signer.cert().clone()]);
+        assert_eq!(verified.v2, verified.v1);
+        assert_eq!(verified.v3, verified.v1);
+        assert_eq!(apk_digest(&path, ECDSA_SHA2_512)?.len(), 64);
+        let zip = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+        assert!(zip.file_names().any(|name| name == "META-INF/CERT.EC"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_verify_rotate_to_p384() -> Result<()> {
+        let path = create_zip("test_sign_verify_rotate_to_p384.apk")?;
+        let original = Signer::new(DEBUG_PEM)?;
+        let signer = Signer::new(P384_PEM)?;
+        sign_with_lineage(&path, &[original.clone(), signer.clone()], false)?;
+        let verified = verify(&path)?;
+        assert_eq!(verified.v2, vec![original.cert().clone()]);
+        assert_eq!(verified.v3, vec![signer.cert().clone()]);
+        assert_eq!(
+            verified.lineage,
+            vec![original.cert().clone(), signer.cert().clone()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_ed25519_unsupported() -> Result<()> {
+        let path = create_zip("test_sign_ed25519_unsupported.apk")?;
+        let signer = Signer::new(ED25519_PEM)?;
+        assert!(sign(&path, Some(signer), false).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+//! Configuration splits, which are installed together with the base apk
+//! using `adb install-multiple`.
+use crate::compiler::density_bucket;
+use crate::manifest::{AndroidManifest, Application};
+use crate::res::{Chunk, ResTableValue, ResValueType};
+use crate::Target;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+const ABIS: [Target; 4] = [
+    Target::ArmV7a,
+    Target::Arm64V8a,
+    Target::X86,
+    Target::X86_64,
+];
+
+/// Config of the split containing the native libraries of an abi, like
+/// `arm64_v8a`.
+pub(crate) fn abi_config(abi: &str) -> String {
+    abi.replace('-', "_")
+}
+
+/// Prefix of the configuration splits of an apk like
+/// `helloworld.split_config.`, so the splits of apks in the same directory
+/// don't collide.
+fn split_prefix(apk: &Path) -> String {
+    let stem = apk
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    format!("{}.split_config.", stem)
+}
+
+/// Path of a configuration split like
+/// `helloworld.split_config.arm64_v8a.apk`, which is written next to the
+/// base apk `helloworld.apk`.
+pub(crate) fn split_path(apk: &Path, config: &str) -> PathBuf {
+    apk.with_file_name(format!("{}{}.apk", split_prefix(apk), config))
+}
+
+/// Returns the config and path of the configuration splits of an apk.
+/// When an `abi` is given splits of other abis are skipped.
+pub(crate) fn find_splits(apk: &Path, abi: Option<&str>) -> Result<Vec<(String, PathBuf)>> {
+    let dir = apk
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("invalid path {}", apk.display()))?;
+    let prefix = split_prefix(apk);
+    let abis = ABIS.map(|target| abi_config(target.android_abi()));
+    let abi = abi.map(abi_config);
+    let mut splits = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|name| name.to_str());
+        let config = name
+            .and_then(|name| name.strip_prefix(prefix.as_str()))
+            .and_then(|name| name.strip_suffix(".apk"));
+        if let Some(config) = config {
+            let other_abi = match abi.as_deref() {
+                Some(abi) => abi != config && abis.iter().any(|abi| abi == config),
+                None => false,
+            };
+            if !other_abi {
+                splits.push((config.to_string(), path.clone()));
+            }
+        }
+    }
+    splits.sort();
+    Ok(splits)
+}
+
+/// Manifest of a configuration split, which has to match the package and
+/// version of the base apk.
+pub(crate) fn split_manifest(base: &AndroidManifest, config: &str) -> AndroidManifest {
+    let mut manifest = AndroidManifest::default();
+    manifest.package = base.package.clone();
+    manifest.split = Some(format!("config.{}", config));
+    manifest.version_code = base.version_code;
+    manifest.version_name = base.version_name.clone();
+    manifest.application = Application {
+        has_code: Some(false),
+        ..Default::default()
+    };
+    manifest
+}
+
+/// Moves the resources of density buckets like `xxhdpi` out of a compiled
+/// resource table. Returns the resource table of each density split and the
+/// files referenced by it.
+pub(crate) fn split_densities(
+    table: &mut Chunk,
+) -> Result<BTreeMap<&'static str, (Chunk, BTreeSet<String>)>> {
+    let (header, chunks) = if let Chunk::Table(header, chunks) = table {
+        (header, chunks)
+    } else {
+        anyhow::bail!("expected table chunk");
+    };
+    let strings = if let Some(Chunk::StringPool(_, strings, _, _)) = chunks.first() {
+        strings
+    } else {
+        anyhow::bail!("invalid table");
+    };
+    let density = |chunk: &Chunk| match chunk {
+        Chunk::TableType(header, _, _) => density_bucket(header.config.density),
+        _ => None,
+    };
+    let mut densities = BTreeSet::new();
+    for chunk in chunks.iter() {
+        if let Chunk::TablePackage(_, chunks) = chunk {
+            densities.extend(chunks.iter().filter_map(density));
+        }
+    }
+    let mut splits = BTreeMap::new();
+    for name in densities {
+        let mut files = BTreeSet::new();
+        // the split keeps the string pools and type specs, so resource ids
+        // and string references stay the same.
+        let mut split = vec![chunks[0].clone()];
+        for chunk in &chunks[1..] {
+            if let Chunk::TablePackage(header, chunks) = chunk {
+                let chunks = chunks
+                    .iter()
+                    .filter(|chunk| match chunk {
+                        Chunk::TableType(_, _, _) => density(chunk) == Some(name),
+                        _ => true,
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for chunk in &chunks {
+                    if let Chunk::TableType(_, _, entries) = chunk {
+                        for entry in entries.iter().flatten() {
+                            if let ResTableValue::Simple(value) = &entry.value {
+                                if value.data_type == ResValueType::String as u8 {
+                                    files.extend(strings.get(value.data as usize).cloned());
+                                }
+                            }
+                        }
+                    }
+                }
+                split.push(Chunk::TablePackage(header.clone(), chunks));
+            }
+        }
+        splits.insert(name, (Chunk::Table(*header, split), files));
+    }
+    for chunk in chunks.iter_mut() {
+        if let Chunk::TablePackage(_, chunks) = chunk {
+            chunks.retain(|chunk| density(chunk).is_none());
+        }
+    }
+    Ok(splits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Resources, Table};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_densities() -> Result<()> {
+        let mut resources = Resources::new("com.example.helloworld");
+        resources.add_mipmap("icon", 48)?;
+        resources.add_values(
+            Default::default(),
+            r#"<resources><string name="app_name">helloworld</string></resources>"#,
+        )?;
+        let mut chunk = resources.compile(&Table::default())?;
+        let splits = split_densities(&mut chunk)?;
+        assert_eq!(
+            splits.keys().copied().collect::<Vec<_>>(),
+            ["hdpi", "mdpi", "xhdpi", "xxhdpi", "xxxhdpi"]
+        );
+        let (split, files) = &splits["xxhdpi"];
+        assert_eq!(files.iter().collect::<Vec<_>>(), ["res/icon/icon144.png"]);
+        let mut buf = vec![];
+        split.write(&mut Cursor::new(&mut buf))?;
+        assert_eq!(split, &Chunk::parse(&mut Cursor::new(&buf))?);
+
+        let mut densities = vec![];
+        if let Chunk::Table(_, chunks) = &chunk {
+            for chunk in chunks {
+                if let Chunk::TablePackage(_, chunks) = chunk {
+                    for chunk in chunks {
+                        if let Chunk::TableType(header, _, _) = chunk {
+                            densities.push(header.config.density);
+                        }
+                    }
+                }
+            }
+        }
+        // only the string remains in the base
+        assert_eq!(densities, [0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_splits() -> Result<()> {
+        let dir = std::env::temp_dir().join("apk_find_splits");
+        std::fs::create_dir_all(&dir)?;
+        let apk = dir.join("helloworld.apk");
+        for config in ["arm64_v8a", "x86_64", "xxhdpi"] {
+            std::fs::write(split_path(&apk, config), [])?;
+        }
+        let other = dir.join("other.apk");
+        std::fs::write(split_path(&other, "armeabi_v7a"), [])?;
+        assert_eq!(
+            split_path(&apk, "xxhdpi"),
+            dir.join("helloworld.split_config.xxhdpi.apk")
+        );
+        let configs = |abi| -> Result<Vec<String>> {
+            Ok(find_splits(&apk, abi)?
+                .into_iter()
+                .map(|(config, _)| config)
+                .collect())
+        };
+        assert_eq!(configs(None)?, ["arm64_v8a", "x86_64", "xxhdpi"]);
+        assert_eq!(configs(Some("arm64-v8a"))?, ["arm64_v8a", "xxhdpi"]);
+        assert_eq!(find_splits(&other, None)?.len(), 1);
+        Ok(())
+    }
+}
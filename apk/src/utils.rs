@@ -23,13 +23,16 @@ impl Target {
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct VersionCode {
-    major: u8,
-    minor: u8,
-    patch: u8,
+    major: u32,
+    minor: u32,
+    patch: u32,
 }
 
 impl VersionCode {
-    pub fn new(major: u8, minor: u8, patch: u8) -> Self {
+    /// Largest version code accepted by google play.
+    pub const MAX: u32 = 2_100_000_000;
+
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
         Self {
             major,
             minor,
@@ -48,11 +51,43 @@ impl VersionCode {
         Ok(Self::new(p()?, p()?, p()?))
     }
 
-    pub fn to_code(&self, apk_id: u8) -> u32 {
-        (apk_id as u32) << 24
-            | (self.major as u32) << 16
-            | (self.minor as u32) << 8
-            | self.patch as u32
+    pub fn major(&self) -> u32 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+
+    pub fn patch(&self) -> u32 {
+        self.patch
+    }
+
+    /// Encodes the version as `apk_id << 24 | major << 16 | minor << 8 | patch`.
+    ///
+    /// Each part of the version only has 8 bits, versions with a part larger
+    /// than 255 need a build number which is used as the version code instead.
+    pub fn to_code(&self, apk_id: u8) -> Result<u32> {
+        for (name, part) in [
+            ("major", self.major),
+            ("minor", self.minor),
+            ("patch", self.patch),
+        ] {
+            anyhow::ensure!(
+                part <= u8::MAX as u32,
+                "{} version {} doesn't fit into a version code, use a build number instead",
+                name,
+                part
+            );
+        }
+        let code = (apk_id as u32) << 24 | self.major << 16 | self.minor << 8 | self.patch;
+        anyhow::ensure!(
+            code <= Self::MAX,
+            "version code {} exceeds {}",
+            code,
+            Self::MAX
+        );
+        Ok(code)
     }
 }
 
@@ -67,4 +102,14 @@ mod tests {
         let v = VersionCode::from_semver("254.254.254-alpha.fix+2").unwrap();
         assert_eq!(v, VersionCode::new(254, 254, 254));
     }
+
+    #[test]
+    fn to_code() {
+        let v = VersionCode::from_semver("1.2.3").unwrap();
+        assert_eq!(v.to_code(1).unwrap(), 0x0101_0203);
+        let v = VersionCode::from_semver("0.256.0").unwrap();
+        assert!(v.to_code(1).is_err());
+        let v = VersionCode::from_semver("255.0.0").unwrap();
+        assert!(v.to_code(127).is_err());
+    }
 }
@@ -67,4 +67,30 @@ pub struct InfoPlist {
 
     #[serde(rename(serialize = "UILaunchStoryboardName"))]
     pub storyboard_name: Option<String>,
+
+    /// A message that tells the user why the app is requesting access to
+    /// the device's camera.
+    #[serde(rename(serialize = "NSCameraUsageDescription"))]
+    pub camera_usage_description: Option<String>,
+    /// A message that tells the user why the app is requesting access to
+    /// the device's microphone.
+    #[serde(rename(serialize = "NSMicrophoneUsageDescription"))]
+    pub microphone_usage_description: Option<String>,
+    /// A message that tells the user why the app is requesting access to
+    /// the user's location information while the app is running in the
+    /// foreground.
+    #[serde(rename(serialize = "NSLocationWhenInUseUsageDescription"))]
+    pub location_when_in_use_usage_description: Option<String>,
+    /// A message that tells the user why the app is requesting access to
+    /// the user's location information in macOS 10.14 and earlier.
+    #[serde(rename(serialize = "NSLocationUsageDescription"))]
+    pub location_usage_description: Option<String>,
+    /// A message that tells the user why the app needs access to
+    /// Bluetooth.
+    #[serde(rename(serialize = "NSBluetoothAlwaysUsageDescription"))]
+    pub bluetooth_always_usage_description: Option<String>,
+    /// A message that tells the user why the app is requesting the ability
+    /// to connect to Bluetooth peripherals in iOS 12 and earlier.
+    #[serde(rename(serialize = "NSBluetoothPeripheralUsageDescription"))]
+    pub bluetooth_peripheral_usage_description: Option<String>,
 }
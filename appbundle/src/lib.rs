@@ -5,7 +5,6 @@ use apple_codesign::{
     BundleSigner, CodeSignatureFlags, NotarizationUpload, Notarizer, SettingsScope, SigningSettings,
 };
 use icns::{IconFamily, Image};
-use pkcs8::EncodePrivateKey;
 use plist::Value;
 use rasn_cms::{ContentInfo, SignedData};
 use std::fs::{File, OpenOptions};
@@ -149,6 +148,17 @@ impl AppBundle {
         Ok(())
     }
 
+    /// Enables a boolean entitlement like `com.apple.security.device.camera`
+    /// which is added to the entitlements of the provisioning profile.
+    pub fn add_entitlement(&mut self, key: &str) {
+        let entitlements = self
+            .entitlements
+            .get_or_insert_with(|| Value::Dictionary(Default::default()));
+        if let Some(entitlements) = entitlements.as_dictionary_mut() {
+            entitlements.insert(key.to_string(), Value::Boolean(true));
+        }
+    }
+
     pub fn add_provisioning_profile(&mut self, raw_profile: &[u8]) -> Result<()> {
         let info = rasn::der::decode::<ContentInfo>(raw_profile)
             .map_err(|err| anyhow::anyhow!("{}", err))?;
@@ -160,7 +170,7 @@ impl AppBundle {
         let dict = profile
             .as_dictionary()
             .ok_or_else(|| anyhow::anyhow!("invalid provisioning profile"))?;
-        let entitlements = dict
+        let mut entitlements = dict
             .get("Entitlements")
             .ok_or_else(|| anyhow::anyhow!("missing key Entitlements"))?
             .clone();
@@ -194,6 +204,13 @@ impl AppBundle {
             };
             self.info.bundle_identifier = Some(bundle_id);
         }
+        if let (Some(Value::Dictionary(added)), Some(entitlements)) =
+            (self.entitlements.take(), entitlements.as_dictionary_mut())
+        {
+            for (key, value) in added {
+                entitlements.insert(key, value);
+            }
+        }
         self.entitlements = Some(entitlements);
         std::fs::write(self.appdir().join("embedded.mobileprovision"), raw_profile)?;
         Ok(())
@@ -212,7 +229,7 @@ impl AppBundle {
             let mut signing_settings = SigningSettings::default();
             let cert =
                 CapturedX509Certificate::from_der(rasn::der::encode(signer.cert()).unwrap())?;
-            let key = InMemorySigningKeyPair::from_pkcs8_der(signer.key().to_pkcs8_der().unwrap())?;
+            let key = InMemorySigningKeyPair::from_pkcs8_der(signer.key())?;
             signing_settings.set_signing_key(&key, cert);
             signing_settings.chain_apple_certificates();
             signing_settings
@@ -240,7 +257,7 @@ impl AppBundle {
         let mut f = OpenOptions::new().read(true).write(true).open(path)?;
         let mut signing_settings = SigningSettings::default();
         let cert = CapturedX509Certificate::from_der(rasn::der::encode(signer.cert()).unwrap())?;
-        let key = InMemorySigningKeyPair::from_pkcs8_der(signer.key().to_pkcs8_der().unwrap())?;
+        let key = InMemorySigningKeyPair::from_pkcs8_der(signer.key())?;
         signing_settings.set_signing_key(&key, cert);
         signing_settings.chain_apple_certificates();
         signing_settings
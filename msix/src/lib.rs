@@ -139,7 +139,7 @@ impl Msix {
         };
 
         // sign zip
-        let sig = p7x::p7x(&signer, &digests);
+        let sig = p7x::p7x(&signer, &digests)?;
         let mut zip = Zip::append(path, compress)?;
         zip.create_file(
             "AppxSignature.p7x".as_ref(),
@@ -126,7 +126,7 @@ impl Default for TargetDeviceFamily {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Capability {
     #[serde(rename(deserialize = "capability"))]
     #[serde(rename(serialize = "Capability"))]
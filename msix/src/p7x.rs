@@ -4,7 +4,8 @@ use anyhow::Result;
 use byteorder::{BigEndian, ReadBytesExt};
 use rasn::prelude::*;
 use rasn_cms::pkcs7_compat::{EncapsulatedContentInfo, SignedData};
-use rasn_cms::{ContentInfo, CONTENT_SIGNED_DATA};
+use rasn_cms::{CertificateChoices, ContentInfo, CONTENT_SIGNED_DATA};
+use rasn_pkix::Certificate;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -27,13 +28,28 @@ pub fn read_p7x(path: &Path) -> Result<SignedData> {
     Ok(data)
 }
 
-pub fn p7x(signer: &Signer, digests: &Digests) -> Vec<u8> {
+/// Returns the certificates embedded in the signature of an msix.
+pub fn read_p7x_certificates(path: &Path) -> Result<Vec<Certificate>> {
+    let data = read_p7x(path)?;
+    let certificates = data
+        .certificates
+        .iter()
+        .flatten()
+        .filter_map(|cert| match cert {
+            CertificateChoices::Certificate(cert) => Some(Certificate::clone(cert)),
+            _ => None,
+        })
+        .collect();
+    Ok(certificates)
+}
+
+pub fn p7x(signer: &Signer, digests: &Digests) -> Result<Vec<u8>> {
     let payload = Payload::encode(digests);
     let encap_content_info = EncapsulatedContentInfo {
         content_type: SPC_INDIRECT_DATA_OBJID.into(),
         content: Any::new(payload),
     };
-    let signed_data = build_pkcs7(signer, encap_content_info);
+    let signed_data = build_pkcs7(signer, encap_content_info)?;
     let content_info = ContentInfo {
         content_type: CONTENT_SIGNED_DATA.into(),
         content: Any::new(rasn::der::encode(&signed_data).unwrap()),
@@ -41,7 +57,7 @@ pub fn p7x(signer: &Signer, digests: &Digests) -> Vec<u8> {
     let mut p7x = vec![];
     p7x.extend_from_slice(&P7X_MAGIC.to_be_bytes());
     p7x.extend(rasn::der::encode(&content_info).unwrap());
-    p7x
+    Ok(p7x)
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
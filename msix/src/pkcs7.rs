@@ -1,25 +1,66 @@
 use crate::Signer;
+use anyhow::Result;
 use rasn::prelude::*;
 use rasn_cms::pkcs7_compat::{EncapsulatedContentInfo, SignedData};
 use rasn_cms::{AlgorithmIdentifier, IssuerAndSerialNumber, SignerIdentifier, SignerInfo};
 use rasn_pkix::Attribute;
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha384};
+use xcommon::SignatureAlgorithm;
 
 pub const SPC_INDIRECT_DATA_OBJID: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 311, 2, 1, 4]);
 pub const SPC_SP_OPUS_INFO_OBJID: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 311, 2, 1, 12]);
 pub const SPC_SIPINFO_OBJID: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 311, 2, 1, 30]);
+pub const ECDSA_WITH_SHA256: ConstOid = ConstOid(&[1, 2, 840, 10045, 4, 3, 2]);
+pub const ECDSA_WITH_SHA384: ConstOid = ConstOid(&[1, 2, 840, 10045, 4, 3, 3]);
+pub const SHA384: ConstOid = ConstOid(&[2, 16, 840, 1, 101, 3, 4, 2, 2]);
+
+fn signature_algorithm(signer: &Signer) -> Result<AlgorithmIdentifier> {
+    Ok(match signer.algorithm() {
+        SignatureAlgorithm::RsaPkcs1Sha256 => AlgorithmIdentifier {
+            algorithm: Oid::ISO_MEMBER_BODY_US_RSADSI_PKCS1.into(),
+            parameters: Some(Any::new(vec![5, 0])),
+        },
+        SignatureAlgorithm::EcdsaP256Sha256 => AlgorithmIdentifier {
+            algorithm: ECDSA_WITH_SHA256.into(),
+            parameters: None,
+        },
+        SignatureAlgorithm::EcdsaP384Sha384 => AlgorithmIdentifier {
+            algorithm: ECDSA_WITH_SHA384.into(),
+            parameters: None,
+        },
+        algorithm => anyhow::bail!("{:?} keys can't be used to sign msix", algorithm),
+    })
+}
+
+/// Digests `data` with the hash function of the signature algorithm, so
+/// P-384 keys use SHA-384 throughout.
+fn message_digest(signer: &Signer, data: &[u8]) -> (AlgorithmIdentifier, Vec<u8>) {
+    let (algorithm, digest) = match signer.algorithm() {
+        SignatureAlgorithm::EcdsaP384Sha384 => (SHA384.into(), Sha384::digest(data).to_vec()),
+        _ => (
+            Oid::JOINT_ISO_ITU_T_COUNTRY_US_ORGANIZATION_GOV_CSOR_NIST_ALGORITHMS_HASH_SHA256
+                .into(),
+            Sha256::digest(data).to_vec(),
+        ),
+    };
+    let algorithm = AlgorithmIdentifier {
+        algorithm,
+        parameters: Some(Any::new(vec![5, 0])),
+    };
+    (algorithm, digest)
+}
 
 #[allow(clippy::mutable_key_type)]
-pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo) -> SignedData {
-    let digest = Sha256::digest(&encap_content_info.content.as_bytes()[8..]);
+pub fn build_pkcs7(
+    signer: &Signer,
+    encap_content_info: EncapsulatedContentInfo,
+) -> Result<SignedData> {
+    let signature_algorithm = signature_algorithm(signer)?;
+    let (digest_algorithm, digest) =
+        message_digest(signer, &encap_content_info.content.as_bytes()[8..]);
     let signature = signer.sign(&encap_content_info.content.as_bytes()[8..]);
     let cert = signer.cert();
 
-    let digest_algorithm = AlgorithmIdentifier {
-        algorithm:
-            Oid::JOINT_ISO_ITU_T_COUNTRY_US_ORGANIZATION_GOV_CSOR_NIST_ALGORITHMS_HASH_SHA256.into(),
-        parameters: Some(Any::new(vec![5, 0])),
-    };
     let signer_info = SignerInfo {
         version: 1.into(),
         sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
@@ -41,7 +82,7 @@ pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo)
                 r#type: Oid::ISO_MEMBER_BODY_US_RSADSI_PKCS9_MESSAGE_DIGEST.into(),
                 value: {
                     let mut digests = SetOf::default();
-                    digests.insert(OctetString::from(digest.to_vec()));
+                    digests.insert(OctetString::from(digest));
                     Any::new(rasn::der::encode(&digests).unwrap())
                 },
             });
@@ -55,17 +96,14 @@ pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo)
             });
             signed_attrs
         }),
-        signature_algorithm: AlgorithmIdentifier {
-            algorithm: Oid::ISO_MEMBER_BODY_US_RSADSI_PKCS1.into(),
-            parameters: Some(Any::new(vec![5, 0])),
-        },
+        signature_algorithm,
         signature: OctetString::from(signature.to_vec()),
         unsigned_attrs: Some({
             // TODO: 1.3.6.1.4.1.311.3.3.1 timestamp? optional?
             SetOf::default()
         }),
     };
-    SignedData {
+    Ok(SignedData {
         version: 1.into(),
         digest_algorithms: {
             let mut digest_algorithms = SetOf::default();
@@ -80,5 +118,24 @@ pub fn build_pkcs7(signer: &Signer, encap_content_info: EncapsulatedContentInfo)
             signer_infos.insert(signer_info);
             signer_infos
         },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P384_PEM: &str = include_str!("../../xcommon/assets/test_p384.pem");
+
+    #[test]
+    fn test_p384_digest() -> Result<()> {
+        let signer = Signer::new(P384_PEM)?;
+        let (algorithm, digest) = message_digest(&signer, b"hello world");
+        assert_eq!(algorithm.algorithm, ObjectIdentifier::from(SHA384));
+        assert_eq!(digest.len(), 48);
+        let (algorithm, digest) = message_digest(&Signer::new(crate::DEBUG_PEM)?, b"hello world");
+        assert_ne!(algorithm.algorithm, ObjectIdentifier::from(SHA384));
+        assert_eq!(digest.len(), 32);
+        Ok(())
     }
 }
@@ -43,15 +43,28 @@ impl<D: Download> Maven<D> {
     }
 
     pub fn resolve(&self, package: Package, version: Version) -> Result<Vec<PathBuf>> {
-        Ok(pubgrub::solver::resolve(self, package, version)
-            .map_err(|err| {
-                if let PubGrubError::NoSolution(mut tree) = err {
-                    tree.collapse_no_versions();
-                    anyhow::anyhow!("{}", DefaultStringReporter::report(&tree))
-                } else {
-                    anyhow::anyhow!("{:?}", err)
-                }
-            })?
+        let solution = pubgrub::solver::resolve(self, package, version).map_err(report)?;
+        Ok(self.artifacts(solution))
+    }
+
+    /// Resolves multiple packages at once, so a dependency shared by them is
+    /// only selected in a single version.
+    pub fn resolve_all(&self, packages: Vec<(Package, Version)>) -> Result<Vec<PathBuf>> {
+        let root = Root {
+            maven: self,
+            packages,
+        };
+        let solution =
+            pubgrub::solver::resolve(&root, root_package(), root_version()).map_err(report)?;
+        Ok(self.artifacts(
+            solution
+                .into_iter()
+                .filter(|(package, _)| *package != root_package()),
+        ))
+    }
+
+    fn artifacts(&self, solution: impl IntoIterator<Item = (Package, Version)>) -> Vec<PathBuf> {
+        solution
             .into_iter()
             .filter_map(
                 |(package, version)| match self.package(&package, &version) {
@@ -75,7 +88,7 @@ impl<D: Download> Maven<D> {
                     }
                 },
             )
-            .collect())
+            .collect()
     }
 
     pub fn package(&self, package: &Package, version: &Version) -> Result<PathBuf> {
@@ -206,3 +219,63 @@ impl<D: Download> DependencyProvider<Package, Version> for Maven<D> {
         Ok(Dependencies::Known(deps))
     }
 }
+
+fn report(err: PubGrubError<Package, Version>) -> anyhow::Error {
+    if let PubGrubError::NoSolution(mut tree) = err {
+        tree.collapse_no_versions();
+        anyhow::anyhow!("{}", DefaultStringReporter::report(&tree))
+    } else {
+        anyhow::anyhow!("{:?}", err)
+    }
+}
+
+/// Virtual package depending on the packages passed to [`Maven::resolve_all`].
+struct Root<'a, D: Download> {
+    maven: &'a Maven<D>,
+    packages: Vec<(Package, Version)>,
+}
+
+fn root_package() -> Package {
+    Package::new("", "")
+}
+
+fn root_version() -> Version {
+    Version {
+        major: 0,
+        minor: 0,
+        patch: 0,
+        suffix: None,
+    }
+}
+
+impl<'a, D: Download> DependencyProvider<Package, Version> for Root<'a, D> {
+    fn choose_package_version<T: Borrow<Package>, U: Borrow<Range<Version>>>(
+        &self,
+        potential_packages: impl Iterator<Item = (T, U)>,
+    ) -> Result<(T, Option<Version>), Box<dyn Error>> {
+        let mut potential_packages = potential_packages.peekable();
+        if let Some((package, _)) = potential_packages.peek() {
+            if *package.borrow() == root_package() {
+                let (package, _) = potential_packages.next().unwrap();
+                return Ok((package, Some(root_version())));
+            }
+        }
+        self.maven.choose_package_version(potential_packages)
+    }
+
+    fn get_dependencies(
+        &self,
+        package: &Package,
+        version: &Version,
+    ) -> Result<Dependencies<Package, Version>, Box<dyn Error>> {
+        if *package == root_package() {
+            let deps = self
+                .packages
+                .iter()
+                .map(|(package, version)| (package.clone(), Range::exact(version.clone())))
+                .collect();
+            return Ok(Dependencies::Known(deps));
+        }
+        self.maven.get_dependencies(package, version)
+    }
+}
@@ -0,0 +1,324 @@
+use crate::config::ANDROID_MIN_SDK_VERSION;
+use crate::download::DownloadManager;
+use crate::{task, BuildEnv, Opt};
+use anyhow::Result;
+use apk::{Aar, Symbols};
+use mvn::{Package, Version};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Maven dependencies of an android app which doesn't use flutter.
+#[derive(Default)]
+pub struct AndroidLibraries {
+    /// Android libraries unpacked into `<platform_dir>/aar`.
+    pub aars: Vec<Aar>,
+    /// Plain java libraries.
+    pub jars: Vec<PathBuf>,
+}
+
+impl AndroidLibraries {
+    pub fn is_empty(&self) -> bool {
+        self.aars.is_empty() && self.jars.is_empty()
+    }
+}
+
+impl<'a> DownloadManager<'a> {
+    /// Resolves the `android.dependencies` of the manifest and unpacks the
+    /// android libraries.
+    pub fn android_dependencies(&self) -> Result<AndroidLibraries> {
+        let mut packages = vec![];
+        for dep in self.env().manifest().android_dependencies() {
+            packages.push(parse_dependency(dep)?);
+        }
+        let mut libraries = AndroidLibraries::default();
+        if packages.is_empty() {
+            return Ok(libraries);
+        }
+        let aar_dir = self.env().platform_dir().join("aar");
+        for path in self.maven()?.resolve_all(packages)? {
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("aar") => {
+                    let name = path.file_stem().unwrap();
+                    libraries
+                        .aars
+                        .push(Aar::extract(&path, &aar_dir.join(name))?);
+                }
+                Some("jar") => libraries.jars.push(path),
+                _ => {}
+            }
+        }
+        Ok(libraries)
+    }
+}
+
+/// Parses maven coordinates like `androidx.core:core:1.7.0`.
+fn parse_dependency(dep: &str) -> Result<(Package, Version)> {
+    let parts = dep.split(':').collect::<Vec<_>>();
+    if let [group, name, version] = parts.as_slice() {
+        Ok((Package::new(group, name), version.parse()?))
+    } else {
+        anyhow::bail!("invalid dependency {}, expected `group:name:version`", dep);
+    }
+}
+
+/// Returns the resource ids of the previous build, which are passed to the
+/// apk so the ids of existing resources don't change.
+pub fn stable_ids(env: &BuildEnv) -> Result<Symbols> {
+    let path = env.android_stable_ids();
+    if !path.exists() {
+        return Ok(Symbols::default());
+    }
+    let ids = std::fs::read_to_string(&path)?;
+    Symbols::parse_stable_ids(&ids).map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))
+}
+
+/// Writes the ids of the app resources as rust constants and updates the
+/// stable ids. The files are only touched when the ids change, so cargo
+/// doesn't rebuild the crate.
+pub fn write_resource_ids(env: &BuildEnv, libraries: &AndroidLibraries) -> Result<()> {
+    let manifest = env.manifest().android();
+    let symbols = apk::resource_symbols(
+        manifest,
+        env.icon(),
+        env.android_adaptive_icon(),
+        env.android_res(),
+        &[],
+        &libraries.aars,
+        &stable_ids(env)?,
+        &env.android_jar(),
+    )?;
+    let package = manifest.package.as_deref().unwrap_or_default();
+    write_if_changed(&env.android_stable_ids(), &symbols.to_stable_ids(package))?;
+    write_if_changed(&env.android_resource_ids(), &symbols.to_rust())?;
+    Ok(())
+}
+
+fn write_if_changed(path: &Path, contents: &str) -> Result<()> {
+    if std::fs::read_to_string(path).ok().as_deref() != Some(contents) {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+/// Compiles the java and kotlin sources of the app together with the `R`
+/// classes of the app and its android libraries and dexes them with the
+/// libraries. `proguard_rules` keep the classes referenced by the manifest
+/// and the xml resources. Returns `classes.dex` followed by `classes2.dex` and so on when
+/// the classes don't fit into a single dex.
+pub fn build_classes_dex(
+    env: &BuildEnv,
+    r8: &Path,
+    symbols: &Symbols,
+    proguard_rules: &str,
+    libraries: &AndroidLibraries,
+) -> Result<Vec<PathBuf>> {
+    let platform_dir = env.platform_dir();
+    let android_jar = env.android_jar();
+    let mut jars = libraries.jars.clone();
+    for aar in &libraries.aars {
+        jars.extend(aar.jars()?);
+    }
+    let r_dir = platform_dir.join("r");
+    let java_dir = platform_dir.join("java");
+    let kotlin_dir = platform_dir.join("kotlin");
+    for dir in [&r_dir, &java_dir, &kotlin_dir] {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+    }
+
+    // generate R classes
+    let mut packages = BTreeSet::new();
+    packages.extend(env.manifest().android().package.as_deref());
+    packages.extend(libraries.aars.iter().map(|aar| aar.package()));
+    let mut r_sources = vec![];
+    for package in packages {
+        let dir = package.split('.').fold(r_dir.clone(), |dir, p| dir.join(p));
+        std::fs::create_dir_all(&dir)?;
+        let source = dir.join("R.java");
+        std::fs::write(&source, symbols.to_java(package))?;
+        r_sources.push(source);
+    }
+    let mut java_sources = vec![];
+    let mut kotlin_sources = vec![];
+    if let Some(dir) = env.android_java() {
+        source_files(dir, &mut java_sources, &mut kotlin_sources)?;
+    }
+
+    // build kotlin classes
+    let mut classpath = jars.clone();
+    classpath.push(android_jar.clone());
+    if !kotlin_sources.is_empty() {
+        let kotlinc = which::which("kotlinc")
+            .map_err(|_| anyhow::anyhow!("kotlinc is required to compile kotlin sources"))?;
+        // java sources are passed along so kotlin code can reference them
+        let mut cmd = Command::new(&kotlinc);
+        cmd.arg("-classpath")
+            .arg(join_paths(&classpath))
+            .arg("-d")
+            .arg(&kotlin_dir)
+            .args(&kotlin_sources)
+            .args(&java_sources)
+            .args(&r_sources);
+        task::run(cmd, env.verbose())?;
+        jars.push(kotlin_stdlib(&kotlinc)?);
+        classpath.push(kotlin_dir.clone());
+    }
+
+    // build java classes
+    let mut cmd = Command::new("javac");
+    cmd.arg("--class-path")
+        .arg(join_paths(&classpath))
+        .args(&r_sources)
+        .args(&java_sources)
+        .arg("-d")
+        .arg(&java_dir);
+    task::run(cmd, env.verbose())?;
+    let mut classes = vec![];
+    for dir in [&java_dir, &kotlin_dir] {
+        if dir.exists() {
+            class_files(dir, dir, &mut classes)?;
+        }
+    }
+
+    // build classes.dex
+    let mut rules = String::new();
+    // classes of the app may only be referenced by the manifest or through
+    // jni, so they are kept. `R` classes are shrunk.
+    for (name, _) in &classes {
+        let class = name.rsplit('.').next().unwrap_or_default();
+        if class != "R" && !class.starts_with("R$") {
+            rules.push_str(&format!("-keep class {} {{ *; }}\n", name));
+        }
+    }
+    rules.push_str(proguard_rules);
+    for aar in &libraries.aars {
+        rules.push_str(&aar.proguard_rules()?);
+    }
+    let pg = platform_dir.join("proguard-rules.pro");
+    std::fs::write(&pg, rules)?;
+    let dex_dir = platform_dir.join("dex");
+    if dex_dir.exists() {
+        std::fs::remove_dir_all(&dex_dir)?;
+    }
+    std::fs::create_dir_all(&dex_dir)?;
+    let mut java = Command::new("java");
+    java.arg("-cp")
+        .arg(r8)
+        .arg("com.android.tools.r8.R8")
+        .args(&jars)
+        .args(classes.iter().map(|(_, path)| path))
+        .arg("--lib")
+        .arg(android_jar)
+        .arg("--output")
+        .arg(&dex_dir)
+        .arg("--pg-conf")
+        .arg(pg);
+    // r8 only splits the classes into multiple dex files when targeting
+    // the native multidex support of api 21 and later
+    let min_sdk = env
+        .manifest()
+        .android()
+        .sdk
+        .min_sdk_version
+        .unwrap_or(ANDROID_MIN_SDK_VERSION);
+    java.arg("--min-api").arg(min_sdk.to_string());
+    // r8 defaults to release mode, which shrinks and optimizes the classes
+    match env.target().opt() {
+        Opt::Debug => java.arg("--debug"),
+        Opt::Release => java.arg("--release"),
+    };
+    task::run(java, env.verbose())?;
+
+    let mut dex = vec![];
+    for i in 1.. {
+        let name = if i == 1 {
+            "classes.dex".to_string()
+        } else {
+            format!("classes{}.dex", i)
+        };
+        let path = dex_dir.join(name);
+        if !path.exists() {
+            break;
+        }
+        dex.push(path);
+    }
+    anyhow::ensure!(!dex.is_empty(), "r8 didn't output a classes.dex");
+    Ok(dex)
+}
+
+/// Returns if the app has java or kotlin sources.
+pub fn has_sources(env: &BuildEnv) -> Result<bool> {
+    let mut java = vec![];
+    let mut kotlin = vec![];
+    if let Some(dir) = env.android_java() {
+        source_files(dir, &mut java, &mut kotlin)?;
+    }
+    Ok(!java.is_empty() || !kotlin.is_empty())
+}
+
+fn source_files(dir: &Path, java: &mut Vec<PathBuf>, kotlin: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            source_files(&path, java, kotlin)?;
+        } else if path.extension() == Some("java".as_ref()) {
+            java.push(path);
+        } else if path.extension() == Some("kt".as_ref()) {
+            kotlin.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Collects the class files in `dir` and their binary names like
+/// `com.example.Foo$Bar` relative to the output directory `root`.
+fn class_files(root: &Path, dir: &Path, classes: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            class_files(root, &path, classes)?;
+        } else if path.extension() == Some("class".as_ref()) {
+            let name = path
+                .strip_prefix(root)?
+                .with_extension("")
+                .iter()
+                .map(|c| c.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(".");
+            classes.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+fn join_paths(paths: &[PathBuf]) -> String {
+    let separator = if cfg!(windows) { ";" } else { ":" };
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// The kotlin runtime isn't part of android, so it is dexed with the app.
+/// kotlinc lives in the `bin` directory of the compiler distribution.
+fn kotlin_stdlib(kotlinc: &Path) -> Result<PathBuf> {
+    let kotlinc = std::fs::canonicalize(kotlinc)?;
+    let stdlib = kotlinc
+        .parent()
+        .and_then(|bin| bin.parent())
+        .map(|home| home.join("lib").join("kotlin-stdlib.jar"))
+        .filter(|stdlib| stdlib.exists())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "failed to locate kotlin-stdlib.jar next to {}",
+                kotlinc.display()
+            )
+        })?;
+    Ok(stdlib)
+}
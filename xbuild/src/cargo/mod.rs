@@ -179,7 +179,7 @@ impl CargoBuild {
             target_sdk_version
         );
         self.use_ld("lld");
-        self.add_link_arg("--target=aarch64-linux-android");
+        self.add_link_arg(&format!("--target={}", self.target.ndk_clang_triple()));
         self.add_link_arg(&format!("-B{}", sdk_lib_dir.display()));
         self.add_link_arg(&format!("-L{}", sdk_lib_dir.display()));
         self.add_link_arg(&format!("-L{}", lib_dir.display()));
@@ -337,6 +337,10 @@ impl CargoBuild {
         self.cmd.arg(arg);
     }
 
+    pub fn env(&mut self, name: &str, value: &str) {
+        self.cmd.env(name, value);
+    }
+
     pub fn exec(mut self) -> Result<()> {
         self.cargo_target_env("RUSTFLAGS", &self.rust_flags.clone());
         self.cc_triple_env("CFLAGS", &self.c_flags.clone());
@@ -1,10 +1,11 @@
+use crate::android::AndroidLibraries;
 use crate::cargo::CrateType;
 use crate::download::DownloadManager;
 use crate::flutter::depfile::depfile_is_dirty;
 use crate::task::TaskRunner;
 use crate::{BuildEnv, Format, Opt, Platform};
 use anyhow::Result;
-use apk::Apk;
+use apk::{Aab, Aar, AdaptiveIcon, Apk, Symbols, Target};
 use appbundle::AppBundle;
 use appimage::AppImage;
 use msix::Msix;
@@ -18,7 +19,7 @@ pub fn build(env: &BuildEnv) -> Result<()> {
     let mut num_tasks = 8;
     let mut build_classes_dex = false;
     if env.target().platform() == Platform::Android {
-        num_tasks += 1;
+        num_tasks += 2;
         if !platform_dir.join("classes.dex").exists() {
             build_classes_dex = true;
         }
@@ -63,6 +64,30 @@ pub fn build(env: &BuildEnv) -> Result<()> {
         }
     }
 
+    let mut android_libraries = AndroidLibraries::default();
+    if env.target().platform() == Platform::Android {
+        runner.start_task("Build classes.dex");
+        if let Some(flutter) = env.flutter() {
+            if !platform_dir.join("classes.dex").exists() {
+                let r8 = manager.r8()?;
+                let deps = manager.flutter_embedding()?;
+                flutter.build_classes_dex(env, &r8, deps)?;
+                runner.end_task();
+            }
+        } else if !env.manifest().android_dependencies().is_empty() {
+            // the R classes are generated once the resources are compiled
+            android_libraries = manager.android_dependencies()?;
+            runner.end_task();
+        }
+    }
+
+    runner.start_task("Generate resource ids");
+    if env.target().platform() == Platform::Android {
+        // native code is compiled against the ids before the app is packaged
+        crate::android::write_resource_ids(env, &android_libraries)?;
+        runner.end_task();
+    }
+
     runner.start_task("Build rust");
     let bin_target = env.target().platform() != Platform::Android
         && (env.flutter().is_none() || env.target().platform() != Platform::Ios);
@@ -79,18 +104,6 @@ pub fn build(env: &BuildEnv) -> Result<()> {
         runner.end_verbose_task();
     }
 
-    if env.target().platform() == Platform::Android {
-        runner.start_task("Build classes.dex");
-        if let Some(flutter) = env.flutter() {
-            if !platform_dir.join("classes.dex").exists() {
-                let r8 = manager.r8()?;
-                let deps = manager.flutter_embedding()?;
-                flutter.build_classes_dex(env, &r8, deps)?;
-                runner.end_task();
-            }
-        }
-    }
-
     runner.start_task("Build flutter assets");
     let flutter_assets = env.build_dir().join("flutter_assets");
     if let Some(flutter) = env.flutter() {
@@ -206,75 +219,56 @@ pub fn build(env: &BuildEnv) -> Result<()> {
         }
         Platform::Android => {
             let out = platform_dir.join(format!("{}.{}", env.name(), env.target().format()));
-
-            let mut apk = Apk::new(
-                out,
-                env.manifest().android().clone(),
-                env.target().opt() != Opt::Debug,
-            )?;
-            apk.add_res(env.icon(), &env.android_jar())?;
-            if let Some(flutter) = env.flutter() {
-                for target in env.target().compile_targets() {
-                    apk.add_lib(
-                        target.android_abi(),
-                        &flutter.engine_dir(target)?.join("libflutter.so"),
-                    )?;
-                }
-                apk.add_dex(&platform_dir.join("classes.dex"))?;
-                apk.add_directory(&flutter_assets, &Path::new("assets").join("flutter_assets"))?;
-                apk.add_file(
-                    &flutter.vm_snapshot_data()?,
-                    &Path::new("assets")
-                        .join("flutter_assets")
-                        .join("vm_snapshot_data"),
-                    ZipFileOptions::Compressed,
+            let manifest = env.manifest().android().clone();
+            let compress = env.target().opt() != Opt::Debug;
+            if env.target().format() == Format::Aab {
+                let mut aab = Aab::new(out, manifest, compress)?;
+                add_android_files(
+                    &mut aab,
+                    env,
+                    &manager,
+                    &android_libraries,
+                    &platform_dir,
+                    &flutter_assets,
+                    has_lib,
                 )?;
-                apk.add_file(
-                    &flutter.isolate_snapshot_data()?,
-                    &Path::new("assets")
-                        .join("flutter_assets")
-                        .join("isolate_snapshot_data"),
-                    ZipFileOptions::Compressed,
-                )?;
-                match env.target().opt() {
-                    Opt::Debug => {
-                        apk.add_file(
-                            &platform_dir.join("kernel_blob.bin"),
-                            &Path::new("assets")
-                                .join("flutter_assets")
-                                .join("kernel_blob.bin"),
-                            ZipFileOptions::Compressed,
-                        )?;
-                    }
-                    Opt::Release => {
-                        for target in env.target().compile_targets() {
-                            apk.add_lib(
-                                target.android_abi(),
-                                &platform_dir
-                                    .join(target.arch().to_string())
-                                    .join("libapp.so"),
-                            )?;
-                        }
-                    }
+                aab.finish()?;
+            } else {
+                let mut apk = Apk::new(out, manifest, compress)?;
+                // debug builds are installed with `adb install --incremental`
+                apk.set_idsig(env.target().opt() == Opt::Debug);
+                if env.manifest().android_uncompressed_native_libs() {
+                    // 16k alignment works for both 4k and 16k page sizes
+                    apk.set_uncompressed_libs(16384);
                 }
-            }
-
-            if has_lib {
-                for target in env.target().compile_targets() {
-                    let arch_dir = platform_dir.join(target.arch().to_string());
-                    let lib =
-                        env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Cdylib)?;
-                    apk.add_lib(target.android_abi(), &lib)?;
+                apk.set_splits(env.manifest().android_splits());
+                add_android_files(
+                    &mut apk,
+                    env,
+                    &manager,
+                    &android_libraries,
+                    &platform_dir,
+                    &flutter_assets,
+                    has_lib,
+                )?;
+                let signer = env.target().signer().cloned();
+                if env.target().previous_signers().is_empty() {
+                    apk.finish(signer)?;
+                } else {
+                    let mut signers = env.target().previous_signers().to_vec();
+                    signers.extend(signer);
+                    apk.finish_with_lineage(&signers)?;
                 }
             }
-
-            apk.finish(env.target().signer().cloned())?;
         }
         Platform::Macos => {
             let target = env.target().compile_targets().next().unwrap();
             let arch_dir = platform_dir.join(target.arch().to_string());
 
             let mut app = AppBundle::new(&arch_dir, env.manifest().macos().clone())?;
+            for entitlement in env.manifest().entitlements(Platform::Macos) {
+                app.add_entitlement(entitlement);
+            }
             if let Some(icon) = env.icon() {
                 app.add_icon(icon)?;
             }
@@ -347,6 +341,9 @@ pub fn build(env: &BuildEnv) -> Result<()> {
             let arch_dir = platform_dir.join(target.arch().to_string());
             std::fs::create_dir_all(&arch_dir)?;
             let mut app = AppBundle::new(&arch_dir, env.manifest().ios().clone())?;
+            for entitlement in env.manifest().entitlements(Platform::Ios) {
+                app.add_entitlement(entitlement);
+            }
             if let Some(icon) = env.icon() {
                 app.add_icon(icon)?;
             }
@@ -471,3 +468,167 @@ pub fn build(env: &BuildEnv) -> Result<()> {
 
     Ok(())
 }
+
+/// Files shared between apks and app bundles.
+trait AndroidPackage {
+    fn set_adaptive_icon(&mut self, icon: AdaptiveIcon);
+    fn add_library(&mut self, aar: Aar);
+    fn set_stable_ids(&mut self, ids: Symbols);
+    fn add_res(&mut self, icon: Option<&Path>, res: Option<&Path>, android: &Path) -> Result<()>;
+    fn symbols(&self) -> &Symbols;
+    fn proguard_rules(&self) -> &str;
+    fn add_dex(&mut self, dex: &Path) -> Result<()>;
+    fn add_lib(&mut self, target: Target, path: &Path) -> Result<()>;
+    fn add_file(&mut self, source: &Path, dest: &Path, opts: ZipFileOptions) -> Result<()>;
+    fn add_directory(&mut self, source: &Path, dest: &Path) -> Result<()>;
+}
+
+/// Forwards the methods of [`AndroidPackage`] to the inherent methods of
+/// `Apk` and `Aab`, which share their signatures.
+macro_rules! impl_android_package {
+    ($ty:ident) => {
+        impl AndroidPackage for $ty {
+            fn set_adaptive_icon(&mut self, icon: AdaptiveIcon) {
+                $ty::set_adaptive_icon(self, icon)
+            }
+
+            fn add_library(&mut self, aar: Aar) {
+                $ty::add_library(self, aar)
+            }
+
+            fn set_stable_ids(&mut self, ids: Symbols) {
+                $ty::set_stable_ids(self, ids)
+            }
+
+            fn add_res(
+                &mut self,
+                icon: Option<&Path>,
+                res: Option<&Path>,
+                android: &Path,
+            ) -> Result<()> {
+                $ty::add_res(self, icon, res, android)
+            }
+
+            fn symbols(&self) -> &Symbols {
+                $ty::symbols(self)
+            }
+
+            fn proguard_rules(&self) -> &str {
+                $ty::proguard_rules(self)
+            }
+
+            fn add_dex(&mut self, dex: &Path) -> Result<()> {
+                $ty::add_dex(self, dex)
+            }
+
+            fn add_lib(&mut self, target: Target, path: &Path) -> Result<()> {
+                $ty::add_lib(self, target, path)
+            }
+
+            fn add_file(&mut self, source: &Path, dest: &Path, opts: ZipFileOptions) -> Result<()> {
+                $ty::add_file(self, source, dest, opts)
+            }
+
+            fn add_directory(&mut self, source: &Path, dest: &Path) -> Result<()> {
+                $ty::add_directory(self, source, dest)
+            }
+        }
+    };
+}
+
+impl_android_package!(Apk);
+impl_android_package!(Aab);
+
+fn add_android_files(
+    pkg: &mut impl AndroidPackage,
+    env: &BuildEnv,
+    manager: &DownloadManager,
+    libraries: &AndroidLibraries,
+    platform_dir: &Path,
+    flutter_assets: &Path,
+    has_lib: bool,
+) -> Result<()> {
+    if let Some(icon) = env.android_adaptive_icon() {
+        pkg.set_adaptive_icon(icon.clone());
+    }
+    for aar in &libraries.aars {
+        pkg.add_library(aar.clone());
+    }
+    // written by `write_resource_ids`, so the ids match the rust constants
+    pkg.set_stable_ids(crate::android::stable_ids(env)?);
+    pkg.add_res(env.icon(), env.android_res(), &env.android_jar())?;
+    if env.flutter().is_none() && (!libraries.is_empty() || crate::android::has_sources(env)?) {
+        let r8 = manager.r8()?;
+        for dex in crate::android::build_classes_dex(
+            env,
+            &r8,
+            pkg.symbols(),
+            pkg.proguard_rules(),
+            libraries,
+        )? {
+            pkg.add_dex(&dex)?;
+        }
+        for target in env.target().compile_targets() {
+            for aar in &libraries.aars {
+                for lib in aar.libs(target.android_abi())? {
+                    pkg.add_lib(target.android_abi(), &lib)?;
+                }
+            }
+        }
+    }
+    if let Some(flutter) = env.flutter() {
+        for target in env.target().compile_targets() {
+            pkg.add_lib(
+                target.android_abi(),
+                &flutter.engine_dir(target)?.join("libflutter.so"),
+            )?;
+        }
+        pkg.add_dex(&platform_dir.join("classes.dex"))?;
+        pkg.add_directory(flutter_assets, &Path::new("assets").join("flutter_assets"))?;
+        pkg.add_file(
+            &flutter.vm_snapshot_data()?,
+            &Path::new("assets")
+                .join("flutter_assets")
+                .join("vm_snapshot_data"),
+            ZipFileOptions::Compressed,
+        )?;
+        pkg.add_file(
+            &flutter.isolate_snapshot_data()?,
+            &Path::new("assets")
+                .join("flutter_assets")
+                .join("isolate_snapshot_data"),
+            ZipFileOptions::Compressed,
+        )?;
+        match env.target().opt() {
+            Opt::Debug => {
+                pkg.add_file(
+                    &platform_dir.join("kernel_blob.bin"),
+                    &Path::new("assets")
+                        .join("flutter_assets")
+                        .join("kernel_blob.bin"),
+                    ZipFileOptions::Compressed,
+                )?;
+            }
+            Opt::Release => {
+                for target in env.target().compile_targets() {
+                    pkg.add_lib(
+                        target.android_abi(),
+                        &platform_dir
+                            .join(target.arch().to_string())
+                            .join("libapp.so"),
+                    )?;
+                }
+            }
+        }
+    }
+
+    if has_lib {
+        for target in env.target().compile_targets() {
+            let arch_dir = platform_dir.join(target.arch().to_string());
+            let lib = env.cargo_artefact(&arch_dir.join("cargo"), target, CrateType::Cdylib)?;
+            pkg.add_lib(target.android_abi(), &lib)?;
+        }
+    }
+
+    Ok(())
+}
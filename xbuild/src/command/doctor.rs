@@ -38,6 +38,7 @@ impl Default for Doctor {
                         Check::new("adb", Some(VersionCheck::new("--version", 0, 4))),
                         Check::new("javac", Some(VersionCheck::new("--version", 0, 1))),
                         Check::new("java", Some(VersionCheck::new("--version", 0, 1))),
+                        Check::new("kotlinc", None),
                     ],
                 },
                 Group {
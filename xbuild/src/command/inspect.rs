@@ -0,0 +1,286 @@
+use crate::Format;
+use anyhow::Result;
+use apk::{Aab, Apk};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use xcommon::{Certificate, CertificateInfo};
+use zip::{CompressionMethod, ZipArchive};
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    format: String,
+    size: u64,
+    /// Bundle identifier of an app bundle.
+    identifier: Option<String>,
+    /// Decoded `AndroidManifest.xml`, `AppxManifest.xml` or `Info.plist`.
+    manifest: Option<String>,
+    certificates: Vec<CertificateSummary>,
+    files: Vec<FileSummary>,
+    /// Native libraries by android abi.
+    libs: BTreeMap<String, Vec<String>>,
+    warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CertificateSummary {
+    /// Signature scheme like `v2` or `p7x`.
+    scheme: String,
+    subject: String,
+    issuer: String,
+    serial: String,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FileSummary {
+    name: String,
+    compressed: bool,
+    size: u64,
+    compressed_size: u64,
+    /// Offset of the file data inside the archive.
+    offset: Option<u64>,
+    /// Largest power of two up to 4096 the offset is a multiple of.
+    alignment: Option<u64>,
+}
+
+pub fn inspect(path: &Path, json: bool) -> Result<()> {
+    let format = Format::from_path(path)?;
+    let mut summary = Summary {
+        format: format.to_string(),
+        ..Default::default()
+    };
+    match format {
+        Format::Apk => {
+            summary.manifest = Some(Apk::manifest(path)?);
+            match Apk::verify(path) {
+                Ok(verified) => {
+                    summary.add_certificates("v1", &verified.v1)?;
+                    summary.add_certificates("v2", &verified.v2)?;
+                    summary.add_certificates("v3", &verified.v3)?;
+                    summary.add_certificates("lineage", &verified.lineage)?;
+                }
+                Err(err) => summary.warnings.push(format!("invalid signature: {}", err)),
+            }
+            if let Err(err) = Apk::verify_alignment(path, 4096) {
+                summary.warnings.push(format!("misaligned: {}", err));
+            }
+            summary.add_zip_files(path)?;
+        }
+        Format::Aab => {
+            summary.manifest = Some(Aab::manifest(path)?);
+            match Aab::verify(path) {
+                Ok(certificates) => summary.add_certificates("v1", &certificates)?,
+                Err(err) => summary.warnings.push(format!("invalid signature: {}", err)),
+            }
+            summary.add_zip_files(path)?;
+        }
+        Format::Msix => {
+            let manifest = xcommon::extract_zip_file(path, "AppxManifest.xml")?;
+            summary.manifest = Some(String::from_utf8(manifest)?);
+            match msix::p7x::read_p7x_certificates(path) {
+                Ok(certificates) => summary.add_certificates("p7x", &certificates)?,
+                Err(err) => summary.warnings.push(format!("invalid signature: {}", err)),
+            }
+            summary.add_zip_files(path)?;
+        }
+        Format::Ipa => {
+            summary.add_zip_files(path)?;
+            let info = summary
+                .files
+                .iter()
+                .map(|file| file.name.as_str())
+                .find(|name| {
+                    let parts = name.split('/').collect::<Vec<_>>();
+                    match parts.as_slice() {
+                        ["Payload", app, "Info.plist"] => app.ends_with(".app"),
+                        _ => false,
+                    }
+                })
+                .map(|name| name.to_string());
+            if let Some(info) = info {
+                let info = xcommon::extract_zip_file(path, &info)?;
+                summary.manifest = Some(plist_to_string(info));
+            } else {
+                summary.warnings.push("missing Info.plist".into());
+            }
+        }
+        Format::Appbundle => {
+            summary.identifier = Some(appbundle::app_bundle_identifier(path)?);
+            summary.manifest = Some(plist_to_string(std::fs::read(path.join("Info.plist"))?));
+            summary.add_dir_files(path, path)?;
+        }
+        Format::Appdir => {
+            summary.add_dir_files(path, path)?;
+        }
+        Format::Dmg => {
+            let dmg = dmg_oxide::DmgReader::open(path)?;
+            for i in 0..dmg.plist().partitions().len() {
+                let table = dmg.partition_table(i)?;
+                summary.files.push(FileSummary {
+                    name: dmg.partition_name(i).to_string(),
+                    compressed: true,
+                    size: table.sector_count * 512,
+                    compressed_size: table
+                        .chunks
+                        .iter()
+                        .map(|chunk| chunk.compressed_length)
+                        .sum(),
+                    offset: None,
+                    alignment: None,
+                });
+            }
+        }
+        Format::Appimage => {
+            let mut magic = [0; 11];
+            File::open(path)?.read_exact(&mut magic)?;
+            anyhow::ensure!(
+                &magic[..4] == b"\x7fELF" && &magic[8..] == b"AI\x02",
+                "{} is not a type 2 AppImage",
+                path.display()
+            );
+        }
+    }
+    summary.size = if path.is_dir() {
+        summary.files.iter().map(|file| file.size).sum()
+    } else {
+        std::fs::metadata(path)?.len()
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        summary.print();
+    }
+    Ok(())
+}
+
+impl Summary {
+    fn add_certificates(&mut self, scheme: &str, certificates: &[Certificate]) -> Result<()> {
+        for cert in certificates {
+            let info = CertificateInfo::new(cert)?;
+            self.certificates.push(CertificateSummary {
+                scheme: scheme.to_string(),
+                subject: info.subject,
+                issuer: info.issuer,
+                serial: info.serial,
+                sha256: info.sha256,
+            });
+        }
+        Ok(())
+    }
+
+    fn add_zip_files(&mut self, path: &Path) -> Result<()> {
+        let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        for i in 0..zip.len() {
+            let f = zip.by_index(i)?;
+            if f.is_dir() {
+                continue;
+            }
+            let offset = f.data_start();
+            self.add_lib(f.name());
+            self.files.push(FileSummary {
+                name: f.name().to_string(),
+                compressed: f.compression() != CompressionMethod::Stored,
+                size: f.size(),
+                compressed_size: f.compressed_size(),
+                offset: Some(offset),
+                alignment: Some(1 << offset.trailing_zeros().min(12)),
+            });
+        }
+        Ok(())
+    }
+
+    fn add_dir_files(&mut self, root: &Path, dir: &Path) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                self.add_dir_files(root, &path)?;
+                continue;
+            }
+            let size = entry.metadata()?.len();
+            self.files.push(FileSummary {
+                name: path.strip_prefix(root)?.display().to_string(),
+                compressed: false,
+                size,
+                compressed_size: size,
+                offset: None,
+                alignment: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Records libraries like `lib/arm64-v8a/libfoo.so` or
+    /// `base/lib/arm64-v8a/libfoo.so`.
+    fn add_lib(&mut self, name: &str) {
+        let parts = name.split('/').collect::<Vec<_>>();
+        if let [.., "lib", abi, lib] = parts.as_slice() {
+            if lib.ends_with(".so") {
+                self.libs
+                    .entry(abi.to_string())
+                    .or_default()
+                    .push(lib.to_string());
+            }
+        }
+    }
+
+    fn print(&self) {
+        println!("{:20}{}", "format", self.format);
+        println!("{:20}{}", "size", self.size);
+        if let Some(identifier) = self.identifier.as_ref() {
+            println!("{:20}{}", "identifier", identifier);
+        }
+        for cert in &self.certificates {
+            println!(
+                "{:20}{}",
+                format!("certificate ({})", cert.scheme),
+                cert.subject
+            );
+            println!("{:20}issuer {}", "", cert.issuer);
+            println!("{:20}serial {}", "", cert.serial);
+            println!("{:20}sha256 {}", "", cert.sha256);
+        }
+        for (abi, libs) in &self.libs {
+            println!("{:20}{}", format!("libs ({})", abi), libs.join(" "));
+        }
+        for warning in &self.warnings {
+            println!("{:20}{}", "warning", warning);
+        }
+        if !self.files.is_empty() {
+            println!();
+            println!(
+                "{:12}{:>12}{:>12}{:>12}{:>8}  name",
+                "method", "size", "compressed", "offset", "align"
+            );
+            for file in &self.files {
+                let method = if file.compressed {
+                    "deflated"
+                } else {
+                    "stored"
+                };
+                let offset = file.offset.map(|o| o.to_string()).unwrap_or_default();
+                let alignment = file.alignment.map(|a| a.to_string()).unwrap_or_default();
+                println!(
+                    "{:12}{:>12}{:>12}{:>12}{:>8}  {}",
+                    method, file.size, file.compressed_size, offset, alignment, file.name
+                );
+            }
+        }
+        if let Some(manifest) = self.manifest.as_ref() {
+            println!();
+            println!("{}", manifest.trim_end());
+        }
+    }
+}
+
+/// Returns the xml of an `Info.plist`, binary plists are not decoded.
+fn plist_to_string(plist: Vec<u8>) -> String {
+    if plist.starts_with(b"bplist") {
+        "<binary plist>".into()
+    } else {
+        String::from_utf8_lossy(&plist).into_owned()
+    }
+}
@@ -8,11 +8,13 @@ use std::process::Command;
 //mod attach;
 mod build;
 mod doctor;
+mod inspect;
 mod new;
 
 //pub use attach::attach;
 pub use build::build;
 pub use doctor::doctor;
+pub use inspect::inspect;
 pub use new::new;
 
 pub fn devices() -> Result<()> {
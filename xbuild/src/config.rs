@@ -1,12 +1,20 @@
-use crate::{Opt, Platform};
+use crate::{BuildTarget, Opt, Platform};
 use anyhow::Result;
-use apk::manifest::{Activity, AndroidManifest, IntentFilter, MetaData, Permission};
-use apk::VersionCode;
+use apk::manifest::{
+    Activity, AndroidManifest, IntentFilter, MetaData, Permission as UsesPermission,
+};
+use apk::{AdaptiveIcon, IconBackground, VersionCode};
 use appbundle::InfoPlist;
+use msix::manifest::Capability;
 use msix::AppxManifest;
 use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+/// Default `minSdkVersion`, the first api level with native multidex support.
+pub const ANDROID_MIN_SDK_VERSION: u32 = 21;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub name: String,
@@ -78,12 +86,62 @@ pub struct Manifest {
 }
 
 impl Manifest {
-    pub fn parse<P: AsRef<Path>>(path: P) -> Result<Self> {
+    /// Parses `manifest.yaml`. The block of `flavor` in `flavors` and then
+    /// the `debug` or `release` block matching `opt` mirror the structure of
+    /// the manifest and are merged into it. Placeholders like
+    /// `${applicationId}` in strings are replaced.
+    pub fn parse<P: AsRef<Path>>(
+        path: P,
+        config: &Config,
+        opt: Opt,
+        flavor: Option<&str>,
+        defines: &[(String, String)],
+    ) -> Result<Self> {
         if !path.as_ref().exists() {
+            anyhow::ensure!(
+                flavor.is_none(),
+                "flavors are declared in {}",
+                path.as_ref().display()
+            );
             return Ok(Default::default());
         }
         let contents = std::fs::read_to_string(path.as_ref())?;
-        let config: RawConfig = serde_yaml::from_str(&contents)?;
+        Self::parse_str(&contents, config, opt, flavor, defines)
+    }
+
+    fn parse_str(
+        contents: &str,
+        config: &Config,
+        opt: Opt,
+        flavor: Option<&str>,
+        defines: &[(String, String)],
+    ) -> Result<Self> {
+        let mut value: Value = serde_yaml::from_str(contents)?;
+        if let Value::Mapping(mapping) = &mut value {
+            let flavors = mapping.remove(&Value::from("flavors"));
+            if let Some(flavor) = flavor {
+                let overrides = flavors
+                    .as_ref()
+                    .and_then(|flavors| flavors.get(flavor))
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("unknown flavor {}", flavor))?;
+                merge(&mut value, overrides);
+            }
+        }
+        if let Value::Mapping(mapping) = &mut value {
+            let debug = mapping.remove(&Value::from("debug"));
+            let release = mapping.remove(&Value::from("release"));
+            let overrides = match opt {
+                Opt::Debug => debug,
+                Opt::Release => release,
+            };
+            if let Some(overrides) = overrides {
+                merge(&mut value, overrides);
+            }
+        }
+        let placeholders = Placeholders::new(&value, config, flavor, defines);
+        placeholders.resolve(&mut value)?;
+        let config: RawConfig = serde_yaml::from_value(value)?;
         Ok(Manifest {
             generic: config.generic.unwrap_or_default(),
             android: config.android.unwrap_or_default(),
@@ -108,6 +166,96 @@ impl Manifest {
         self.generic.icon.as_deref()
     }
 
+    /// Android resource directory, defaults to `res` if it exists.
+    pub fn android_res(&self, path: &Path) -> Option<PathBuf> {
+        if let Some(res) = self.android.res.as_ref() {
+            return Some(path.join(res));
+        }
+        let res = path.join("res");
+        if res.exists() {
+            Some(res)
+        } else {
+            None
+        }
+    }
+
+    /// Adaptive launcher icon, defaults to a white background.
+    pub fn android_adaptive_icon(&self, path: &Path) -> Result<Option<AdaptiveIcon>> {
+        let android = &self.android;
+        let foreground = if let Some(foreground) = android.icon_foreground.as_ref() {
+            path.join(foreground)
+        } else {
+            anyhow::ensure!(
+                android.icon_background.is_none() && android.icon_monochrome.is_none(),
+                "android.icon_background and android.icon_monochrome require android.icon_foreground"
+            );
+            return Ok(None);
+        };
+        let background = match android.icon_background.as_deref() {
+            Some(color) if color.starts_with('#') => IconBackground::Color(color.to_string()),
+            Some(image) => IconBackground::Image(path.join(image)),
+            None => IconBackground::Color("#ffffff".into()),
+        };
+        let monochrome = android
+            .icon_monochrome
+            .as_ref()
+            .map(|monochrome| path.join(monochrome));
+        Ok(Some(AdaptiveIcon {
+            foreground,
+            background,
+            monochrome,
+        }))
+    }
+
+    pub fn android_uncompressed_native_libs(&self) -> bool {
+        self.android.uncompressed_native_libs
+    }
+
+    pub fn android_splits(&self) -> bool {
+        self.android.splits
+    }
+
+    /// Android java and kotlin source directory, defaults to `android/java`
+    /// if it exists.
+    pub fn android_java(&self, path: &Path) -> Option<PathBuf> {
+        if let Some(java) = self.android.java.as_ref() {
+            return Some(path.join(java));
+        }
+        let java = path.join("android").join("java");
+        if java.exists() {
+            Some(java)
+        } else {
+            None
+        }
+    }
+
+    /// Cargo features enabled for all platforms and for `platform`.
+    pub fn cargo_features(&self, platform: Platform) -> Vec<String> {
+        let features = match platform {
+            Platform::Android => &self.android.generic.features,
+            Platform::Ios => &self.ios.generic.features,
+            Platform::Macos => &self.macos.generic.features,
+            Platform::Linux => &self.linux.generic.features,
+            Platform::Windows => &self.windows.generic.features,
+        };
+        let mut features = [&self.generic.features[..], &features[..]].concat();
+        features.sort();
+        features.dedup();
+        features
+    }
+
+    pub fn android_dependencies(&self) -> &[String] {
+        &self.android.dependencies
+    }
+
+    pub fn android_resource_ids(&self) -> Option<&Path> {
+        self.android.resource_ids.as_deref()
+    }
+
+    pub fn android_stable_ids(&self) -> Option<&Path> {
+        self.android.stable_ids.as_deref()
+    }
+
     pub fn target_file(&self, path: &Path, platform: Platform) -> PathBuf {
         let file = path.join("lib").join(format!("{}.dart", platform));
         if file.exists() {
@@ -117,20 +265,60 @@ impl Manifest {
         }
     }
 
-    pub fn apply_config(&mut self, config: &Config, opt: Opt, flutter: bool) {
+    /// Boolean entitlements enabled when signing the app bundle.
+    pub fn entitlements(&self, platform: Platform) -> &[String] {
+        match platform {
+            Platform::Ios => &self.ios.entitlements,
+            Platform::Macos => &self.macos.entitlements,
+            _ => &[],
+        }
+    }
+
+    pub fn apply_config(
+        &mut self,
+        config: &Config,
+        platform: Platform,
+        opt: Opt,
+        flutter: bool,
+        java: bool,
+    ) {
+        // platform specific values take precedence
+        if let Some(app_id) = self.generic.app_id.as_ref() {
+            let identifiers = [
+                &mut self.android.manifest.package,
+                &mut self.ios.info.bundle_identifier,
+                &mut self.macos.info.bundle_identifier,
+                &mut self.windows.manifest.identity.name,
+            ];
+            for identifier in identifiers {
+                identifier.get_or_insert_with(|| app_id.clone());
+            }
+        }
+        if let Some(display_name) = self.generic.display_name.as_ref() {
+            let names = [
+                &mut self.android.manifest.application.label,
+                &mut self.ios.info.display_name,
+                &mut self.macos.info.display_name,
+                &mut self.windows.manifest.properties.display_name,
+            ];
+            for name in names {
+                name.get_or_insert_with(|| display_name.clone());
+            }
+        }
+        self.apply_permissions(config, platform);
+
         let manifest = &mut self.android.manifest;
-        manifest
+        let package = manifest
             .package
-            .get_or_insert_with(|| format!("com.example.{}", config.name.replace('-', "_")));
+            .get_or_insert_with(|| default_package(&config.name));
+        if let Some(suffix) = self.android.package_suffix.as_deref() {
+            package.push_str(suffix);
+        }
         manifest
             .version_name
             .get_or_insert_with(|| config.version.clone());
-        if let Ok(code) = VersionCode::from_semver(&config.version) {
-            manifest.version_code.get_or_insert_with(|| code.to_code(1));
-        }
         let target_sdk_version = 31;
         let target_sdk_codename = 11;
-        let min_sdk_version = 21;
         manifest
             .compile_sdk_version
             .get_or_insert(target_sdk_version);
@@ -147,10 +335,17 @@ impl Manifest {
             .sdk
             .target_sdk_version
             .get_or_insert(target_sdk_version);
-        manifest.sdk.min_sdk_version.get_or_insert(min_sdk_version);
-        if flutter && opt == Opt::Debug {
-            manifest.uses_permission.push(Permission {
-                name: "android.permission.INTERNET".into(),
+        manifest
+            .sdk
+            .min_sdk_version
+            .get_or_insert(ANDROID_MIN_SDK_VERSION);
+        let internet = "android.permission.INTERNET";
+        if flutter
+            && opt == Opt::Debug
+            && !manifest.uses_permission.iter().any(|p| p.name == internet)
+        {
+            manifest.uses_permission.push(UsesPermission {
+                name: internet.into(),
                 max_sdk_version: None,
             });
         }
@@ -169,9 +364,10 @@ impl Manifest {
                 .get_or_insert_with(|| "androidx.core.app.CoreComponentFactory".into());
             application.meta_data.push(MetaData {
                 name: "flutterEmbedding".into(),
-                value: "2".into(),
+                value: Some("2".into()),
+                ..Default::default()
             });
-        } else {
+        } else if self.android.dependencies.is_empty() && !java {
             application.has_code.get_or_insert(false);
         }
         if application.activities.is_empty() {
@@ -208,7 +404,8 @@ impl Manifest {
                 } else {
                     vec![MetaData {
                         name: "android.app.lib_name".into(),
-                        value: config.name.replace('-', "_"),
+                        value: Some(config.name.replace('-', "_")),
+                        ..Default::default()
                     }]
                 },
                 intent_filters: vec![IntentFilter {
@@ -216,6 +413,7 @@ impl Manifest {
                     categories: vec!["android.intent.category.LAUNCHER".into()],
                     data: vec![],
                 }],
+                ..Default::default()
             };
             application.activities.push(activity);
         }
@@ -256,11 +454,6 @@ impl Manifest {
             .properties
             .display_name
             .get_or_insert_with(|| config.name.clone());
-        self.windows
-            .manifest
-            .identity
-            .version
-            .get_or_insert_with(|| config.version.clone());
         self.windows
             .manifest
             .properties
@@ -268,6 +461,192 @@ impl Manifest {
             .get_or_insert_with(|| config.description.clone());
     }
 
+    /// Adds the platform equivalents of the generic permissions, usage
+    /// descriptions set in `info` are kept. Warns about permissions
+    /// `platform` has no equivalent of.
+    fn apply_permissions(&mut self, config: &Config, platform: Platform) {
+        let app_name = self.generic.display_name.as_ref().unwrap_or(&config.name);
+        for permission in &self.generic.permissions {
+            let uses_permission = &mut self.android.manifest.uses_permission;
+            for (name, max_sdk_version) in permission.android() {
+                if !uses_permission.iter().any(|p| p.name == *name) {
+                    uses_permission.push(UsesPermission {
+                        name: name.to_string(),
+                        max_sdk_version: *max_sdk_version,
+                    });
+                }
+            }
+
+            let description = format!("{} uses {}.", app_name, permission.description());
+            for description_key in permission.usage_descriptions(&mut self.ios.info, false) {
+                description_key.get_or_insert_with(|| description.clone());
+            }
+            for description_key in permission.usage_descriptions(&mut self.macos.info, true) {
+                description_key.get_or_insert_with(|| description.clone());
+            }
+            let entitlement = permission.macos_entitlement().to_string();
+            if !self.macos.entitlements.contains(&entitlement) {
+                self.macos.entitlements.push(entitlement);
+            }
+
+            let capability = permission.msix_capability();
+            if !self.windows.manifest.capabilities.contains(&capability) {
+                self.windows.manifest.capabilities.push(capability);
+            }
+
+            let supported = match platform {
+                Platform::Ios => *permission != Permission::Internet,
+                Platform::Linux => false,
+                Platform::Android | Platform::Macos | Platform::Windows => true,
+            };
+            if !supported {
+                log::warn!(
+                    "{} has no equivalent of the {} permission",
+                    platform,
+                    permission.name()
+                );
+            }
+        }
+    }
+
+    /// Build number from CI or the number of git commits, see
+    /// [`GenericConfig::build_number`].
+    pub fn build_number(&self, platform: Platform, root_dir: &Path) -> Result<Option<u32>> {
+        let build_number = match platform {
+            Platform::Android => self.android.generic.build_number.as_deref(),
+            Platform::Ios => self.ios.generic.build_number.as_deref(),
+            Platform::Macos => self.macos.generic.build_number.as_deref(),
+            Platform::Linux => self.linux.generic.build_number.as_deref(),
+            Platform::Windows => self.windows.generic.build_number.as_deref(),
+        };
+        let build_number = if let Some(build_number) = build_number {
+            build_number
+        } else if let Some(build_number) = self.generic.build_number.as_deref() {
+            build_number
+        } else {
+            return Ok(None);
+        };
+        let number = if build_number == "git" {
+            let output = std::process::Command::new("git")
+                .current_dir(root_dir)
+                .arg("rev-list")
+                .arg("--count")
+                .arg("HEAD")
+                .output()?;
+            anyhow::ensure!(
+                output.status.success(),
+                "git rev-list exited with code {:?}",
+                output.status.code()
+            );
+            std::str::from_utf8(&output.stdout)?.trim().to_string()
+        } else if let Some(var) = build_number.strip_prefix('$') {
+            std::env::var(var)
+                .map_err(|_| anyhow::anyhow!("build number variable {} is not set", var))?
+        } else {
+            build_number.to_string()
+        };
+        let number = number
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid build number {}", number))?;
+        Ok(Some(number))
+    }
+
+    /// Sets the android versionCode, the `CFBundleVersion` and the msix
+    /// version. With a build number it is used as the version code,
+    /// otherwise the version code is derived from the semver version.
+    ///
+    /// The msix version is the semver version followed by the build number.
+    /// Without a build number the version is used as is.
+    pub fn apply_version(
+        &mut self,
+        config: &Config,
+        root_dir: &Path,
+        target: &BuildTarget,
+    ) -> Result<()> {
+        let build_number = self.build_number(target.platform(), root_dir)?;
+        let semver = VersionCode::from_semver(&config.version).ok();
+        let android = &mut self.android;
+        if target.platform() == Platform::Android && android.manifest.version_code.is_none() {
+            let code = if let Some(build_number) = build_number {
+                anyhow::ensure!(
+                    build_number <= VersionCode::MAX,
+                    "build number {} exceeds {}",
+                    build_number,
+                    VersionCode::MAX
+                );
+                Some(build_number)
+            } else if let Some(semver) = semver {
+                Some(semver.to_code(1)?)
+            } else {
+                None
+            };
+            let targets = target.compile_targets().collect::<Vec<_>>();
+            let offset = android.version_code_abi_offset;
+            android.manifest.version_code = match (code, targets.as_slice(), offset) {
+                (Some(code), [target], Some(offset)) => {
+                    // otherwise the version codes of different abis collide
+                    anyhow::ensure!(
+                        code < offset,
+                        "version code {} must be less than the abi offset {}",
+                        code,
+                        offset
+                    );
+                    let code = (target.android_abi() as u32)
+                        .checked_mul(offset)
+                        .and_then(|abi_code| abi_code.checked_add(code))
+                        .filter(|code| *code <= VersionCode::MAX)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "version code {} with abi offset {} exceeds {}",
+                                code,
+                                offset,
+                                VersionCode::MAX
+                            )
+                        })?;
+                    Some(code)
+                }
+                (code, _, _) => code,
+            };
+        }
+
+        if let Some(build_number) = build_number {
+            self.ios
+                .info
+                .version
+                .get_or_insert_with(|| build_number.to_string());
+            self.macos
+                .info
+                .version
+                .get_or_insert_with(|| build_number.to_string());
+        }
+
+        let identity = &mut self.windows.manifest.identity;
+        if target.platform() == Platform::Windows && identity.version.is_none() {
+            let version = match (build_number, semver) {
+                (Some(build_number), Some(semver)) => {
+                    let parts = [semver.major(), semver.minor(), semver.patch(), build_number];
+                    anyhow::ensure!(
+                        parts.iter().all(|part| *part <= u16::MAX as u32),
+                        "msix version parts can't exceed {}",
+                        u16::MAX
+                    );
+                    let parts = parts
+                        .iter()
+                        .map(|part| part.to_string())
+                        .collect::<Vec<_>>();
+                    parts.join(".")
+                }
+                (Some(_), None) => anyhow::bail!(
+                    "version {} must be semver to add the build number to the msix version",
+                    config.version
+                ),
+                (None, _) => config.version.clone(),
+            };
+            identity.version = Some(version);
+        }
+        Ok(())
+    }
+
     pub fn android(&self) -> &AndroidManifest {
         &self.android.manifest
     }
@@ -285,6 +664,118 @@ impl Manifest {
     }
 }
 
+fn default_package(name: &str) -> String {
+    format!("com.example.{}", name.replace('-', "_"))
+}
+
+/// Merges `other` into `value`. Mappings are merged recursively, sequences
+/// are appended and other values are replaced.
+fn merge(value: &mut Value, other: Value) {
+    match (value, other) {
+        (Value::Mapping(value), Value::Mapping(other)) => {
+            for (key, other) in other {
+                if let Some(value) = value.get_mut(&key) {
+                    merge(value, other);
+                } else {
+                    value.insert(key, other);
+                }
+            }
+        }
+        (Value::Sequence(value), Value::Sequence(other)) => value.extend(other),
+        (value, other) => *value = other,
+    }
+}
+
+/// Values of the `${key}` placeholders in the strings of `manifest.yaml`.
+/// Keys passed with `--define key=value` take precedence over the builtin
+/// `applicationId`, `versionName`, `name` and `flavor`, other keys are
+/// looked up in the environment. `$${key}` is kept as `${key}`.
+struct Placeholders {
+    values: BTreeMap<String, String>,
+}
+
+impl Placeholders {
+    fn new(
+        manifest: &Value,
+        config: &Config,
+        flavor: Option<&str>,
+        defines: &[(String, String)],
+    ) -> Self {
+        let android = &manifest["android"];
+        let mut application_id = android["manifest"]["package"]
+            .as_str()
+            .or_else(|| manifest["app_id"].as_str())
+            .map(|package| package.to_string())
+            .unwrap_or_else(|| default_package(&config.name));
+        if let Some(suffix) = android["package_suffix"].as_str() {
+            application_id.push_str(suffix);
+        }
+        let version_name = android["manifest"]["version_name"]
+            .as_str()
+            .unwrap_or(&config.version)
+            .to_string();
+        let mut values = BTreeMap::new();
+        values.insert("applicationId".to_string(), application_id);
+        values.insert("versionName".to_string(), version_name);
+        values.insert("name".to_string(), config.name.clone());
+        if let Some(flavor) = flavor {
+            values.insert("flavor".to_string(), flavor.to_string());
+        }
+        values.extend(defines.iter().cloned());
+        Self { values }
+    }
+
+    fn get(&self, key: &str) -> Result<String> {
+        if let Some(value) = self.values.get(key) {
+            return Ok(value.clone());
+        }
+        std::env::var(key).map_err(|_| {
+            anyhow::anyhow!(
+                "placeholder ${{{0}}} is not defined, pass `--define {0}=<value>`",
+                key
+            )
+        })
+    }
+
+    /// Replaces the placeholders in the strings of `value`.
+    fn resolve(&self, value: &mut Value) -> Result<()> {
+        match value {
+            Value::String(string) if string.contains("${") => {
+                let mut resolved = String::with_capacity(string.len());
+                let mut rest = string.as_str();
+                while let Some(start) = rest.find("${") {
+                    if let Some(escaped) = rest[..start].strip_suffix('$') {
+                        resolved.push_str(escaped);
+                        resolved.push_str("${");
+                        rest = &rest[start + 2..];
+                        continue;
+                    }
+                    resolved.push_str(&rest[..start]);
+                    let len = rest[start..]
+                        .find('}')
+                        .ok_or_else(|| anyhow::anyhow!("unterminated placeholder in {}", string))?;
+                    resolved.push_str(&self.get(&rest[start + 2..start + len])?);
+                    rest = &rest[start + len + 1..];
+                }
+                resolved.push_str(rest);
+                *string = resolved;
+            }
+            Value::Sequence(values) => {
+                for value in values {
+                    self.resolve(value)?;
+                }
+            }
+            Value::Mapping(mapping) => {
+                for (_, value) in mapping.iter_mut() {
+                    self.resolve(value)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 #[derive(Deserialize)]
 struct RawConfig {
     #[serde(flatten)]
@@ -298,7 +789,27 @@ struct RawConfig {
 
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct GenericConfig {
+    /// Android package, bundle identifier and msix identity name, unless
+    /// they are set in the platform manifests.
+    app_id: Option<String>,
+    /// Android label, bundle display name and msix display name, unless
+    /// they are set in the platform manifests.
+    display_name: Option<String>,
     icon: Option<PathBuf>,
+    /// Cargo features enabled when building the app.
+    #[serde(default)]
+    features: Vec<String>,
+    /// Permissions like `camera` mapped to the android permissions, the
+    /// usage descriptions and entitlements of ios and macos and the msix
+    /// capabilities.
+    #[serde(default)]
+    permissions: Vec<Permission>,
+    /// A number, `git` for the number of commits or an environment variable
+    /// like `$GITHUB_RUN_NUMBER`. Used as the android versionCode, the
+    /// `CFBundleVersion` and the last component of the msix version. Without
+    /// a build number the versionCode is derived from the version, which
+    /// limits each part of the version to 255.
+    build_number: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -306,6 +817,10 @@ pub struct AppbundleConfig {
     #[serde(flatten)]
     generic: GenericConfig,
     info: InfoPlist,
+    /// Boolean entitlements like `com.apple.security.device.camera` added
+    /// to the entitlements of the provisioning profile.
+    #[serde(default)]
+    entitlements: Vec<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -313,6 +828,40 @@ pub struct ApkConfig {
     #[serde(flatten)]
     generic: GenericConfig,
     manifest: AndroidManifest,
+    res: Option<PathBuf>,
+    /// Directory containing java and kotlin sources which are compiled into
+    /// the `classes.dex` of the apk.
+    java: Option<PathBuf>,
+    icon_foreground: Option<PathBuf>,
+    /// Color like `#ffffff` or path to an image.
+    icon_background: Option<String>,
+    icon_monochrome: Option<PathBuf>,
+    /// Stores native libraries uncompressed and page aligned.
+    #[serde(default)]
+    uncompressed_native_libs: bool,
+    /// Writes native libraries and density specific resources to
+    /// configuration splits next to the apk.
+    #[serde(default)]
+    splits: bool,
+    /// Multiplied with the abi index (armeabi-v7a 1, arm64-v8a 2, x86 3,
+    /// x86_64 4) and added to the versionCode of apks built for a single
+    /// abi, so they have distinct version codes.
+    version_code_abi_offset: Option<u32>,
+    /// Maven coordinates like `androidx.core:core:1.7.0`. The classes,
+    /// resources, native libraries and manifests of the resolved libraries
+    /// are merged into the apk.
+    #[serde(default)]
+    dependencies: Vec<String>,
+    /// Appended to the package, like `.debug` in a `debug` block so debug
+    /// and release builds can be installed side by side.
+    package_suffix: Option<String>,
+    /// Rust file the resource ids of the app are written to, defaults to
+    /// `android_resources.rs` in the build directory.
+    resource_ids: Option<PathBuf>,
+    /// File the ids of the app resources are kept in between builds, in the
+    /// format of `aapt2 link --stable-ids`. Defaults to `resource_ids.txt` in
+    /// the build directory, checking it in keeps ids stable on clean builds.
+    stable_ids: Option<PathBuf>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -327,3 +876,510 @@ pub struct MsixConfig {
     generic: GenericConfig,
     manifest: AppxManifest,
 }
+
+/// Permission requested on all platforms.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Camera,
+    Microphone,
+    Location,
+    Internet,
+    Bluetooth,
+}
+
+impl Permission {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Camera => "camera",
+            Self::Microphone => "microphone",
+            Self::Location => "location",
+            Self::Internet => "internet",
+            Self::Bluetooth => "bluetooth",
+        }
+    }
+
+    /// Completes the default usage description `<app> uses ...`.
+    fn description(self) -> &'static str {
+        match self {
+            Self::Camera => "the camera",
+            Self::Microphone => "the microphone",
+            Self::Location => "your location",
+            Self::Internet => "the network",
+            Self::Bluetooth => "bluetooth",
+        }
+    }
+
+    /// Android permissions and their `maxSdkVersion`. Bluetooth is split
+    /// into `BLUETOOTH_CONNECT` and `BLUETOOTH_SCAN` in api level 31.
+    fn android(self) -> &'static [(&'static str, Option<u32>)] {
+        match self {
+            Self::Camera => &[("android.permission.CAMERA", None)],
+            Self::Microphone => &[("android.permission.RECORD_AUDIO", None)],
+            Self::Location => &[
+                ("android.permission.ACCESS_COARSE_LOCATION", None),
+                ("android.permission.ACCESS_FINE_LOCATION", None),
+            ],
+            Self::Internet => &[("android.permission.INTERNET", None)],
+            Self::Bluetooth => &[
+                ("android.permission.BLUETOOTH", Some(30)),
+                ("android.permission.BLUETOOTH_ADMIN", Some(30)),
+                ("android.permission.BLUETOOTH_CONNECT", None),
+                ("android.permission.BLUETOOTH_SCAN", None),
+            ],
+        }
+    }
+
+    /// Usage description keys of `info`, access to the network doesn't
+    /// need one.
+    fn usage_descriptions(self, info: &mut InfoPlist, macos: bool) -> Vec<&mut Option<String>> {
+        match self {
+            Self::Camera => vec![&mut info.camera_usage_description],
+            Self::Microphone => vec![&mut info.microphone_usage_description],
+            Self::Location if macos => vec![
+                &mut info.location_when_in_use_usage_description,
+                &mut info.location_usage_description,
+            ],
+            Self::Location => vec![&mut info.location_when_in_use_usage_description],
+            Self::Internet => vec![],
+            Self::Bluetooth if macos => vec![&mut info.bluetooth_always_usage_description],
+            Self::Bluetooth => vec![
+                &mut info.bluetooth_always_usage_description,
+                &mut info.bluetooth_peripheral_usage_description,
+            ],
+        }
+    }
+
+    /// Entitlement of the hardened runtime or the app sandbox.
+    fn macos_entitlement(self) -> &'static str {
+        match self {
+            Self::Camera => "com.apple.security.device.camera",
+            Self::Microphone => "com.apple.security.device.audio-input",
+            Self::Location => "com.apple.security.personal-information.location",
+            Self::Internet => "com.apple.security.network.client",
+            Self::Bluetooth => "com.apple.security.device.bluetooth",
+        }
+    }
+
+    fn msix_capability(self) -> Capability {
+        match self {
+            Self::Camera => Capability::Device {
+                name: "webcam".into(),
+            },
+            Self::Microphone => Capability::Device {
+                name: "microphone".into(),
+            },
+            Self::Location => Capability::Device {
+                name: "location".into(),
+            },
+            Self::Internet => Capability::Capability {
+                name: "internetClient".into(),
+            },
+            Self::Bluetooth => Capability::Device {
+                name: "bluetooth".into(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Arch, Format};
+
+    fn config() -> Config {
+        Config {
+            name: "helloworld".into(),
+            version: "1.2.3".into(),
+            description: "".into(),
+        }
+    }
+
+    fn target(platform: Platform, archs: Vec<Arch>) -> BuildTarget {
+        BuildTarget {
+            opt: Opt::Debug,
+            platform,
+            archs,
+            format: Format::Apk,
+            device: None,
+            store: None,
+            signer: None,
+            previous_signers: vec![],
+            provisioning_profile: None,
+            notarization_key_and_issuer: None,
+        }
+    }
+
+    fn android_manifest(build_number: &str, offset: Option<u32>) -> Manifest {
+        Manifest {
+            generic: GenericConfig {
+                build_number: Some(build_number.into()),
+                ..Default::default()
+            },
+            android: ApkConfig {
+                version_code_abi_offset: offset,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn parse(yaml: &str, opt: Opt, flavor: Option<&str>) -> Result<Manifest> {
+        Manifest::parse_str(yaml, &config(), opt, flavor, &[])
+    }
+
+    fn yaml(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut value = yaml(
+            r#"
+            android:
+              manifest:
+                package: com.example.helloworld
+                version_name: "1.0"
+              dependencies: [a]
+            icon: icon.png
+            "#,
+        );
+        merge(
+            &mut value,
+            yaml(
+                r#"
+                android:
+                  manifest:
+                    version_name: "1.0-debug"
+                  dependencies: [b]
+                icon: debug.png
+                "#,
+            ),
+        );
+        let expected = yaml(
+            r#"
+            android:
+              manifest:
+                package: com.example.helloworld
+                version_name: "1.0-debug"
+              dependencies: [a, b]
+            icon: debug.png
+            "#,
+        );
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_placeholders() -> Result<()> {
+        let defines = [("host".to_string(), "example.com".to_string())];
+        let mut value = yaml(
+            r#"
+            app_id: com.example.app
+            urls: ["${host}/${applicationId}", "$${host}", "${name} ${versionName}"]
+            "#,
+        );
+        let placeholders = Placeholders::new(&value, &config(), None, &defines);
+        placeholders.resolve(&mut value)?;
+        assert_eq!(
+            value["urls"],
+            yaml(r#"["example.com/com.example.app", "${host}", "helloworld 1.2.3"]"#)
+        );
+
+        std::env::set_var("XBUILD_TEST_PLACEHOLDER", "env");
+        let mut value = yaml(r#"icon: "${XBUILD_TEST_PLACEHOLDER}.png""#);
+        placeholders.resolve(&mut value)?;
+        assert_eq!(value["icon"], yaml("env.png"));
+
+        for invalid in [
+            r#"icon: "${XBUILD_TEST_UNDEFINED_PLACEHOLDER}""#,
+            r#"icon: "${host""#,
+        ] {
+            assert!(placeholders.resolve(&mut yaml(invalid)).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_release_overrides() -> Result<()> {
+        let manifest = r#"
+            android:
+              manifest:
+                package: com.example.helloworld
+              package_suffix: .release
+            debug:
+              android:
+                package_suffix: .debug
+                manifest:
+                  application:
+                    label: "${applicationId}"
+            "#;
+        let mut debug = parse(manifest, Opt::Debug, None)?;
+        debug.apply_config(&config(), Platform::Android, Opt::Debug, false, false);
+        let android = debug.android();
+        assert_eq!(
+            android.package.as_deref(),
+            Some("com.example.helloworld.debug")
+        );
+        assert_eq!(
+            android.application.label.as_deref(),
+            Some("com.example.helloworld.debug")
+        );
+        let mut release = parse(manifest, Opt::Release, None)?;
+        release.apply_config(&config(), Platform::Android, Opt::Release, false, false);
+        let android = release.android();
+        assert_eq!(
+            android.package.as_deref(),
+            Some("com.example.helloworld.release")
+        );
+        assert_eq!(android.application.label.as_deref(), Some("helloworld"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_flavors() -> Result<()> {
+        let manifest = r#"
+            app_id: com.example.helloworld
+            android:
+              manifest:
+                version_name: "1.0"
+            flavors:
+              free:
+                app_id: com.example.helloworld.free
+                display_name: Hello Free
+                features: [free]
+                android:
+                  package_suffix: .free
+              pro:
+                display_name: Hello Pro
+            debug:
+              android:
+                package_suffix: .debug
+            "#;
+        let mut free = parse(manifest, Opt::Debug, Some("free"))?;
+        free.apply_config(&config(), Platform::Android, Opt::Debug, false, false);
+        // the debug block is merged after the flavor
+        assert_eq!(
+            free.android().package.as_deref(),
+            Some("com.example.helloworld.free.debug")
+        );
+        assert_eq!(
+            free.android().application.label.as_deref(),
+            Some("Hello Free")
+        );
+        assert_eq!(
+            free.ios().bundle_identifier.as_deref(),
+            Some("com.example.helloworld.free")
+        );
+        assert_eq!(free.ios().display_name.as_deref(), Some("Hello Free"));
+        assert_eq!(
+            free.windows().identity.name.as_deref(),
+            Some("com.example.helloworld.free")
+        );
+        assert_eq!(free.cargo_features(Platform::Android), ["free"]);
+
+        let mut pro = parse(manifest, Opt::Release, Some("pro"))?;
+        pro.apply_config(&config(), Platform::Android, Opt::Release, false, false);
+        assert_eq!(
+            pro.android().package.as_deref(),
+            Some("com.example.helloworld")
+        );
+        assert_eq!(pro.macos().display_name.as_deref(), Some("Hello Pro"));
+        assert!(pro.cargo_features(Platform::Android).is_empty());
+
+        assert!(parse(manifest, Opt::Debug, Some("unknown")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_flavor_platform_override() -> Result<()> {
+        let manifest = r#"
+            app_id: com.example.helloworld
+            display_name: Hello
+            flavors:
+              staging:
+                android:
+                  manifest:
+                    package: com.example.staging
+                    application:
+                      label: Hello ${applicationId}
+            "#;
+        let mut staging = parse(manifest, Opt::Debug, Some("staging"))?;
+        staging.apply_config(&config(), Platform::Android, Opt::Debug, false, false);
+        assert_eq!(
+            staging.android().package.as_deref(),
+            Some("com.example.staging")
+        );
+        assert_eq!(
+            staging.android().application.label.as_deref(),
+            Some("Hello com.example.staging")
+        );
+        assert_eq!(
+            staging.ios().bundle_identifier.as_deref(),
+            Some("com.example.helloworld")
+        );
+        assert_eq!(staging.ios().display_name.as_deref(), Some("Hello"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_permissions() -> Result<()> {
+        let manifest = r#"
+            permissions: [camera, location, internet, bluetooth]
+            ios:
+              info:
+                camera_usage_description: Scans qr codes.
+            "#;
+        let mut manifest = parse(manifest, Opt::Debug, None)?;
+        manifest.apply_config(&config(), Platform::Android, Opt::Debug, true, false);
+
+        let permissions = manifest
+            .android()
+            .uses_permission
+            .iter()
+            .map(|p| (p.name.as_str(), p.max_sdk_version))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            permissions,
+            [
+                ("android.permission.CAMERA", None),
+                ("android.permission.ACCESS_COARSE_LOCATION", None),
+                ("android.permission.ACCESS_FINE_LOCATION", None),
+                ("android.permission.INTERNET", None),
+                ("android.permission.BLUETOOTH", Some(30)),
+                ("android.permission.BLUETOOTH_ADMIN", Some(30)),
+                ("android.permission.BLUETOOTH_CONNECT", None),
+                ("android.permission.BLUETOOTH_SCAN", None),
+            ]
+        );
+
+        let ios = manifest.ios();
+        assert_eq!(
+            ios.camera_usage_description.as_deref(),
+            Some("Scans qr codes.")
+        );
+        assert_eq!(
+            ios.location_when_in_use_usage_description.as_deref(),
+            Some("helloworld uses your location.")
+        );
+        assert!(ios.location_usage_description.is_none());
+        assert!(ios.bluetooth_peripheral_usage_description.is_some());
+        assert!(ios.microphone_usage_description.is_none());
+        assert!(manifest.entitlements(Platform::Ios).is_empty());
+
+        let macos = manifest.macos();
+        assert_eq!(
+            macos.camera_usage_description.as_deref(),
+            Some("helloworld uses the camera.")
+        );
+        assert!(macos.location_usage_description.is_some());
+        assert!(macos.bluetooth_peripheral_usage_description.is_none());
+        assert_eq!(
+            manifest.entitlements(Platform::Macos),
+            [
+                "com.apple.security.device.camera",
+                "com.apple.security.personal-information.location",
+                "com.apple.security.network.client",
+                "com.apple.security.device.bluetooth",
+            ]
+        );
+
+        let device = |name: &str| Capability::Device { name: name.into() };
+        assert_eq!(
+            manifest.windows().capabilities,
+            [
+                device("webcam"),
+                device("location"),
+                Capability::Capability {
+                    name: "internetClient".into()
+                },
+                device("bluetooth"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_code_abi_offset() -> Result<()> {
+        let root_dir = Path::new(".");
+        let mut manifest = android_manifest("42", Some(1_000_000));
+        let arm64 = target(Platform::Android, vec![Arch::Arm64]);
+        manifest.apply_version(&config(), root_dir, &arm64)?;
+        assert_eq!(manifest.android().version_code, Some(2_000_042));
+
+        // the offset only applies to apks built for a single abi
+        let mut manifest = android_manifest("42", Some(1_000_000));
+        let all = target(Platform::Android, vec![Arch::Arm, Arch::Arm64]);
+        manifest.apply_version(&config(), root_dir, &all)?;
+        assert_eq!(manifest.android().version_code, Some(42));
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_code_overflow() {
+        let root_dir = Path::new(".");
+        let x86_64 = target(Platform::Android, vec![Arch::X64]);
+        let mut manifest = android_manifest("2100000001", None);
+        assert!(manifest
+            .apply_version(&config(), root_dir, &x86_64)
+            .is_err());
+        let mut manifest = android_manifest("100000001", Some(500_000_000));
+        assert!(manifest
+            .apply_version(&config(), root_dir, &x86_64)
+            .is_err());
+    }
+
+    #[test]
+    fn test_version_code_exceeds_abi_offset() {
+        let root_dir = Path::new(".");
+        let arm64 = target(Platform::Android, vec![Arch::Arm64]);
+        let mut manifest = android_manifest("1000", Some(1000));
+        assert!(manifest.apply_version(&config(), root_dir, &arm64).is_err());
+    }
+
+    #[test]
+    fn test_msix_version() -> Result<()> {
+        let root_dir = Path::new(".");
+        let windows = target(Platform::Windows, vec![Arch::X64]);
+        let mut manifest = Manifest {
+            generic: GenericConfig {
+                build_number: Some("42".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        manifest.apply_version(&config(), root_dir, &windows)?;
+        assert_eq!(
+            manifest.windows().identity.version.as_deref(),
+            Some("1.2.3.42")
+        );
+
+        let mut config = config();
+        config.version = "1.2".into();
+        let mut manifest = Manifest::default();
+        manifest.apply_version(&config, root_dir, &windows)?;
+        assert_eq!(manifest.windows().identity.version.as_deref(), Some("1.2"));
+        let mut manifest = Manifest {
+            generic: GenericConfig {
+                build_number: Some("42".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(manifest.apply_version(&config, root_dir, &windows).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_number_variable() -> Result<()> {
+        let root_dir = Path::new(".");
+        std::env::set_var("XBUILD_TEST_BUILD_NUMBER", "7");
+        let manifest = android_manifest("$XBUILD_TEST_BUILD_NUMBER", None);
+        assert_eq!(manifest.build_number(Platform::Android, root_dir)?, Some(7));
+        let manifest = android_manifest("$XBUILD_TEST_UNSET_BUILD_NUMBER", None);
+        assert!(manifest.build_number(Platform::Android, root_dir).is_err());
+        std::env::set_var("XBUILD_TEST_INVALID_BUILD_NUMBER", "seven");
+        let manifest = android_manifest("$XBUILD_TEST_INVALID_BUILD_NUMBER", None);
+        assert!(manifest.build_number(Platform::Android, root_dir).is_err());
+        Ok(())
+    }
+}
@@ -73,6 +73,16 @@ impl Adb {
     }
 
     fn install(&self, device: &str, path: &Path) -> Result<()> {
+        let abi = self.getprop(device, "ro.product.cpu.abi")?;
+        let splits = Apk::splits(path, Some(abi.as_str()))?;
+        if !splits.is_empty() {
+            let mut apks = vec![path.to_path_buf()];
+            apks.extend(splits);
+            return self.install_multiple(device, &apks);
+        }
+        if self.install_incremental(device, "install", &[path.to_path_buf()])? {
+            return Ok(());
+        }
         let file_name = path.file_name().unwrap().to_str().unwrap();
         self.push(device, path)?;
         let status = self
@@ -89,6 +99,41 @@ impl Adb {
         Ok(())
     }
 
+    fn install_multiple(&self, device: &str, apks: &[PathBuf]) -> Result<()> {
+        if self.install_incremental(device, "install-multiple", apks)? {
+            return Ok(());
+        }
+        let status = self
+            .adb(device)
+            .arg("install-multiple")
+            .args(apks)
+            .status()?;
+        anyhow::ensure!(
+            status.success(),
+            "adb install-multiple exited with code {:?}",
+            status.code()
+        );
+        Ok(())
+    }
+
+    /// Installs the apks with `adb <command> --incremental` if all of them
+    /// have a v4 signature. Returns false if a regular install is needed.
+    fn install_incremental(&self, device: &str, command: &str, apks: &[PathBuf]) -> Result<bool> {
+        if !apks.iter().all(|apk| Apk::idsig_path(apk).exists()) {
+            return Ok(false);
+        }
+        let status = self
+            .adb(device)
+            .arg(command)
+            .arg("--incremental")
+            .args(apks)
+            .status()?;
+        if !status.success() {
+            log::warn!("incremental install failed, falling back to a regular install");
+        }
+        Ok(status.success())
+    }
+
     /// To run a native activity use "android.app.NativeActivity" as the activity name
     fn start(&self, device: &str, package: &str, activity: &str) -> Result<()> {
         let status = self
@@ -337,9 +382,9 @@ impl Adb {
     pub fn arch(&self, device: &str) -> Result<Arch> {
         let arch = match self.getprop(device, "ro.product.cpu.abi")?.as_str() {
             "arm64-v8a" => Arch::Arm64,
-            //"armeabi-v7a" => Arch::Arm,
+            "armeabi-v7a" => Arch::Arm,
             "x86_64" => Arch::X64,
-            //"x86" => Arch::X86,
+            "x86" => Arch::X86,
             abi => anyhow::bail!("unrecognized abi {}", abi),
         };
         Ok(arch)
@@ -9,7 +9,7 @@ use std::path::PathBuf;
 use zip::ZipArchive;
 
 impl<'a> DownloadManager<'a> {
-    fn maven(&'a self) -> Result<Maven<&'a Self>> {
+    pub(crate) fn maven(&'a self) -> Result<Maven<&'a Self>> {
         const GOOGLE: &str = "https://maven.google.com";
         const FLUTTER: &str = "http://download.flutter.io";
         const CENTRAL: &str = "https://repo1.maven.org/maven2";
@@ -134,6 +134,9 @@ impl<'a> DownloadManager<'a> {
                     "flutter_windows.dll",
                 ));
             }
+            (Platform::Android, Arch::X86, Opt::Release) => {
+                anyhow::bail!("flutter doesn't support release builds for x86");
+            }
             (Platform::Android, arch, opt) => {
                 let output = engine_dir.join("libflutter.so");
                 if !output.exists() {
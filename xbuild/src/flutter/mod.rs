@@ -253,6 +253,7 @@ impl Flutter {
             let arch = match target.arch() {
                 Arch::X64 => "x86_64",
                 Arch::Arm64 => "arm64",
+                arch => anyhow::bail!("unsupported arch {}", arch),
             };
             let mut cmd = Command::new("clang");
             cmd.arg("-c")
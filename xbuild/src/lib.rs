@@ -3,6 +3,7 @@ use crate::config::{Config, Manifest};
 use crate::devices::Device;
 use crate::flutter::Flutter;
 use anyhow::Result;
+use apk::AdaptiveIcon;
 use clap::Parser;
 use std::path::{Path, PathBuf};
 use xcommon::Signer;
@@ -18,6 +19,7 @@ macro_rules! exe {
     };
 }
 
+pub mod android;
 pub mod cargo;
 pub mod command;
 pub mod config;
@@ -93,10 +95,10 @@ impl std::str::FromStr for Platform {
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Arch {
-    //Arm,
+    Arm,
     Arm64,
     X64,
-    //X86,
+    X86,
 }
 
 impl Arch {
@@ -114,10 +116,10 @@ impl Arch {
 impl std::fmt::Display for Arch {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            //Self::Arm => write!(f, "arm"),
+            Self::Arm => write!(f, "arm"),
             Self::Arm64 => write!(f, "arm64"),
             Self::X64 => write!(f, "x64"),
-            //Self::X86 => write!(f, "x86"),
+            Self::X86 => write!(f, "x86"),
         }
     }
 }
@@ -127,10 +129,10 @@ impl std::str::FromStr for Arch {
 
     fn from_str(arch: &str) -> Result<Self> {
         Ok(match arch {
-            //"arm" => Self::Arm,
+            "arm" => Self::Arm,
             "arm64" => Self::Arm64,
             "x64" => Self::X64,
-            //"x86" => Self::X86,
+            "x86" => Self::X86,
             _ => anyhow::bail!("unsupported arch {}", arch),
         })
     }
@@ -185,7 +187,7 @@ impl Format {
     pub fn platform_default(platform: Platform, opt: Opt) -> Self {
         match (platform, opt) {
             (Platform::Android, Opt::Debug) => Self::Apk,
-            (Platform::Android, Opt::Release) => Self::Apk, /* TODO: Aab is not currently supported */
+            (Platform::Android, Opt::Release) => Self::Aab,
             (Platform::Ios, Opt::Debug) => Self::Appbundle,
             (Platform::Ios, Opt::Release) => Self::Ipa,
             (Platform::Linux, Opt::Debug) => Self::Appdir,
@@ -209,6 +211,27 @@ impl Format {
         }
     }
 
+    /// Detects the format of a built artifact from its extension.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        [
+            Self::Aab,
+            Self::Apk,
+            Self::Appbundle,
+            Self::Appdir,
+            Self::Appimage,
+            Self::Dmg,
+            Self::Ipa,
+            Self::Msix,
+        ]
+        .into_iter()
+        .find(|format| format.extension().eq_ignore_ascii_case(ext))
+        .ok_or_else(|| anyhow::anyhow!("unsupported file {}", path.display()))
+    }
+
     pub fn supports_multiarch(self) -> bool {
         matches!(self, Self::Aab | Self::Apk)
     }
@@ -278,8 +301,10 @@ impl CompileTarget {
     pub fn android_abi(self) -> apk::Target {
         assert_eq!(self.platform(), Platform::Android);
         match self.arch() {
+            Arch::Arm => apk::Target::ArmV7a,
             Arch::Arm64 => apk::Target::Arm64V8a,
             Arch::X64 => apk::Target::X86_64,
+            Arch::X86 => apk::Target::X86,
         }
     }
 
@@ -288,21 +313,35 @@ impl CompileTarget {
         assert_eq!(self.platform(), Platform::Android);
         match self.arch() {
             Arch::Arm64 => "aarch64-linux-android",
-            //Arch::Arm => "arm-linux-androideabi",
-            //Arch::X86 => "i686-linux-android",
+            Arch::Arm => "arm-linux-androideabi",
+            Arch::X86 => "i686-linux-android",
+            Arch::X64 => "x86_64-linux-android",
+        }
+    }
+
+    /// Returns the triple used by the NDK's clang
+    pub fn ndk_clang_triple(self) -> &'static str {
+        assert_eq!(self.platform(), Platform::Android);
+        match self.arch() {
+            Arch::Arm64 => "aarch64-linux-android",
+            Arch::Arm => "armv7a-linux-androideabi",
+            Arch::X86 => "i686-linux-android",
             Arch::X64 => "x86_64-linux-android",
         }
     }
 
     pub fn rust_triple(self) -> Result<&'static str> {
         Ok(match (self.arch, self.platform) {
+            (Arch::Arm, Platform::Android) => "armv7-linux-androideabi",
             (Arch::Arm64, Platform::Android) => "aarch64-linux-android",
             (Arch::Arm64, Platform::Ios) => "aarch64-apple-ios",
             (Arch::Arm64, Platform::Linux) => "aarch64-unknown-linux-gnu",
             (Arch::Arm64, Platform::Macos) => "aarch64-apple-darwin",
+            (Arch::X64, Platform::Android) => "x86_64-linux-android",
             (Arch::X64, Platform::Linux) => "x86_64-unknown-linux-gnu",
             (Arch::X64, Platform::Macos) => "x86_64-apple-darwin",
             (Arch::X64, Platform::Windows) => "x86_64-pc-windows-msvc",
+            (Arch::X86, Platform::Android) => "i686-linux-android",
             (arch, platform) => anyhow::bail!(
                 "unsupported arch/platform combination {} {}",
                 arch,
@@ -324,6 +363,14 @@ pub struct BuildArgs {
     build_target: BuildTargetArgs,
     #[clap(flatten)]
     cargo: CargoArgs,
+    /// Builds a flavor declared in the `flavors` section of `manifest.yaml`.
+    /// Flavors are built into separate directories.
+    #[clap(long)]
+    flavor: Option<String>,
+    /// Defines a placeholder like `--define key=value`, which replaces
+    /// `${key}` in `manifest.yaml`. Can be passed multiple times.
+    #[clap(long = "define")]
+    defines: Vec<String>,
     /// Use verbose output
     #[clap(long, short)]
     verbose: bool,
@@ -369,7 +416,7 @@ pub struct BuildTargetArgs {
     #[clap(long, conflicts_with = "device")]
     platform: Option<Platform>,
     /// Build artifacts for target arch. Can be one of
-    /// `arm64` or `x64`.
+    /// `arm`, `arm64`, `x64` or `x86`.
     #[clap(long, requires = "platform")]
     arch: Option<Arch>,
     /// Build artifacts for target device. To find the device
@@ -385,10 +432,22 @@ pub struct BuildTargetArgs {
     /// `apple`, `microsoft`, `play` or `sideload`.
     #[clap(long, conflicts_with = "device", conflicts_with = "format")]
     store: Option<Store>,
-    /// Path to a PEM encoded RSA2048 signing key and certificate
-    /// used to sign artifacts.
+    /// Path to a signing key and certificate used to sign artifacts.
+    /// Can be a PEM file with an optionally encrypted PKCS#8 key, a
+    /// PKCS#12 keystore or a Java keystore. The password of encrypted
+    /// keys is read from `X_KEYSTORE_PASSWORD` or prompted for.
     #[clap(long)]
     pem: Option<PathBuf>,
+    /// Alias of the key to use when the keystore passed with `--pem`
+    /// contains multiple keys.
+    #[clap(long, requires = "pem")]
+    key_alias: Option<String>,
+    /// Path to a signing key and certificate previously used to sign
+    /// apks. Can be passed multiple times, oldest first, to rotate to
+    /// the signing key passed with `--pem`. The password of encrypted
+    /// keys is read from `X_ROTATE_FROM_PASSWORD` or prompted for.
+    #[clap(long)]
+    rotate_from: Vec<PathBuf>,
     /// Path to an apple provisioning profile.
     #[clap(long)]
     provisioning_profile: Option<PathBuf>,
@@ -404,12 +463,27 @@ impl BuildTargetArgs {
     pub fn build_target(self) -> Result<BuildTarget> {
         let signer = if let Some(pem) = self.pem.as_ref() {
             anyhow::ensure!(pem.exists(), "pem file doesn't exist {}", pem.display());
-            Some(Signer::from_path(pem)?)
+            Some(load_signer(
+                pem,
+                self.key_alias.as_deref(),
+                "X_KEYSTORE_PASSWORD",
+            )?)
         } else if let Ok(pem) = std::env::var("X_PEM") {
-            Some(Signer::new(&pem)?)
+            Some(Signer::from_keystore(pem.as_bytes(), None, &mut || {
+                keystore_password("X_KEYSTORE_PASSWORD", "keystore password: ")
+            })?)
         } else {
             None
         };
+        anyhow::ensure!(
+            signer.is_some() || self.rotate_from.is_empty(),
+            "rotating the signing key requires `--pem`"
+        );
+        let mut previous_signers = Vec::with_capacity(self.rotate_from.len());
+        for pem in &self.rotate_from {
+            anyhow::ensure!(pem.exists(), "pem file doesn't exist {}", pem.display());
+            previous_signers.push(load_signer(pem, None, "X_ROTATE_FROM_PASSWORD")?);
+        }
         let store = self.store;
         let device = if self.platform.is_none() && store.is_none() && self.device.is_none() {
             Some(Device::host())
@@ -436,7 +510,7 @@ impl BuildTargetArgs {
             match store {
                 Store::Apple => vec![Arch::X64, Arch::Arm64],
                 Store::Microsoft => vec![Arch::X64],
-                Store::Play => vec![Arch::Arm64],
+                Store::Play => vec![Arch::Arm, Arch::Arm64, Arch::X86, Arch::X64],
                 Store::Sideload => anyhow::bail!("sideload store requires arch arg"),
             }
         } else if let Some(device) = device.as_ref() {
@@ -453,6 +527,11 @@ impl BuildTargetArgs {
             format
         } else if store == Some(Store::Play) {
             Format::Aab
+        } else if platform == Platform::Android
+            && (device.is_some() || store == Some(Store::Sideload))
+        {
+            // app bundles can't be installed on a device
+            Format::Apk
         } else {
             Format::platform_default(platform, opt)
         };
@@ -487,12 +566,31 @@ impl BuildTargetArgs {
             device,
             store,
             signer,
+            previous_signers,
             provisioning_profile,
             notarization_key_and_issuer,
         })
     }
 }
 
+/// Loads a keystore, reading its password from the `password_var`
+/// environment variable or prompting for it.
+fn load_signer(path: &Path, alias: Option<&str>, password_var: &str) -> Result<Signer> {
+    let bytes = std::fs::read(path)?;
+    let prompt = format!("password of {}: ", path.display());
+    Signer::from_keystore(&bytes, alias, &mut || {
+        keystore_password(password_var, &prompt)
+    })
+    .map_err(|err| anyhow::anyhow!("failed to load {}: {}", path.display(), err))
+}
+
+fn keystore_password(password_var: &str, prompt: &str) -> Result<String> {
+    if let Ok(password) = std::env::var(password_var) {
+        return Ok(password);
+    }
+    Ok(rpassword::prompt_password(prompt)?)
+}
+
 #[derive(Clone, Debug)]
 pub struct BuildTarget {
     opt: Opt,
@@ -502,6 +600,7 @@ pub struct BuildTarget {
     device: Option<Device>,
     store: Option<Store>,
     signer: Option<Signer>,
+    previous_signers: Vec<Signer>,
     provisioning_profile: Option<Vec<u8>>,
     notarization_key_and_issuer: Option<(String, String)>,
 }
@@ -548,6 +647,11 @@ impl BuildTarget {
         self.signer.as_ref()
     }
 
+    /// Signers to rotate from, oldest first.
+    pub fn previous_signers(&self) -> &[Signer] {
+        &self.previous_signers
+    }
+
     pub fn provisioning_profile(&self) -> Option<&[u8]> {
         self.provisioning_profile.as_deref()
     }
@@ -565,6 +669,9 @@ pub struct BuildEnv {
     build_dir: PathBuf,
     cache_dir: PathBuf,
     icon: Option<PathBuf>,
+    android_res: Option<PathBuf>,
+    android_java: Option<PathBuf>,
+    android_adaptive_icon: Option<AdaptiveIcon>,
     target_file: PathBuf,
     cargo: Cargo,
     pubspec: PathBuf,
@@ -579,8 +686,21 @@ impl BuildEnv {
         let verbose = args.verbose;
         let offline = args.cargo.offline;
         let cargo = args.cargo.cargo()?;
-        let build_target = args.build_target.build_target()?;
-        let build_dir = cargo.target_dir().join("x");
+        let mut build_target = args.build_target.build_target()?;
+        let x_dir = cargo.target_dir().join("x");
+        let build_dir = if let Some(flavor) = args.flavor.as_deref() {
+            anyhow::ensure!(
+                !flavor.is_empty()
+                    && flavor
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                "invalid flavor name {}",
+                flavor
+            );
+            x_dir.join("flavors").join(flavor)
+        } else {
+            x_dir.clone()
+        };
         let cache_dir = dirs::cache_dir().unwrap().join("x");
         let pubspec = cargo.root_dir().join("pubspec.yaml");
         let flutter = if pubspec.exists() {
@@ -592,20 +712,56 @@ impl BuildEnv {
         } else {
             None
         };
-        let (config, mut manifest) = if flutter.is_some() {
-            let config = &pubspec;
-            let manifest = config.parent().unwrap().join("manifest.yaml");
-            (Config::pubspec_yaml(&pubspec)?, Manifest::parse(&manifest)?)
+        if flutter.is_some()
+            && build_target.platform == Platform::Android
+            && build_target.opt == Opt::Release
+            && build_target.archs.contains(&Arch::X86)
+        {
+            // flutter doesn't provide a release engine for x86
+            anyhow::ensure!(
+                build_target.archs.len() > 1,
+                "flutter doesn't support release builds for x86"
+            );
+            log::warn!("skipping x86, flutter doesn't support release builds for it");
+            build_target.archs.retain(|arch| *arch != Arch::X86);
+        }
+        let mut defines = Vec::with_capacity(args.defines.len());
+        for define in &args.defines {
+            let (key, value) = define.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("invalid define {}, expected `key=value`", define)
+            })?;
+            defines.push((key.to_string(), value.to_string()));
+        }
+        let (config, manifest) = if flutter.is_some() {
+            let manifest = pubspec.parent().unwrap().join("manifest.yaml");
+            (Config::pubspec_yaml(&pubspec)?, manifest)
         } else {
             let config = cargo.manifest();
             let manifest = config.parent().unwrap().join("manifest.yaml");
-            (Config::cargo_toml(config)?, Manifest::parse(&manifest)?)
+            (Config::cargo_toml(config)?, manifest)
         };
-        manifest.apply_config(&config, build_target.opt(), flutter.is_some());
+        let mut manifest = Manifest::parse(
+            &manifest,
+            &config,
+            build_target.opt(),
+            args.flavor.as_deref(),
+            &defines,
+        )?;
+        let android_java = manifest.android_java(cargo.root_dir());
+        manifest.apply_config(
+            &config,
+            build_target.platform(),
+            build_target.opt(),
+            flutter.is_some(),
+            android_java.is_some(),
+        );
+        manifest.apply_version(&config, cargo.root_dir(), &build_target)?;
         let target_file = manifest.target_file(cargo.root_dir(), build_target.platform());
         let icon = manifest
             .icon(build_target.platform())
             .map(|icon| cargo.root_dir().join(icon));
+        let android_res = manifest.android_res(cargo.root_dir());
+        let android_adaptive_icon = manifest.android_adaptive_icon(cargo.root_dir())?;
         let name = config.name;
         Ok(Self {
             name,
@@ -613,6 +769,9 @@ impl BuildEnv {
             pubspec,
             target_file,
             icon,
+            android_res,
+            android_java,
+            android_adaptive_icon,
             cargo,
             flutter,
             manifest,
@@ -701,6 +860,18 @@ impl BuildEnv {
         self.icon.as_deref()
     }
 
+    pub fn android_res(&self) -> Option<&Path> {
+        self.android_res.as_deref()
+    }
+
+    pub fn android_java(&self) -> Option<&Path> {
+        self.android_java.as_deref()
+    }
+
+    pub fn android_adaptive_icon(&self) -> Option<&AdaptiveIcon> {
+        self.android_adaptive_icon.as_ref()
+    }
+
     pub fn cargo(&self) -> &Cargo {
         &self.cargo
     }
@@ -717,6 +888,23 @@ impl BuildEnv {
         self.manifest().android().sdk.target_sdk_version.unwrap()
     }
 
+    /// Rust file containing the resource ids of the app, which is available
+    /// to the crate as `XBUILD_ANDROID_RESOURCES`.
+    pub fn android_resource_ids(&self) -> PathBuf {
+        if let Some(path) = self.manifest().android_resource_ids() {
+            return self.root_dir().join(path);
+        }
+        self.platform_dir().join("android_resources.rs")
+    }
+
+    /// File the ids of the app resources are kept in between builds.
+    pub fn android_stable_ids(&self) -> PathBuf {
+        if let Some(path) = self.manifest().android_stable_ids() {
+            return self.root_dir().join(path);
+        }
+        self.platform_dir().join("resource_ids.txt")
+    }
+
     pub fn android_jar(&self) -> PathBuf {
         self.cache_dir()
             .join("Android.sdk")
@@ -761,6 +949,11 @@ impl BuildEnv {
 
     pub fn cargo_build(&self, target: CompileTarget, target_dir: &Path) -> Result<CargoBuild> {
         let mut cargo = self.cargo.build(target, target_dir)?;
+        let features = self.manifest().cargo_features(target.platform());
+        if !features.is_empty() {
+            cargo.arg("--features");
+            cargo.arg(&features.join(","));
+        }
         if target.platform() == Platform::Linux {
             cargo.add_link_arg("-Wl,-rpath");
             cargo.add_link_arg("-Wl,$ORIGIN/lib");
@@ -769,6 +962,10 @@ impl BuildEnv {
             let ndk = self.android_ndk();
             let target_sdk_version = self.manifest().android().sdk.target_sdk_version.unwrap();
             cargo.use_android_ndk(&ndk, target_sdk_version)?;
+            cargo.env(
+                "XBUILD_ANDROID_RESOURCES",
+                self.android_resource_ids().to_str().unwrap(),
+            );
         }
         if target.platform() == Platform::Windows {
             let sdk = self.windows_sdk();
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 use xbuild::{command, BuildArgs, BuildEnv};
 
 #[derive(Parser)]
@@ -55,6 +56,14 @@ enum Commands {
         #[clap(flatten)]
         args: BuildArgs,
     },
+    /// Show the manifest, signatures and files of a built artifact
+    Inspect {
+        /// Path to an apk, aab, msix, dmg, AppImage, ipa or app bundle
+        path: PathBuf,
+        /// Print the summary as json
+        #[clap(long)]
+        json: bool,
+    },
     /*Attach {
         #[clap(long)]
         url: String,
@@ -90,12 +99,14 @@ impl Commands {
                 let env = BuildEnv::new(args)?;
                 command::build(&env)?;
                 command::lldb(&env)?;
-            } /*Self::Attach {
-                  url,
-                  root_dir,
-                  target_file,
-                  host_vmservice_port,
-              } => command::attach(&url, &root_dir, &target_file, host_vmservice_port)?,*/
+            }
+            Self::Inspect { path, json } => command::inspect(&path, json)?,
+            /*Self::Attach {
+                url,
+                root_dir,
+                target_file,
+                host_vmservice_port,
+            } => command::attach(&url, &root_dir, &target_file, host_vmservice_port)?,*/
         }
         Ok(())
     }
@@ -0,0 +1,214 @@
+//! Loading signing keys from PEM files, PKCS#12 and Java keystores.
+use crate::Signer;
+use anyhow::Result;
+use byteorder::{BigEndian, ReadBytesExt};
+use p12_keystore::{KeyStore, KeyStoreEntry};
+use rasn::prelude::*;
+use rasn_pkix::AlgorithmIdentifier;
+use sha1::{Digest, Sha1};
+use std::io::{Cursor, Read};
+
+const JKS_MAGIC: u32 = 0xfeed_feed;
+const JCEKS_MAGIC: u32 = 0xcece_cece;
+const JKS_PRIVATE_KEY: u32 = 1;
+const JKS_TRUSTED_CERT: u32 = 2;
+/// Sun's proprietary key protection algorithm.
+const JKS_KEY_PROTECTOR: ConstOid = ConstOid(&[1, 3, 6, 1, 4, 1, 42, 2, 17, 1, 1]);
+
+/// Loads a signer from a PEM file, a PKCS#12 keystore or a Java keystore.
+///
+/// `password` is only called when the key is encrypted. When the keystore
+/// contains multiple keys, `alias` selects the key, otherwise the first key
+/// is used. Aliases are ignored for PEM files.
+pub fn load(
+    bytes: &[u8],
+    alias: Option<&str>,
+    password: &mut dyn FnMut() -> Result<String>,
+) -> Result<Signer> {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or_default();
+    if bytes[start..].starts_with(b"-----BEGIN") {
+        return load_pem(std::str::from_utf8(bytes)?, password);
+    }
+    if bytes.len() >= 4 {
+        match u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) {
+            JKS_MAGIC => return load_jks(bytes, alias, &password()?),
+            JCEKS_MAGIC => {
+                anyhow::bail!("JCEKS keystores are not supported, convert it to PKCS#12")
+            }
+            _ => {}
+        }
+    }
+    load_pkcs12(bytes, alias, &password()?)
+}
+
+fn load_pem(pem: &str, password: &mut dyn FnMut() -> Result<String>) -> Result<Signer> {
+    let pems = pem::parse_many(pem)?;
+    let encrypted = if let Some(key) = pems.iter().find(|pem| pem.tag == "ENCRYPTED PRIVATE KEY") {
+        key
+    } else {
+        return Signer::new(pem);
+    };
+    let key = pkcs8::EncryptedPrivateKeyInfo::try_from(&encrypted.contents[..])
+        .map_err(|err| anyhow::anyhow!("{}", err))?
+        .decrypt(password()?)
+        .map_err(|_| anyhow::anyhow!("failed to decrypt private key, wrong password?"))?;
+    let cert = if let Some(cert) = pems.iter().find(|pem| pem.tag == "CERTIFICATE") {
+        cert.contents.clone()
+    } else {
+        anyhow::bail!("no certificate found");
+    };
+    Signer::from_der(key.as_bytes().to_vec(), &cert)
+}
+
+fn load_pkcs12(bytes: &[u8], alias: Option<&str>, password: &str) -> Result<Signer> {
+    let keystore = KeyStore::from_pkcs12(bytes, password)
+        .map_err(|err| anyhow::anyhow!("failed to open PKCS#12 keystore: {}", err))?;
+    let (alias, chain) = if let Some(alias) = alias {
+        match keystore.entry(alias) {
+            Some(KeyStoreEntry::PrivateKeyChain(chain)) => (alias, chain),
+            Some(_) => anyhow::bail!("keystore entry {} is not a private key", alias),
+            None => anyhow::bail!("keystore doesn't contain alias {}", alias),
+        }
+    } else {
+        keystore
+            .private_key_chain()
+            .ok_or_else(|| anyhow::anyhow!("keystore doesn't contain a private key"))?
+    };
+    let cert = chain
+        .chain()
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("keystore entry {} has no certificate", alias))?;
+    Signer::from_der(chain.key().to_vec(), cert.as_der())
+}
+
+#[derive(AsnType, Decode)]
+struct EncryptedPrivateKeyInfo {
+    algorithm: AlgorithmIdentifier,
+    encrypted_data: OctetString,
+}
+
+struct JksKey {
+    alias: String,
+    key: Vec<u8>,
+    chain: Vec<Vec<u8>>,
+}
+
+fn load_jks(bytes: &[u8], alias: Option<&str>, password: &str) -> Result<Signer> {
+    anyhow::ensure!(bytes.len() > 20, "invalid JKS keystore");
+    let password = password
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes())
+        .collect::<Vec<_>>();
+    let (data, mac) = bytes.split_at(bytes.len() - 20);
+    let mut hasher = Sha1::new();
+    hasher.update(&password);
+    hasher.update(b"Mighty Aphrodite");
+    hasher.update(data);
+    anyhow::ensure!(
+        hasher.finalize().as_slice() == mac,
+        "keystore was tampered with or the password is incorrect"
+    );
+
+    let mut r = Cursor::new(data);
+    let _magic = r.read_u32::<BigEndian>()?;
+    let version = r.read_u32::<BigEndian>()?;
+    anyhow::ensure!(
+        version == 1 || version == 2,
+        "unsupported JKS version {}",
+        version
+    );
+    let count = r.read_u32::<BigEndian>()?;
+    let mut keys = vec![];
+    for _ in 0..count {
+        match r.read_u32::<BigEndian>()? {
+            JKS_PRIVATE_KEY => {
+                let alias = read_jks_string(&mut r)?;
+                let _timestamp = r.read_u64::<BigEndian>()?;
+                let key = read_jks_bytes(&mut r)?;
+                let chain_len = r.read_u32::<BigEndian>()?;
+                let mut chain = Vec::with_capacity(chain_len as usize);
+                for _ in 0..chain_len {
+                    if version == 2 {
+                        read_jks_string(&mut r)?;
+                    }
+                    chain.push(read_jks_bytes(&mut r)?);
+                }
+                keys.push(JksKey { alias, key, chain });
+            }
+            JKS_TRUSTED_CERT => {
+                read_jks_string(&mut r)?;
+                let _timestamp = r.read_u64::<BigEndian>()?;
+                if version == 2 {
+                    read_jks_string(&mut r)?;
+                }
+                read_jks_bytes(&mut r)?;
+            }
+            tag => anyhow::bail!("unsupported JKS entry {}", tag),
+        }
+    }
+
+    let entry = if let Some(alias) = alias {
+        // aliases are stored in lower case
+        let alias = alias.to_lowercase();
+        keys.iter()
+            .find(|key| key.alias == alias)
+            .ok_or_else(|| anyhow::anyhow!("keystore doesn't contain alias {}", alias))?
+    } else {
+        keys.first()
+            .ok_or_else(|| anyhow::anyhow!("keystore doesn't contain a private key"))?
+    };
+    let info = rasn::der::decode::<EncryptedPrivateKeyInfo>(&entry.key)
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+    let algorithm: &[u32] = &info.algorithm.algorithm;
+    anyhow::ensure!(
+        algorithm == JKS_KEY_PROTECTOR.0,
+        "unsupported JKS key protection algorithm"
+    );
+    let key = decrypt_jks_key(&info.encrypted_data, &password)?;
+    let cert = entry
+        .chain
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("keystore entry {} has no certificate", entry.alias))?;
+    Signer::from_der(key, cert)
+}
+
+/// Decrypts a key protected using a SHA-1 based key stream.
+fn decrypt_jks_key(data: &[u8], password: &[u8]) -> Result<Vec<u8>> {
+    anyhow::ensure!(data.len() > 40, "invalid JKS key");
+    let (salt, data) = data.split_at(20);
+    let (encrypted, check) = data.split_at(data.len() - 20);
+    let mut key = Vec::with_capacity(encrypted.len());
+    let mut digest = salt.to_vec();
+    for chunk in encrypted.chunks(20) {
+        let mut hasher = Sha1::new();
+        hasher.update(password);
+        hasher.update(&digest);
+        digest = hasher.finalize().to_vec();
+        key.extend(chunk.iter().zip(&digest).map(|(a, b)| a ^ b));
+    }
+    let mut hasher = Sha1::new();
+    hasher.update(password);
+    hasher.update(&key);
+    anyhow::ensure!(
+        hasher.finalize().as_slice() == check,
+        "failed to decrypt private key, wrong password?"
+    );
+    Ok(key)
+}
+
+fn read_jks_string(r: &mut impl Read) -> Result<String> {
+    let len = r.read_u16::<BigEndian>()?;
+    let mut buf = vec![0; len as usize];
+    r.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn read_jks_bytes(r: &mut impl Read) -> Result<Vec<u8>> {
+    let len = r.read_u32::<BigEndian>()?;
+    let mut buf = vec![0; len as usize];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}